@@ -0,0 +1,247 @@
+//! Evaluates a query's plan one operator at a time, to report how many rows each operator outputs
+//! and an estimate of how long it takes to produce them (i.e., `EXPLAIN ANALYZE`).
+
+use crate::error::CLIErrors;
+use crate::evaluate::Compiler;
+use crate::explain::{plan_inputs, plan_to_text_with};
+use crate::timing::timed;
+use partiql_eval::env::basic::MapBindings;
+use partiql_logical::{BindingsOp, LogicalPlan, OpId};
+use partiql_parser::Parsed;
+use partiql_value::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+/// The rows output by a plan operator and the time taken to produce them, as estimated from a
+/// separate evaluation of the operator and those upstream of it.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct OpStats {
+    pub rows: usize,
+    /// Time taken to evaluate the operator, including the time taken by its inputs
+    pub elapsed: Duration,
+    /// Time taken to evaluate the operator, excluding the time taken by its inputs
+    pub self_elapsed: Duration,
+}
+
+impl OpStats {
+    /// The stats as fields of an operator's label.
+    pub(crate) fn fields(&self) -> Vec<String> {
+        vec![
+            format!("rows: {}", self.rows),
+            format!(
+                "est. time: {:?} (self {:?})",
+                micros(self.elapsed),
+                micros(self.self_elapsed)
+            ),
+        ]
+    }
+}
+
+/// Truncates a duration to whole microseconds, finer than which single evaluations are noise.
+fn micros(duration: Duration) -> Duration {
+    Duration::from_micros(duration.as_micros() as u64)
+}
+
+/// A logical plan, along with the stats of evaluating each of its operators.
+#[derive(Debug)]
+pub struct AnalyzedPlan {
+    pub plan: LogicalPlan<BindingsOp>,
+    pub stats: HashMap<OpId, OpStats>,
+}
+
+impl AnalyzedPlan {
+    /// The share of the plan's total self time that was spent in operator `id`.
+    #[cfg(feature = "visualize")]
+    pub(crate) fn share(&self, id: OpId) -> f32 {
+        let total: Duration = self.stats.values().map(|s| s.self_elapsed).sum();
+        match self.stats.get(&id) {
+            Some(stats) if !total.is_zero() => {
+                stats.self_elapsed.as_secs_f32() / total.as_secs_f32()
+            }
+            _ => 0.0,
+        }
+    }
+}
+
+/// Evaluates `plan` against `bindings`, recording the stats of each of its operators.
+///
+/// The evaluator doesn't expose its operators, so each operator is instead evaluated as the sink
+/// of a plan made of it and the operators upstream of it. An operator's `elapsed` time therefore
+/// includes the time taken by its inputs, whose own `elapsed` times are subtracted for its
+/// `self_elapsed` time. Inputs are re-evaluated for each operator they feed, so analyzing a plan
+/// takes longer than evaluating it. The whole plan is evaluated once beforehand, so that the first
+/// operators measured aren't charged for warming up.
+///
+/// As each operator is timed on a run of its own, the times are estimates: an operator's inputs
+/// may take longer on their runs than on its run, leaving it no `self_elapsed` time. The operators
+/// of subqueries are evaluated as part of the operators that use them, and aren't measured.
+pub fn analyze_plan(
+    compiler: &Compiler,
+    parsed: &Parsed,
+    plan: LogicalPlan<BindingsOp>,
+    bindings: &MapBindings<Value>,
+) -> Result<AnalyzedPlan, CLIErrors> {
+    let (inputs, _) = plan_inputs(&plan);
+
+    let eval = compiler.compile(parsed, &plan)?;
+    compiler.evaluate(parsed, eval, bindings.clone())?;
+
+    let mut totals = HashMap::new();
+    for (id, _) in plan.operators_by_id() {
+        let upstream = upstream_plan(&plan, &inputs, id);
+        let eval = compiler.compile(parsed, &upstream)?;
+        let (evaluated, elapsed) = timed(|| compiler.evaluate(parsed, eval, bindings.clone()));
+        let rows = evaluated?.result.iter().count();
+        totals.insert(id, (rows, elapsed));
+    }
+
+    let stats = totals
+        .iter()
+        .map(|(id, (rows, elapsed))| {
+            let inputs_elapsed: Duration = inputs
+                .get(id)
+                .into_iter()
+                .flatten()
+                .map(|(_, input)| totals[input].1)
+                .sum();
+            let stats = OpStats {
+                rows: *rows,
+                elapsed: *elapsed,
+                self_elapsed: elapsed.saturating_sub(inputs_elapsed),
+            };
+            (*id, stats)
+        })
+        .collect();
+    Ok(AnalyzedPlan { plan, stats })
+}
+
+/// A plan made of operator `id` of `plan` and the operators upstream of it, output to a sink.
+fn upstream_plan(
+    plan: &LogicalPlan<BindingsOp>,
+    inputs: &HashMap<OpId, Vec<(u8, OpId)>>,
+    id: OpId,
+) -> LogicalPlan<BindingsOp> {
+    fn copy(
+        plan: &LogicalPlan<BindingsOp>,
+        inputs: &HashMap<OpId, Vec<(u8, OpId)>>,
+        id: OpId,
+        upstream: &mut LogicalPlan<BindingsOp>,
+        copied: &mut HashMap<OpId, OpId>,
+    ) -> OpId {
+        if let Some(copy) = copied.get(&id) {
+            return *copy;
+        }
+        let op = plan.operator(id).expect("plan operator").clone();
+        let dst = upstream.add_operator(op);
+        copied.insert(id, dst);
+        for (branch, input) in inputs.get(&id).into_iter().flatten() {
+            let src = copy(plan, inputs, *input, upstream, copied);
+            upstream.add_flow_with_branch_num(src, dst, *branch);
+        }
+        dst
+    }
+
+    let mut upstream = LogicalPlan::new();
+    let top = copy(plan, inputs, id, &mut upstream, &mut HashMap::new());
+    if !matches!(plan.operator(id), Some(BindingsOp::Sink)) {
+        let sink = upstream.add_operator(BindingsOp::Sink);
+        upstream.add_flow(top, sink);
+    }
+    upstream
+}
+
+/// Renders an analyzed plan as an indented tree of operators, each followed by its stats, and a
+/// note on how the stats were measured.
+pub fn analyzed_plan_to_text(analyzed: &AnalyzedPlan) -> String {
+    let mut text = plan_to_text_with(&analyzed.plan, |id| {
+        analyzed
+            .stats
+            .get(&id)
+            .map(OpStats::fields)
+            .unwrap_or_default()
+    });
+    text.push_str(ESTIMATES_NOTE);
+    text
+}
+
+/// How the stats of an analyzed plan were measured.
+const ESTIMATES_NOTE: &str =
+    "\nTimes are estimates, each from a separate evaluation of an operator \
+    and those upstream of it; operators in subqueries aren't measured.\n";
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+
+    /// The rows each operator of `query` outputs over the globals of `env`, by the kind of operator,
+    /// in the order of the plan's operators.
+    fn rows(query: &str, env: &str) -> Vec<(String, usize)> {
+        let bindings = match evaluate(env, MapBindings::default()).unwrap().result {
+            Value::Tuple(globals) => MapBindings::from(*globals),
+            _ => panic!("env isn't a struct"),
+        };
+        let compiler = Compiler::default();
+        let parsed = compiler.parse(query).unwrap();
+        let plan = compiler.plan(&parsed).unwrap();
+        let analyzed = analyze_plan(&compiler, &parsed, plan, &bindings).unwrap();
+        let mut rows: Vec<_> = analyzed
+            .plan
+            .operators_by_id()
+            .map(|(id, op)| {
+                let kind = format!("{op:?}");
+                let kind = kind.split('(').next().unwrap().to_string();
+                (kind, analyzed.stats[&id].rows)
+            })
+            .collect();
+        rows.sort();
+        rows
+    }
+
+    fn counts(expected: &[(&str, usize)]) -> Vec<(String, usize)> {
+        let mut counts: Vec<_> = expected.iter().map(|(k, n)| (k.to_string(), *n)).collect();
+        counts.sort();
+        counts
+    }
+
+    #[test]
+    fn rows_are_counted_per_operator() {
+        let env = "{'t': <<{'a': 1}, {'a': 2}, {'a': 3}, {'a': 4}>>}";
+        assert_eq!(
+            rows("SELECT a FROM t WHERE a > 1 LIMIT 2", env),
+            counts(&[
+                ("Scan", 4),
+                ("Filter", 3),
+                ("LimitOffset", 2),
+                ("Project", 2),
+                ("Sink", 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn rows_are_counted_for_each_side_of_a_join() {
+        let env = "{'t': <<{'a': 1}, {'a': 2}>>, 'u': <<{'b': 2}, {'b': 2}, {'b': 3}>>}";
+        assert_eq!(
+            rows("SELECT t.a, u.b FROM t JOIN u ON t.a = u.b", env),
+            counts(&[
+                ("Scan", 2),
+                ("Scan", 3),
+                ("Join", 2),
+                ("Project", 2),
+                ("Sink", 2),
+            ])
+        );
+    }
+
+    #[test]
+    fn text_notes_that_times_are_estimates() {
+        let compiler = Compiler::default();
+        let parsed = compiler.parse("SELECT a FROM t").unwrap();
+        let plan = compiler.plan(&parsed).unwrap();
+        let analyzed = analyze_plan(&compiler, &parsed, plan, &MapBindings::default()).unwrap();
+        let text = analyzed_plan_to_text(&analyzed);
+        assert!(text.contains("est. time: "), "{text}");
+        assert!(text.ends_with(ESTIMATES_NOTE), "{text}");
+    }
+}
@@ -5,30 +5,136 @@ use clap::{Parser, Subcommand, ValueEnum};
 pub struct Args {
     #[clap(subcommand)]
     pub command: Commands,
+
+    /// How errors are reported on stderr
+    #[clap(long = "error-format", value_enum, global = true, default_value_t)]
+    pub error_format: ErrorFormat,
+
+    /// Warn about the MISSING values evaluation leaves and the type mismatches that cause them,
+    /// which evaluates the query a second time
+    #[clap(long = "warnings", global = true)]
+    pub warnings: bool,
+
+    /// Fail, rather than only warn, on any warning; implies `--warnings`
+    #[clap(long = "warnings-as-errors", global = true)]
+    pub warnings_as_errors: bool,
 }
 
 #[derive(Subcommand)]
 pub enum Commands {
-    #[cfg(feature = "visualize")]
     /// Dump the AST for a query
     Ast {
         #[clap(short = 'T', long = "format", value_enum)]
         format: Format,
 
+        /// Graph layout engine for the `dot`, `svg`, `png` and `display` formats
+        #[cfg(feature = "visualize")]
+        #[clap(long = "layout", value_enum, default_value_t)]
+        layout: Layout,
+
+        /// How the `display` format shows graphs in the terminal
+        #[cfg(feature = "visualize")]
+        #[clap(flatten)]
+        display: DisplayArgs,
+
+        /// Dump the AST as of this pass, or each pass in turn; passes other than `parsed` need the
+        /// `text` format
+        #[clap(long = "pass", value_enum, default_value_t)]
+        pass: AstPass,
+
+        /// Show each pass as the changes from the pass before it
+        #[clap(long = "diff")]
+        diff: bool,
+
         /// Query to parse
         #[clap(value_parser)]
         query: String,
     },
-    #[cfg(feature = "visualize")]
     /// Dump the Plan for a query
     Plan {
         #[clap(short = 'T', long = "format", value_enum)]
         format: Format,
 
+        /// Graph layout engine for the `dot`, `svg`, `png` and `display` formats
+        #[cfg(feature = "visualize")]
+        #[clap(long = "layout", value_enum, default_value_t)]
+        layout: Layout,
+
+        /// How the `display` format shows graphs in the terminal
+        #[cfg(feature = "visualize")]
+        #[clap(flatten)]
+        display: DisplayArgs,
+
         /// Query to parse
         #[clap(value_parser)]
         query: String,
     },
+    /// Dump the compiled evaluation Plan for a query
+    EvalPlan {
+        #[clap(short = 'T', long = "format", value_enum)]
+        format: Format,
+
+        /// Graph layout engine for the `dot`, `svg`, `png` and `display` formats
+        #[cfg(feature = "visualize")]
+        #[clap(long = "layout", value_enum, default_value_t)]
+        layout: Layout,
+
+        /// How the `display` format shows graphs in the terminal
+        #[cfg(feature = "visualize")]
+        #[clap(flatten)]
+        display: DisplayArgs,
+
+        /// Query to compile
+        #[clap(value_parser)]
+        query: String,
+    },
+    /// Compare the Plans for two queries
+    PlanDiff {
+        #[clap(short = 'T', long = "format", value_enum)]
+        format: Format,
+
+        /// Graph layout engine for the `dot`, `svg`, `png` and `display` formats
+        #[cfg(feature = "visualize")]
+        #[clap(long = "layout", value_enum, default_value_t)]
+        layout: Layout,
+
+        /// How the `display` format shows graphs in the terminal
+        #[cfg(feature = "visualize")]
+        #[clap(flatten)]
+        display: DisplayArgs,
+
+        /// Query whose plan is compared against
+        #[clap(value_parser)]
+        query_a: String,
+
+        /// Query whose plan is compared
+        #[clap(value_parser)]
+        query_b: String,
+    },
+    /// Evaluate the query, and dump its Plan annotated with each operator's rows and estimated
+    /// time, each measured by a separate evaluation of the operator and those upstream of it
+    ExplainAnalyze {
+        #[clap(short = 'T', long = "format", value_enum)]
+        format: Format,
+
+        /// Graph layout engine for the `dot`, `svg`, `png` and `display` formats
+        #[cfg(feature = "visualize")]
+        #[clap(long = "layout", value_enum, default_value_t)]
+        layout: Layout,
+
+        /// How the `display` format shows graphs in the terminal
+        #[cfg(feature = "visualize")]
+        #[clap(flatten)]
+        display: DisplayArgs,
+
+        /// Optional environment file (.env or .ion)
+        #[clap(short = 'E', long = "environment")]
+        environment: Option<String>,
+
+        /// Query to evaluate
+        #[clap(value_parser)]
+        query: String,
+    },
     /// Evaluate the query with the optional global environment
     Eval {
         /// Query to evaluate
@@ -37,6 +143,25 @@ pub enum Commands {
         /// Output format
         #[clap(short = 'f', long = "format", value_enum, default_value_t=OutputFormat::Partiql)]
         output: OutputFormat,
+        /// How nested values are rendered in `table`, `html` and `markdown` output; `markdown` cells
+        /// are single lines, so `pretty` and `expand` render as `compact` there
+        #[clap(long = "table-nesting", value_enum, default_value_t=TableNesting::Pretty)]
+        table_nesting: TableNesting,
+        /// Whether `table` output is printed as vertical records
+        #[clap(short = 'x', long = "expanded", value_enum, default_value_t=Expanded::Off)]
+        expanded: Expanded,
+        /// Include the query text and timing as a caption in `markdown` and `html` output
+        #[clap(long = "caption")]
+        caption: bool,
+        /// Report parse, plan, compile and evaluate durations and result cardinality on stderr
+        #[clap(
+            long = "timing",
+            value_enum,
+            num_args = 0..=1,
+            require_equals = true,
+            default_missing_value = "human"
+        )]
+        timing: Option<TimingFormat>,
         /// Optional environment file (.env or .ion)
         #[clap(short = 'E', long = "environment")]
         environment: Option<String>,
@@ -59,6 +184,58 @@ pub enum OutputFormat {
     IonPretty,
     /// Table
     Table,
+    /// Markdown table
+    Markdown,
+    /// HTML table
+    Html,
+    /// Vertical records, one `key | value` line per attribute
+    Vertical,
+}
+
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TableNesting {
+    /// Nested values pretty printed over multiple lines within a cell
+    #[default]
+    Pretty,
+    /// Nested values printed on a single line within a cell
+    Compact,
+    /// Nested tuples flattened into dotted columns (e.g., `a.b.c`), other nested values compact;
+    /// names containing `.` are quoted (e.g., `"a.b".c`)
+    Flatten,
+    /// Nested tuples and collections rendered as child tables within a cell
+    Expand,
+}
+
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Expanded {
+    /// Always print `table` output as a table
+    #[default]
+    Off,
+    /// Always print `table` output as vertical records
+    On,
+    /// Print `table` output as vertical records when the table is wider than the terminal
+    Auto,
+}
+
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum TimingFormat {
+    /// Human-readable summary
+    Human,
+    /// JSON record, one per line
+    Json,
+    /// Ion Text record, one per line
+    Ion,
+}
+
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ErrorFormat {
+    /// Rendered diagnostics, pointing into the query text
+    #[default]
+    Human,
+    /// JSON record, one per line
+    Json,
+    /// Ion Text record, one per line
+    Ion,
 }
 
 #[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
@@ -67,10 +244,88 @@ pub enum Format {
     Json,
     /// Graphviz dot
     Dot,
-    /// Graphviz svg output
+    /// Rendered svg output
     Svg,
-    /// Graphviz svg rendered to png
+    /// Rendered svg, rasterized to png
     Png,
     /// Display rendered output
     Display,
+    /// Mermaid flowchart
+    Mermaid,
+    /// Interactive html page
+    Html,
+    /// Indented text tree
+    #[value(alias = "tree")]
+    Text,
+}
+
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum AstPass {
+    /// The AST as parsed
+    #[default]
+    Parsed,
+    /// The AST with the names each node infers, consumes and produces, as resolved for lowering
+    NameResolution,
+    /// Each pass in turn
+    All,
+}
+
+#[cfg(feature = "visualize")]
+#[derive(ValueEnum, Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Layout {
+    /// Graphviz `dot` layout, using the system Graphviz library
+    #[cfg(feature = "graphviz")]
+    Graphviz,
+    /// Built-in layered layout
+    Layered,
+}
+
+#[cfg(feature = "visualize")]
+impl Default for Layout {
+    fn default() -> Self {
+        #[cfg(feature = "graphviz")]
+        return Layout::Graphviz;
+        #[cfg(not(feature = "graphviz"))]
+        return Layout::Layered;
+    }
+}
+
+#[cfg(feature = "visualize")]
+#[derive(ValueEnum, Debug, Default, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+pub enum ImageProtocol {
+    /// The first of kitty, iTerm or sixel the terminal supports, or the text tree if none
+    #[default]
+    Auto,
+    /// Kitty graphics protocol
+    Kitty,
+    /// iTerm inline images protocol
+    Iterm,
+    /// Sixel graphics
+    Sixel,
+    /// Colored half-block characters, for any terminal with color support
+    Blocks,
+    /// Text tree, without graphics
+    Text,
+}
+
+/// Options of the `display` format; any that are set override the configuration file's `[display]`
+/// section.
+#[cfg(feature = "visualize")]
+#[derive(clap::Args, Debug, Default, Clone)]
+pub struct DisplayArgs {
+    /// Image protocol used by the `display` format
+    #[clap(long = "protocol", value_enum)]
+    pub protocol: Option<ImageProtocol>,
+
+    /// Maximum width of the `display` format's image, in terminal columns
+    #[clap(long = "max-width")]
+    pub max_width: Option<u32>,
+
+    /// Maximum height of the `display` format's image, in terminal rows
+    #[clap(long = "max-height")]
+    pub max_height: Option<u32>,
+
+    /// Scale of the `display` format's rendering, e.g. `2` for sharper images on high-density displays
+    #[clap(long = "scale")]
+    pub scale: Option<f32>,
 }
@@ -0,0 +1,282 @@
+//! Runs the AST passes that lowering starts with one at a time, to show what each works out about
+//! a query before it is lowered to a plan.
+
+use crate::args::AstPass;
+use crate::ast_tree::{ast_to_tree_with, symbol_primitive_to_label};
+use crate::error::CLIErrors;
+use crate::evaluate::Compiler;
+use partiql_ast_passes::name_resolver::{KeyRegistry, NameLookup, NameRef, Symbol};
+use partiql_common::node::NodeId;
+use partiql_parser::Parsed;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// A query's AST as of a pass, along with the notes that pass and those before it made on each of
+/// its nodes.
+#[derive(Debug, Clone)]
+pub struct AstStage {
+    pub pass: AstPass,
+    pub notes: HashMap<NodeId, Vec<String>>,
+}
+
+impl AstStage {
+    /// The notes on the AST nodes `ids`, without repeats.
+    fn notes(&self, ids: &[NodeId]) -> Vec<String> {
+        let mut notes: Vec<String> = vec![];
+        for note in ids.iter().filter_map(|id| self.notes.get(id)).flatten() {
+            if !notes.contains(note) {
+                notes.push(note.clone());
+            }
+        }
+        notes
+    }
+}
+
+/// Runs each pass over `parsed`, in the order lowering runs them.
+///
+/// Passes only annotate the AST; they don't rewrite it. Aggregations are rewritten (e.g., into
+/// `$__agg_1` variables) while lowering rather than by a pass, so they are only visible in the
+/// query's plan.
+pub fn ast_stages(compiler: &Compiler, parsed: &Parsed) -> Result<Vec<AstStage>, CLIErrors> {
+    let parsed_stage = AstStage {
+        pass: AstPass::Parsed,
+        notes: HashMap::new(),
+    };
+    let registry = compiler.resolve_names(parsed)?;
+    let resolved = AstStage {
+        pass: AstPass::NameResolution,
+        notes: name_resolution_notes(&registry),
+    };
+    Ok(vec![parsed_stage, resolved])
+}
+
+/// The names each AST node infers as its alias, consumes, produces, and has in scope.
+fn name_resolution_notes(registry: &KeyRegistry) -> HashMap<NodeId, Vec<String>> {
+    let mut notes: HashMap<NodeId, Vec<String>> = HashMap::new();
+    for (id, alias) in &registry.aliases {
+        notes
+            .entry(*id)
+            .or_default()
+            .push(format!("alias: {}", symbol_to_label(alias)));
+    }
+    for (id, schema) in &registry.schema {
+        let node = notes.entry(*id).or_default();
+        if !schema.consume.is_empty() {
+            let names: Vec<_> = schema.consume.iter().map(name_ref_to_label).collect();
+            node.push(format!("consumes: {}", names.join(", ")));
+        }
+        if !schema.produce.is_empty() {
+            let names: Vec<_> = schema.produce.iter().map(symbol_to_label).collect();
+            node.push(format!("produces: {}", names.join(", ")));
+        }
+    }
+    for (id, scope) in &registry.in_scope {
+        if !scope.is_empty() {
+            let ids: Vec<_> = scope.iter().map(|id| format!("#{}", id.0)).collect();
+            notes
+                .entry(*id)
+                .or_default()
+                .push(format!("in scope: {}", ids.join(", ")));
+        }
+    }
+    notes
+}
+
+/// A resolved name, as lowering names it.
+fn symbol_to_label(sym: &Symbol) -> String {
+    match sym {
+        Symbol::Known(sym) => symbol_primitive_to_label(sym),
+        Symbol::Unknown(id) => format!("_{id}"),
+    }
+}
+
+/// A consumed name, followed by where it is looked up, in order.
+fn name_ref_to_label(name: &NameRef) -> String {
+    let lookup: Vec<_> = name
+        .lookup
+        .iter()
+        .map(|lookup| match lookup {
+            NameLookup::Local => "local",
+            NameLookup::Global => "global",
+        })
+        .collect();
+    format!(
+        "{} ({})",
+        symbol_primitive_to_label(&name.sym),
+        lookup.join(", ")
+    )
+}
+
+/// Renders the stages of `parsed` selected by `pass` as indented trees of nodes, each followed by
+/// its notes. With `diff`, each stage after the first is instead rendered as the changes from the
+/// stage before it: nodes whose notes changed are marked `~`, and their notes `+` when added or
+/// `-` when removed.
+pub fn ast_stages_to_text(
+    parsed: &Parsed,
+    stages: &[AstStage],
+    pass: AstPass,
+    diff: bool,
+) -> String {
+    let selected: Vec<_> = stages
+        .iter()
+        .enumerate()
+        .filter(|(_, stage)| pass == AstPass::All || stage.pass == pass)
+        .collect();
+
+    let mut out = String::new();
+    for (n, (idx, stage)) in selected.iter().enumerate() {
+        if selected.len() > 1 {
+            if n > 0 {
+                out.push('\n');
+            }
+            let name = clap::ValueEnum::to_possible_value(&stage.pass).expect("pass value");
+            writeln!(out, "== {} ==", name.get_name()).expect("ast stage text");
+        }
+        match idx
+            .checked_sub(1)
+            .filter(|_| diff)
+            .map(|prev| &stages[prev])
+        {
+            Some(prev) => out.push_str(&stage_diff_to_text(parsed, prev, stage)),
+            None => out.push_str(&ast_to_tree_with(parsed, &|ids| stage.notes(ids), None)),
+        }
+    }
+    out
+}
+
+fn stage_diff_to_text(parsed: &Parsed, before: &AstStage, after: &AstStage) -> String {
+    let annotate = |ids: &[NodeId]| {
+        let (before, after) = (before.notes(ids), after.notes(ids));
+        let removed = before
+            .iter()
+            .filter(|note| !after.contains(note))
+            .map(|note| format!("-{note}"));
+        let kept_or_added = after.iter().map(|note| {
+            if before.contains(note) {
+                note.clone()
+            } else {
+                format!("+{note}")
+            }
+        });
+        removed.chain(kept_or_added).collect()
+    };
+    let marker = |ids: &[NodeId]| {
+        if before.notes(ids) == after.notes(ids) {
+            ' '
+        } else {
+            '~'
+        }
+    };
+    let mut out = ast_to_tree_with(parsed, &annotate, Some(&marker));
+
+    let changed = out.lines().filter(|line| line.starts_with('~')).count();
+    if changed == 0 {
+        writeln!(out, "\nno differences").expect("ast stage text");
+    } else {
+        writeln!(out, "\n{changed} changed").expect("ast stage text");
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Runs the passes over `query`, and checks the text rendering of those selected by `pass`;
+    /// the expected rendering starts with a line break, for readability.
+    #[track_caller]
+    fn assert_text(query: &str, pass: AstPass, diff: bool, text: &str) {
+        let compiler = Compiler::default();
+        let parsed = compiler.parse(query).unwrap();
+        let stages = ast_stages(&compiler, &parsed).unwrap();
+        assert_eq!(ast_stages_to_text(&parsed, &stages, pass, diff), text[1..]);
+    }
+
+    #[test]
+    fn stages_are_in_lowering_order() {
+        let compiler = Compiler::default();
+        let parsed = compiler.parse("SELECT a FROM t").unwrap();
+        let stages = ast_stages(&compiler, &parsed).unwrap();
+        let passes: Vec<_> = stages.iter().map(|stage| stage.pass).collect();
+        assert_eq!(passes, [AstPass::Parsed, AstPass::NameResolution]);
+        assert!(stages[0].notes.is_empty());
+    }
+
+    #[test]
+    fn name_resolution() {
+        assert_text(
+            "SELECT x.a AS b FROM t AS x",
+            AstPass::NameResolution,
+            false,
+            r#"
+Query #12 (b0..b27) | in scope: #8 | consumes: x (local, global), a (local, global) | produces: b
+└── Select #10 (b0..b27) | in scope: #8
+    ├── Projection #6 (b0..b15)
+    │   └── ProjectExpr as b #5 (b7..b15) | alias: b
+    │       └── Path #4 (b7..b10)
+    │           ├── x #2 (b7..b8)
+    │           └── Project
+    │               └── a #3 (b9..b10)
+    └── From #9 (b16..b27) | in scope: #8
+        └── Scan as x #8 (b21..b27) | alias: x | consumes: t (global, local) | produces: x
+            └── t #7 (b21..b22)
+"#,
+        );
+    }
+
+    #[test]
+    fn all_passes_diffed() {
+        assert_text(
+            "SELECT a FROM t",
+            AstPass::All,
+            true,
+            r#"
+== parsed ==
+Query #10 (b0..b15)
+└── Select #8 (b0..b15)
+    ├── Projection #4 (b0..b8)
+    │   └── ProjectExpr #3 (b7..b8)
+    │       └── a #2 (b7..b8)
+    └── From #7 (b9..b15)
+        └── Scan #6 (b14..b15)
+            └── t #5 (b14..b15)
+
+== name-resolution ==
+~ Query #10 (b0..b15) | +in scope: #6 | +consumes: a (local, global) | +produces: a
+~ └── Select #8 (b0..b15) | +in scope: #6
+      ├── Projection #4 (b0..b8)
+~     │   └── ProjectExpr #3 (b7..b8) | +alias: a
+      │       └── a #2 (b7..b8)
+~     └── From #7 (b9..b15) | +in scope: #6
+~         └── Scan #6 (b14..b15) | +alias: t | +consumes: t (global, local) | +produces: t
+              └── t #5 (b14..b15)
+
+5 changed
+"#,
+        );
+    }
+
+    #[test]
+    fn unchanged_passes_diffed() {
+        assert_text(
+            "1 + 1",
+            AstPass::All,
+            true,
+            r#"
+== parsed ==
+Query #6 (b0..b5)
+└── + #4 (b0..b5)
+    ├── 1 #2 (b0..b1)
+    └── 1 #3 (b4..b5)
+
+== name-resolution ==
+  Query #6 (b0..b5)
+  └── + #4 (b0..b5)
+      ├── 1 #2 (b0..b1)
+      └── 1 #3 (b4..b5)
+
+no differences
+"#,
+        );
+    }
+}
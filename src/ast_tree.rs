@@ -0,0 +1,920 @@
+use partiql_ast::ast;
+use partiql_ast::visit::{Traverse, Visit, Visitor};
+use partiql_common::node::NodeId;
+use partiql_parser::Parsed;
+use std::fmt::Write;
+use std::ops::Range;
+
+/// Renders a parsed query's AST as an indented tree of nodes, each annotated with its node id and
+/// source span.
+pub fn ast_to_tree(parsed: &Parsed) -> String {
+    ast_to_tree_with(parsed, &|_| vec![], None)
+}
+
+/// Renders a parsed query's AST as an indented tree of nodes, with the fields given by `annotate`
+/// appended to each node's label. If `marker` is given, each node's line is prefixed with the
+/// marker it gives for the node. Both are given the ids of the AST nodes each tree node stands for.
+pub(crate) fn ast_to_tree_with(
+    parsed: &Parsed,
+    annotate: &Annotate<'_>,
+    marker: Option<&Marker<'_>>,
+) -> String {
+    let mut tree = AstToTree {
+        parsed,
+        pending: None,
+        skipped: vec![],
+        stack: vec![],
+        roots: vec![],
+    };
+    parsed.ast.visit(&mut tree);
+
+    let text = TextTree { annotate, marker };
+    let mut out = String::new();
+    for root in &tree.roots {
+        text.write_node(&mut out, root, "", "").expect("ast text");
+    }
+    out
+}
+
+/// The byte range of the query text that produced an AST node.
+pub fn node_span(parsed: &Parsed, id: NodeId) -> Option<Range<usize>> {
+    let loc = parsed.locations.get(&id)?;
+    Some(loc.start.0 .0 as usize..loc.end.0 .0 as usize)
+}
+
+struct TreeNode {
+    /// The ids of the [`ast::AstNode`]s the node stands for: its own, after those of any enclosing
+    /// nodes that aren't labelled in the tree
+    ids: Vec<NodeId>,
+    label: String,
+    span: Option<String>,
+    children: Vec<TreeNode>,
+}
+
+struct AstToTree<'a, 'input> {
+    parsed: &'a Parsed<'input>,
+    /// The id of the most recently entered [`ast::AstNode`], to be claimed by the next labelled node.
+    pending: Option<NodeId>,
+    /// The ids of entered [`ast::AstNode`]s whose id was displaced from `pending` before being
+    /// claimed, to be claimed along with it.
+    skipped: Vec<NodeId>,
+    stack: Vec<TreeNode>,
+    roots: Vec<TreeNode>,
+}
+
+impl<'a, 'input> AstToTree<'a, 'input> {
+    fn push(&mut self, label: impl Into<String>) -> Traverse {
+        let id = self.pending.take();
+        let span = id.and_then(|id| {
+            let loc = self.parsed.locations.get(&id)?;
+            Some(format!("#{} {loc}", id.0))
+        });
+        let mut ids = std::mem::take(&mut self.skipped);
+        ids.extend(id);
+        self.stack.push(TreeNode {
+            ids,
+            label: label.into(),
+            span,
+            children: vec![],
+        });
+        Traverse::Continue
+    }
+
+    fn pop(&mut self) -> Traverse {
+        let node = self.stack.pop().expect("ast tree node");
+        match self.stack.last_mut() {
+            Some(parent) => parent.children.push(node),
+            None => self.roots.push(node),
+        }
+        Traverse::Continue
+    }
+
+    fn leaf(&mut self, label: impl Into<String>) {
+        self.push(label);
+        self.pop();
+    }
+}
+
+/// Gives the fields appended to the label of the tree node standing for AST nodes `ids`.
+pub(crate) type Annotate<'a> = dyn Fn(&[NodeId]) -> Vec<String> + 'a;
+/// Gives the marker prefixing the line of the tree node standing for AST nodes `ids`.
+pub(crate) type Marker<'a> = dyn Fn(&[NodeId]) -> char + 'a;
+
+/// How the nodes of an AST tree are written.
+struct TextTree<'a> {
+    annotate: &'a Annotate<'a>,
+    marker: Option<&'a Marker<'a>>,
+}
+
+impl TextTree<'_> {
+    fn write_node(
+        &self,
+        out: &mut String,
+        node: &TreeNode,
+        first_prefix: &str,
+        prefix: &str,
+    ) -> std::fmt::Result {
+        if let Some(marker) = self.marker {
+            let marker = marker(&node.ids);
+            write!(out, "{marker} ")?;
+        }
+        write!(out, "{first_prefix}{}", node.label)?;
+        if let Some(span) = &node.span {
+            write!(out, " {span}")?;
+        }
+        for field in (self.annotate)(&node.ids) {
+            write!(out, " | {field}")?;
+        }
+        writeln!(out)?;
+
+        for (idx, child) in node.children.iter().enumerate() {
+            let (first, rest) = if idx == node.children.len() - 1 {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            self.write_node(
+                out,
+                child,
+                &format!("{prefix}{first}"),
+                &format!("{prefix}{rest}"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+fn with_alias(label: &str, kw: &str, alias: &Option<ast::SymbolPrimitive>) -> String {
+    match alias {
+        Some(alias) => format!("{label} {kw} {}", symbol_primitive_to_label(alias)),
+        None => label.to_string(),
+    }
+}
+
+fn setq_to_str(setq: &Option<ast::SetQuantifier>) -> &'static str {
+    match setq {
+        Some(ast::SetQuantifier::Distinct) => " Distinct",
+        Some(ast::SetQuantifier::All) => " All",
+        None => "",
+    }
+}
+
+impl<'a, 'input, 'ast> Visitor<'ast> for AstToTree<'a, 'input> {
+    fn enter_ast_node(&mut self, id: NodeId) -> Traverse {
+        self.skipped.extend(self.pending.replace(id));
+        Traverse::Continue
+    }
+    fn exit_ast_node(&mut self, id: NodeId) -> Traverse {
+        self.pending = None;
+        self.skipped.retain(|skipped| *skipped != id);
+        Traverse::Continue
+    }
+
+    fn enter_query(&mut self, _query: &'ast ast::Query) -> Traverse {
+        self.push("Query")
+    }
+    fn exit_query(&mut self, _query: &'ast ast::Query) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_with_clause(&mut self, with: &'ast ast::WithClause) -> Traverse {
+        self.push(if with.recursive {
+            "With Recursive"
+        } else {
+            "With"
+        })
+    }
+    fn exit_with_clause(&mut self, _with: &'ast ast::WithClause) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_with_element(&mut self, elem: &'ast ast::WithElement) -> Traverse {
+        let mut label = symbol_primitive_to_label(&elem.query_name);
+        if let Some(columns) = &elem.columns {
+            let columns: Vec<_> = columns.iter().map(symbol_primitive_to_label).collect();
+            write!(label, "({})", columns.join(", ")).expect("ast text");
+        }
+        self.push(format!("{label} AS"))
+    }
+    fn exit_with_element(&mut self, _elem: &'ast ast::WithElement) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_bag_op_expr(&mut self, bag_op: &'ast ast::BagOpExpr) -> Traverse {
+        self.push(bag_op_to_str(bag_op))
+    }
+    fn exit_bag_op_expr(&mut self, _bag_op: &'ast ast::BagOpExpr) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_select(&mut self, _select: &'ast ast::Select) -> Traverse {
+        self.push("Select")
+    }
+    fn exit_select(&mut self, _select: &'ast ast::Select) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_query_table(&mut self, table: &'ast ast::QueryTable) -> Traverse {
+        self.push(format!(
+            "Table {}",
+            symbol_primitive_to_label(&table.table_name)
+        ))
+    }
+    fn exit_query_table(&mut self, _table: &'ast ast::QueryTable) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_projection(&mut self, projection: &'ast ast::Projection) -> Traverse {
+        use ast::ProjectionKind;
+        let kind = match &projection.kind {
+            ProjectionKind::ProjectStar => " *",
+            ProjectionKind::ProjectList(_) => "",
+            ProjectionKind::ProjectPivot(_) => " Pivot",
+            ProjectionKind::ProjectValue(_) => " Value",
+        };
+        self.push(format!("Projection{}{kind}", setq_to_str(&projection.setq)))
+    }
+    fn exit_projection(&mut self, _projection: &'ast ast::Projection) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_project_all(&mut self, _project_all: &'ast ast::ProjectAll) -> Traverse {
+        self.push("ProjectAll")
+    }
+    fn exit_project_all(&mut self, _project_all: &'ast ast::ProjectAll) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_project_expr(&mut self, project_expr: &'ast ast::ProjectExpr) -> Traverse {
+        self.push(with_alias("ProjectExpr", "as", &project_expr.as_alias))
+    }
+    fn exit_project_expr(&mut self, _project_expr: &'ast ast::ProjectExpr) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_exclusion(&mut self, _exclusion: &'ast ast::Exclusion) -> Traverse {
+        self.push("Exclude")
+    }
+    fn exit_exclusion(&mut self, _exclusion: &'ast ast::Exclusion) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_exclude_path(&mut self, path: &'ast ast::ExcludePath) -> Traverse {
+        use ast::ExcludePathStep;
+        let steps: String = path
+            .steps
+            .iter()
+            .map(|step| match step {
+                ExcludePathStep::PathProject(sym) => {
+                    format!(".{}", symbol_primitive_to_label(&sym.node))
+                }
+                ExcludePathStep::PathIndex(lit) => format!("[{}]", lit_to_str(&lit.node)),
+                ExcludePathStep::PathForEach => "[*]".to_string(),
+                ExcludePathStep::PathUnpivot => ".*".to_string(),
+            })
+            .collect();
+        self.push(format!("ExcludePath {steps}"))
+    }
+    fn exit_exclude_path(&mut self, _path: &'ast ast::ExcludePath) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_lit(&mut self, lit: &'ast ast::Lit) -> Traverse {
+        self.push(lit_to_str(lit))
+    }
+    fn exit_lit(&mut self, _lit: &'ast ast::Lit) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_var_ref(&mut self, var_ref: &'ast ast::VarRef) -> Traverse {
+        let lbl = symbol_primitive_to_label(&var_ref.name);
+        self.push(match &var_ref.qualifier {
+            ast::ScopeQualifier::Unqualified => lbl,
+            ast::ScopeQualifier::Qualified => format!("@{}", lbl),
+        })
+    }
+    fn exit_var_ref(&mut self, _var_ref: &'ast ast::VarRef) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_bin_op(&mut self, bin_op: &'ast ast::BinOp) -> Traverse {
+        self.push(bin_op_to_str(&bin_op.kind))
+    }
+    fn exit_bin_op(&mut self, _bin_op: &'ast ast::BinOp) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_uni_op(&mut self, uni_op: &'ast ast::UniOp) -> Traverse {
+        self.push(uni_op_to_str(&uni_op.kind))
+    }
+    fn exit_uni_op(&mut self, _uni_op: &'ast ast::UniOp) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_like(&mut self, _like: &'ast ast::Like) -> Traverse {
+        self.push("LIKE")
+    }
+    fn exit_like(&mut self, _like: &'ast ast::Like) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_between(&mut self, _between: &'ast ast::Between) -> Traverse {
+        self.push("BETWEEN")
+    }
+    fn exit_between(&mut self, _between: &'ast ast::Between) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_in(&mut self, _in: &'ast ast::In) -> Traverse {
+        self.push("IN")
+    }
+    fn exit_in(&mut self, _in: &'ast ast::In) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_simple_case(&mut self, _simple_case: &'ast ast::SimpleCase) -> Traverse {
+        self.push("Case")
+    }
+    fn exit_simple_case(&mut self, _simple_case: &'ast ast::SimpleCase) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_searched_case(&mut self, _searched_case: &'ast ast::SearchedCase) -> Traverse {
+        self.push("Case")
+    }
+    fn exit_searched_case(&mut self, _searched_case: &'ast ast::SearchedCase) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_expr_pair(&mut self, _expr_pair: &'ast ast::ExprPair) -> Traverse {
+        self.push("Pair")
+    }
+    fn exit_expr_pair(&mut self, _expr_pair: &'ast ast::ExprPair) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_struct(&mut self, _struct: &'ast ast::Struct) -> Traverse {
+        self.push("Struct")
+    }
+    fn exit_struct(&mut self, _struct: &'ast ast::Struct) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_bag(&mut self, _bag: &'ast ast::Bag) -> Traverse {
+        self.push("Bag")
+    }
+    fn exit_bag(&mut self, _bag: &'ast ast::Bag) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_list(&mut self, _list: &'ast ast::List) -> Traverse {
+        self.push("List")
+    }
+    fn exit_list(&mut self, _list: &'ast ast::List) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_sexp(&mut self, _sexp: &'ast ast::Sexp) -> Traverse {
+        self.push("Sexp")
+    }
+    fn exit_sexp(&mut self, _sexp: &'ast ast::Sexp) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_call(&mut self, call: &'ast ast::Call) -> Traverse {
+        self.push(format!(
+            "Call {}",
+            symbol_primitive_to_label(&call.func_name)
+        ))
+    }
+    fn exit_call(&mut self, _call: &'ast ast::Call) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_call_arg(&mut self, call_arg: &'ast ast::CallArg) -> Traverse {
+        // `*` and type arguments are not visited, so are added here as leaves
+        match call_arg {
+            ast::CallArg::Star() => self.leaf("*"),
+            ast::CallArg::PositionalType(ty) => self.leaf(type_to_str(ty)),
+            _ => {}
+        }
+        Traverse::Continue
+    }
+
+    fn enter_call_arg_named(&mut self, named: &'ast ast::CallArgNamed) -> Traverse {
+        self.push(format!("Named {}", symbol_primitive_to_label(&named.name)))
+    }
+    fn exit_call_arg_named(&mut self, _named: &'ast ast::CallArgNamed) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_call_arg_named_type(&mut self, named: &'ast ast::CallArgNamedType) -> Traverse {
+        self.push(format!(
+            "Named {}: {}",
+            symbol_primitive_to_label(&named.name),
+            type_to_str(&named.ty)
+        ))
+    }
+    fn exit_call_arg_named_type(&mut self, _named: &'ast ast::CallArgNamedType) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_call_agg(&mut self, call_agg: &'ast ast::CallAgg) -> Traverse {
+        self.push(format!(
+            "CallAgg {}",
+            symbol_primitive_to_label(&call_agg.func_name)
+        ))
+    }
+    fn exit_call_agg(&mut self, _call_agg: &'ast ast::CallAgg) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_path(&mut self, _path: &'ast ast::Path) -> Traverse {
+        self.push("Path")
+    }
+    fn exit_path(&mut self, _path: &'ast ast::Path) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_path_step(&mut self, path_step: &'ast ast::PathStep) -> Traverse {
+        self.push(match path_step {
+            ast::PathStep::PathProject(_) => "Project",
+            ast::PathStep::PathIndex(_) => "Index",
+            ast::PathStep::PathForEach => "[*]",
+            ast::PathStep::PathUnpivot => ".*",
+        })
+    }
+    fn exit_path_step(&mut self, _path_step: &'ast ast::PathStep) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_let(&mut self, _let: &'ast ast::Let) -> Traverse {
+        self.push("Let")
+    }
+    fn exit_let(&mut self, _let: &'ast ast::Let) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_let_binding(&mut self, binding: &'ast ast::LetBinding) -> Traverse {
+        self.push(format!(
+            "LetBinding as {}",
+            symbol_primitive_to_label(&binding.as_alias)
+        ))
+    }
+    fn exit_let_binding(&mut self, _binding: &'ast ast::LetBinding) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_from_clause(&mut self, _from_clause: &'ast ast::FromClause) -> Traverse {
+        self.push("From")
+    }
+    fn exit_from_clause(&mut self, _from_clause: &'ast ast::FromClause) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_where_clause(&mut self, _where_clause: &'ast ast::WhereClause) -> Traverse {
+        self.push("Where")
+    }
+    fn exit_where_clause(&mut self, _where_clause: &'ast ast::WhereClause) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_having_clause(&mut self, _having_clause: &'ast ast::HavingClause) -> Traverse {
+        self.push("Having")
+    }
+    fn exit_having_clause(&mut self, _having_clause: &'ast ast::HavingClause) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_from_let(&mut self, from_let: &'ast ast::FromLet) -> Traverse {
+        let lbl = match &from_let.kind {
+            ast::FromLetKind::Scan => "Scan",
+            ast::FromLetKind::Unpivot => "Unpivot",
+        };
+        let lbl = with_alias(lbl, "as", &from_let.as_alias);
+        let lbl = with_alias(&lbl, "at", &from_let.at_alias);
+        self.push(with_alias(&lbl, "by", &from_let.by_alias))
+    }
+    fn exit_from_let(&mut self, _from_let: &'ast ast::FromLet) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_join(&mut self, join: &'ast ast::Join) -> Traverse {
+        self.push(match &join.kind {
+            ast::JoinKind::Inner => "Inner Join",
+            ast::JoinKind::Left => "Left Join",
+            ast::JoinKind::Right => "Right Join",
+            ast::JoinKind::Full => "Full Join",
+            ast::JoinKind::Cross => "Cross Join",
+        })
+    }
+    fn exit_join(&mut self, _join: &'ast ast::Join) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_join_spec(&mut self, join_spec: &'ast ast::JoinSpec) -> Traverse {
+        self.push(match join_spec {
+            ast::JoinSpec::On(_) => "On",
+            ast::JoinSpec::Using(_) => "Using",
+            ast::JoinSpec::Natural => "Natural",
+        })
+    }
+    fn exit_join_spec(&mut self, _join_spec: &'ast ast::JoinSpec) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_group_by_expr(&mut self, group_by: &'ast ast::GroupByExpr) -> Traverse {
+        let lbl = match &group_by.strategy {
+            Some(ast::GroupingStrategy::GroupPartial) => "Group Partial By",
+            _ => "Group By",
+        };
+        self.push(with_alias(lbl, "group as", &group_by.group_as_alias))
+    }
+    fn exit_group_by_expr(&mut self, _group_by: &'ast ast::GroupByExpr) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_group_key(&mut self, group_key: &'ast ast::GroupKey) -> Traverse {
+        self.push(with_alias("GroupKey", "as", &group_key.as_alias))
+    }
+    fn exit_group_key(&mut self, _group_key: &'ast ast::GroupKey) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_order_by_expr(&mut self, _order_by: &'ast ast::OrderByExpr) -> Traverse {
+        self.push("Order By")
+    }
+    fn exit_order_by_expr(&mut self, _order_by: &'ast ast::OrderByExpr) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_limit_offset_clause(
+        &mut self,
+        limit_offset: &'ast ast::LimitOffsetClause,
+    ) -> Traverse {
+        self.push(match (&limit_offset.limit, &limit_offset.offset) {
+            (Some(_), None) => "Limit",
+            (None, Some(_)) => "Offset",
+            _ => "Limit Offset",
+        })
+    }
+    fn exit_limit_offset_clause(
+        &mut self,
+        _limit_offset: &'ast ast::LimitOffsetClause,
+    ) -> Traverse {
+        self.pop()
+    }
+
+    fn enter_sort_spec(&mut self, sort_spec: &'ast ast::SortSpec) -> Traverse {
+        let mut lbl = "SortSpec".to_string();
+        match &sort_spec.ordering_spec {
+            Some(ast::OrderingSpec::Asc) => lbl.push_str(" Asc"),
+            Some(ast::OrderingSpec::Desc) => lbl.push_str(" Desc"),
+            None => {}
+        }
+        match &sort_spec.null_ordering_spec {
+            Some(ast::NullOrderingSpec::First) => lbl.push_str(" Nulls First"),
+            Some(ast::NullOrderingSpec::Last) => lbl.push_str(" Nulls Last"),
+            None => {}
+        }
+        self.push(lbl)
+    }
+    fn exit_sort_spec(&mut self, _sort_spec: &'ast ast::SortSpec) -> Traverse {
+        self.pop()
+    }
+}
+
+pub(crate) fn bag_op_to_str(bag_op: &ast::BagOpExpr) -> String {
+    use ast::BagOperator;
+    let op = match bag_op.bag_op {
+        BagOperator::Union => "Union",
+        BagOperator::Except => "Except",
+        BagOperator::Intersect => "Intersect",
+        BagOperator::OuterUnion => "Outer Union",
+        BagOperator::OuterExcept => "Outer Except",
+        BagOperator::OuterIntersect => "Outer Intersect",
+    };
+    format!("{op}{}", setq_to_str(&bag_op.setq))
+}
+
+pub(crate) fn bin_op_to_str(kind: &ast::BinOpKind) -> &'static str {
+    use ast::BinOpKind;
+    match kind {
+        BinOpKind::Add => "+",
+        BinOpKind::Div => "/",
+        BinOpKind::Exp => "^",
+        BinOpKind::Mod => "%",
+        BinOpKind::Mul => "*",
+        BinOpKind::Sub => "-",
+        BinOpKind::And => "AND",
+        BinOpKind::Or => "OR",
+        BinOpKind::Concat => "||",
+        BinOpKind::Eq => "=",
+        BinOpKind::Gt => ">",
+        BinOpKind::Gte => ">=",
+        BinOpKind::Lt => "<",
+        BinOpKind::Lte => "<=",
+        BinOpKind::Ne => "<>",
+        BinOpKind::Is => "IS",
+    }
+}
+
+pub(crate) fn uni_op_to_str(kind: &ast::UniOpKind) -> &'static str {
+    use ast::UniOpKind;
+    match kind {
+        UniOpKind::Pos => "+",
+        UniOpKind::Neg => "-",
+        UniOpKind::Not => "NOT",
+    }
+}
+
+#[inline]
+pub(crate) fn lit_to_str(ast: &ast::Lit) -> String {
+    use ast::Lit;
+    match ast {
+        Lit::Null => "NULL".to_string(),
+        Lit::Missing => "MISSING".to_string(),
+        Lit::Int8Lit(l) => l.to_string(),
+        Lit::Int16Lit(l) => l.to_string(),
+        Lit::Int32Lit(l) => l.to_string(),
+        Lit::Int64Lit(l) => l.to_string(),
+        Lit::DecimalLit(l) => l.to_string(),
+        Lit::NumericLit(l) => l.to_string(),
+        Lit::RealLit(l) => l.to_string(),
+        Lit::FloatLit(l) => l.to_string(),
+        Lit::DoubleLit(l) => l.to_string(),
+        Lit::BoolLit(l) => (if *l { "TRUE" } else { "FALSE" }).to_string(),
+        Lit::IonStringLit(l) => format!("`{}`", l),
+        Lit::CharStringLit(l) => format!("'{}'", l),
+        Lit::NationalCharStringLit(l) => format!("'{}'", l),
+        Lit::BitStringLit(l) => format!("b'{}'", l),
+        Lit::HexStringLit(l) => format!("x'{}'", l),
+        // TODO render the contents of collection literals
+        Lit::BagLit(_) => "<< ... >>".to_string(),
+        Lit::ListLit(_) => "[ ... ]".to_string(),
+        Lit::StructLit(_) => "{ ... }".to_string(),
+        Lit::TypedLit(val_str, ty) => {
+            format!("{} '{}'", type_to_str(ty), val_str)
+        }
+    }
+}
+
+#[inline]
+fn custom_type_param_to_str(param: &ast::CustomTypeParam) -> String {
+    use ast::CustomTypeParam;
+    match param {
+        CustomTypeParam::Lit(lit) => lit_to_str(lit),
+        CustomTypeParam::Type(ty) => type_to_str(ty),
+    }
+}
+
+#[inline]
+fn custom_type_part_to_str(part: &ast::CustomTypePart) -> String {
+    use ast::CustomTypePart;
+    match part {
+        CustomTypePart::Name(name) => symbol_primitive_to_label(name),
+        CustomTypePart::Parameterized(name, args) => {
+            let name = symbol_primitive_to_label(name);
+            let args = args
+                .iter()
+                .map(custom_type_param_to_str)
+                .collect::<Vec<_>>()
+                .join(",");
+            format!("{}({})", name, args)
+        }
+    }
+}
+
+#[inline]
+pub(crate) fn type_to_str(ty: &ast::Type) -> String {
+    use ast::Type;
+    match ty {
+        Type::CustomType(cty) => cty
+            .parts
+            .iter()
+            .map(custom_type_part_to_str)
+            .collect::<Vec<_>>()
+            .join(" "),
+        _ => format!("{:?}", ty),
+    }
+}
+
+pub(crate) fn symbol_primitive_to_label(sym: &ast::SymbolPrimitive) -> String {
+    use ast::CaseSensitivity;
+    match &sym.case {
+        CaseSensitivity::CaseSensitive => format!("'{}'", sym.value),
+        CaseSensitivity::CaseInsensitive => sym.value.clone(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::Compiler;
+
+    /// Parses `query`, and checks its AST tree; the expected tree starts with a line break, for
+    /// readability.
+    #[track_caller]
+    fn assert_tree(query: &str, tree: &str) {
+        let parsed = Compiler::default().parse(query).unwrap();
+        assert_eq!(ast_to_tree(&parsed), tree[1..]);
+    }
+
+    #[test]
+    fn select_clauses() {
+        assert_tree(
+            "SELECT DISTINCT x.a AS b FROM t AS x AT i LEFT JOIN u AS y ON x.a = y.a \
+             WHERE x.a LIKE 'a%' GROUP BY x.a AS k GROUP AS g HAVING count(*) > 1 \
+             ORDER BY k DESC NULLS LAST LIMIT 2 OFFSET 1",
+            r#"
+Query #45 (b0..b184)
+├── Select #37 (b0..b140)
+│   ├── Projection Distinct #6 (b0..b24)
+│   │   └── ProjectExpr as b #5 (b16..b24)
+│   │       └── Path #4 (b16..b19)
+│   │           ├── x #2 (b16..b17)
+│   │           └── Project
+│   │               └── a #3 (b18..b19)
+│   ├── From #20 (b25..b71)
+│   │   └── Left Join #19 (b30..b71)
+│   │       ├── Scan as x at i #8 (b30..b41)
+│   │       │   └── t #7 (b30..b31)
+│   │       ├── Scan as y #10 (b52..b58)
+│   │       │   └── u #9 (b52..b53)
+│   │       └── On #18 (b59..b71)
+│   │           └── = #17 (b62..b71)
+│   │               ├── Path #13 (b62..b65)
+│   │               │   ├── x #11 (b62..b63)
+│   │               │   └── Project
+│   │               │       └── a #12 (b64..b65)
+│   │               └── Path #16 (b68..b71)
+│   │                   ├── y #14 (b68..b69)
+│   │                   └── Project
+│   │                       └── a #15 (b70..b71)
+│   ├── Where #26 (b72..b91)
+│   │   └── LIKE #25 (b78..b91)
+│   │       ├── Path #23 (b78..b81)
+│   │       │   ├── x #21 (b78..b79)
+│   │       │   └── Project
+│   │       │       └── a #22 (b80..b81)
+│   │       └── 'a%' #24 (b87..b91)
+│   ├── Group By group as g #31 (b92..b120)
+│   │   └── GroupKey as k #30 (b101..b109)
+│   │       └── Path #29 (b101..b104)
+│   │           ├── x #27 (b101..b102)
+│   │           └── Project
+│   │               └── a #28 (b103..b104)
+│   └── Having #36 (b121..b140)
+│       └── > #35 (b128..b140)
+│           ├── CallAgg count #33 (b128..b136)
+│           │   └── * #32 (b134..b135)
+│           └── 1 #34 (b139..b140)
+├── Order By #41 (b141..b167)
+│   └── SortSpec Desc Nulls Last #40 (b150..b167)
+│       └── k #39 (b150..b151)
+└── Limit Offset #44 (b168..b184)
+    ├── 2 #42 (b174..b175)
+    └── 1 #43 (b183..b184)
+"#,
+        );
+    }
+
+    #[test]
+    fn expressions() {
+        assert_tree(
+            "SELECT VALUE CASE WHEN a BETWEEN 1 AND 2 THEN [a, <<a>>] ELSE {'k': -a} END FROM t",
+            r#"
+Query #21 (b0..b82)
+└── Select #19 (b0..b82)
+    ├── Projection Value #15 (b0..b75)
+    │   └── Case #14 (b13..b75)
+    │       ├── Pair
+    │       │   ├── BETWEEN #5 (b23..b40)
+    │       │   │   ├── a #2 (b23..b24)
+    │       │   │   ├── 1 #3 (b33..b34)
+    │       │   │   └── 2 #4 (b39..b40)
+    │       │   └── List #9 (b46..b56)
+    │       │       ├── a #6 (b47..b48)
+    │       │       └── Bag #8 (b50..b55)
+    │       │           └── a #7 (b52..b53)
+    │       └── Struct #13 (b62..b71)
+    │           └── Pair
+    │               ├── 'k' #10 (b63..b66)
+    │               └── - #12 (b68..b70)
+    │                   └── a #11 (b69..b70)
+    └── From #18 (b76..b82)
+        └── Scan #17 (b81..b82)
+            └── t #16 (b81..b82)
+"#,
+        );
+    }
+
+    #[test]
+    fn set_operations_and_calls() {
+        assert_tree(
+            "SELECT CAST(a AS DECIMAL(5, 2)), a IN (1, 2) FROM t UNION ALL SELECT * FROM u",
+            r#"
+Query #35 (b0..b77)
+└── Union All #33 (b0..b77)
+    ├── Query #25 (b0..b52)
+    │   └── Select #23 (b0..b51)
+    │       ├── Projection #19 (b0..b44)
+    │       │   ├── ProjectExpr #11 (b7..b31)
+    │       │   │   └── Call CAST #10 (b7..b31)
+    │       │   │       ├── a #2 (b12..b13)
+    │       │   │       └── Named 'AS' #8 (b14..b30)
+    │       │   │           └── Call DECIMAL #7 (b17..b30)
+    │       │   │               ├── 5 #3 (b25..b26)
+    │       │   │               └── 2 #4 (b28..b29)
+    │       │   └── ProjectExpr #18 (b33..b44)
+    │       │       └── IN #17 (b33..b44)
+    │       │           ├── a #12 (b33..b34)
+    │       │           └── [ ... ] #16 (b38..b44)
+    │       └── From #22 (b45..b51)
+    │           └── Scan #21 (b50..b51)
+    │               └── t #20 (b50..b51)
+    └── Query #32 (b0..b77)
+        └── Select #30 (b62..b77)
+            ├── Projection * #26 (b62..b70)
+            └── From #29 (b71..b77)
+                └── Scan #28 (b76..b77)
+                    └── u #27 (b76..b77)
+"#,
+        );
+    }
+
+    #[test]
+    fn pivot_and_unpivot() {
+        assert_tree(
+            "PIVOT v AT k FROM UNPIVOT t AS v AT k",
+            r#"
+Query #10 (b0..b37)
+└── Select #8 (b0..b37)
+    ├── Projection Pivot #4 (b0..b12)
+    │   ├── k #3 (b11..b12)
+    │   └── v #2 (b6..b7)
+    └── From #7 (b13..b37)
+        └── Unpivot as v at k #6 (b18..b37)
+            └── t #5 (b26..b27)
+"#,
+        );
+    }
+
+    #[test]
+    fn literals() {
+        assert_tree(
+            "SELECT NULL, MISSING, TRUE, 1.5, 'a', TIME '12:00:00', \"Col\" FROM t",
+            r#"
+Query #22 (b0..b67)
+└── Select #20 (b0..b67)
+    ├── Projection #16 (b0..b60)
+    │   ├── ProjectExpr #3 (b7..b11)
+    │   │   └── NULL #2 (b7..b11)
+    │   ├── ProjectExpr #5 (b13..b20)
+    │   │   └── MISSING #4 (b13..b20)
+    │   ├── ProjectExpr #7 (b22..b26)
+    │   │   └── TRUE #6 (b22..b26)
+    │   ├── ProjectExpr #9 (b28..b31)
+    │   │   └── 1.5 #8 (b28..b31)
+    │   ├── ProjectExpr #11 (b33..b36)
+    │   │   └── 'a' #10 (b33..b36)
+    │   ├── ProjectExpr #13 (b38..b53)
+    │   │   └── TIME '12:00:00' #12 (b38..b53)
+    │   └── ProjectExpr #15 (b55..b60)
+    │       └── 'Col' #14 (b55..b60)
+    └── From #19 (b61..b67)
+        └── Scan #18 (b66..b67)
+            └── t #17 (b66..b67)
+"#,
+        );
+    }
+
+    #[test]
+    fn annotations_and_markers() {
+        let parsed = Compiler::default().parse("SELECT a FROM t").unwrap();
+        // nodes wrapping others stand for the ids of both
+        let annotate = |ids: &[NodeId]| {
+            vec![format!(
+                "{:?}",
+                ids.iter().map(|id| id.0).collect::<Vec<_>>()
+            )]
+        };
+        let marker = |ids: &[NodeId]| if ids.len() > 1 { '*' } else { ' ' };
+        assert_eq!(
+            ast_to_tree_with(&parsed, &annotate, Some(&marker)),
+            r#"* Query #10 (b0..b15) | [11, 10]
+* └── Select #8 (b0..b15) | [9, 8]
+      ├── Projection #4 (b0..b8) | [4]
+      │   └── ProjectExpr #3 (b7..b8) | [3]
+      │       └── a #2 (b7..b8) | [2]
+      └── From #7 (b9..b15) | [7]
+          └── Scan #6 (b14..b15) | [6]
+              └── t #5 (b14..b15) | [5]
+"#
+        );
+        assert_eq!(
+            &parsed.text[node_span(&parsed, NodeId(4)).unwrap()],
+            "SELECT a"
+        );
+    }
+}
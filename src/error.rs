@@ -1,20 +1,53 @@
 use miette::{Diagnostic, LabeledSpan, SourceCode};
+use partiql_ast::ast;
+use partiql_ast::visit::{Traverse, Visit, Visitor};
 use partiql_ast_passes::error::{AstTransformError, AstTransformationError};
+use partiql_common::node::NodeId;
 use partiql_eval::error::{EvalErr, EvaluationError, PlanErr, PlanningError};
-use partiql_parser::{ParseError, ParserError};
+use partiql_parser::{ParseError, Parsed, ParserError};
 use partiql_common::syntax::location::{ByteOffset, BytePosition, Location, Located};
-use std::io::Error;
+use crate::args::ErrorFormat;
+use ion_rs::{IonType, IonWriter};
+use std::io::{Error, Write};
+use std::ops::Range;
 
 use thiserror::Error;
 
 #[derive(Debug, Error, Diagnostic)]
-#[error("Error for query `{query}`")]
+#[error("{}", errors_title(.query))]
 pub struct CLIErrors {
+    /// The query the errors are for; empty for errors that aren't about a query
     query: String,
     #[related]
     related: Vec<CLIError>,
 }
 
+fn errors_title(query: &str) -> String {
+    if query.is_empty() {
+        "PartiQL CLI error".to_string()
+    } else {
+        format!("Error for query `{query}`")
+    }
+}
+
+impl From<CLIError> for CLIErrors {
+    fn from(err: CLIError) -> Self {
+        CLIErrors {
+            query: String::new(),
+            related: vec![err],
+        }
+    }
+}
+
+impl From<(&str, CLIError)> for CLIErrors {
+    fn from((query, err): (&str, CLIError)) -> Self {
+        CLIErrors {
+            query: query.to_string(),
+            related: vec![err],
+        }
+    }
+}
+
 impl From<(&str, std::io::Error)> for CLIErrors {
     fn from((query, err): (&str, Error)) -> Self {
         CLIErrors {
@@ -37,36 +70,335 @@ impl<'a> From<ParserError<'a>> for CLIErrors {
     }
 }
 
-impl From<(&str, AstTransformationError)> for CLIErrors {
-    fn from((query, err): (&str, AstTransformationError)) -> Self {
-        let related = err.errors.into_iter().map(|e| (query, e).into()).collect();
+impl From<(&Parsed<'_>, AstTransformationError)> for CLIErrors {
+    fn from((parsed, err): (&Parsed<'_>, AstTransformationError)) -> Self {
+        let mut culprits = Culprits::of(parsed);
+        let related = err
+            .errors
+            .into_iter()
+            .map(|e| {
+                let loc = ast_transform_culprit(&e).and_then(|c| culprits.take(&c));
+                CLIError::from((parsed.text, e)).located(loc)
+            })
+            .collect();
         CLIErrors {
-            query: query.to_string(),
+            query: parsed.text.to_string(),
             related,
         }
     }
 }
 
-impl From<(&str, EvalErr)> for CLIErrors {
-    fn from((query, err): (&str, EvalErr)) -> Self {
-        let related = err.errors.into_iter().map(|e| (query, e).into()).collect();
+impl From<(&Parsed<'_>, EvalErr)> for CLIErrors {
+    fn from((parsed, err): (&Parsed<'_>, EvalErr)) -> Self {
         CLIErrors {
-            query: query.to_string(),
-            related,
+            query: parsed.text.to_string(),
+            related: evaluation_errors(parsed, err.errors),
         }
     }
 }
 
-impl From<(&str, PlanErr)> for CLIErrors {
-    fn from((query, err): (&str, PlanErr)) -> Self {
-        let related = err.errors.into_iter().map(|e| (query, e).into()).collect();
+/// The errors of evaluating `parsed`, each pointing at the part of the query it is about if known.
+pub(crate) fn evaluation_errors(
+    parsed: &Parsed<'_>,
+    errors: Vec<EvaluationError>,
+) -> Vec<CLIError> {
+    let mut culprits = Culprits::of(parsed);
+    errors
+        .into_iter()
+        .map(|e| {
+            let loc = evaluation_culprit(&e).and_then(|c| culprits.take(&c));
+            CLIError::from((parsed.text, e)).located(loc)
+        })
+        .collect()
+}
+
+impl From<(&Parsed<'_>, PlanErr)> for CLIErrors {
+    fn from((parsed, err): (&Parsed<'_>, PlanErr)) -> Self {
+        let mut culprits = Culprits::of(parsed);
+        let related = err
+            .errors
+            .into_iter()
+            .map(|e| {
+                let loc = planning_culprit(&e).and_then(|c| culprits.take(&c));
+                CLIError::from((parsed.text, e)).located(loc)
+            })
+            .collect();
         CLIErrors {
-            query: query.to_string(),
+            query: parsed.text.to_string(),
             related,
         }
     }
 }
 
+/// The CLI's exit status for each class of failure, so that scripts can tell why a command failed.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum ExitStatus {
+    /// A failure in no other class, e.g. of options that can't be used together
+    Failure = 1,
+    /// The command line couldn't be parsed; reported by `clap` itself
+    Usage = 2,
+    /// The query isn't valid PartiQL
+    Syntax = 3,
+    /// The query couldn't be compiled or planned
+    Compile = 4,
+    /// The query failed while being evaluated, or warned with `--warnings-as-errors`
+    Evaluation = 5,
+    /// The input environment couldn't be read
+    Environment = 6,
+    /// The CLI or PartiQL itself failed, e.g. by panicking or drawing a graph it can't render
+    Internal = 7,
+}
+
+impl ExitStatus {
+    /// The class of failure of `report`, if it is a [`CLIErrors`]: that of its first related error,
+    /// unless one of them is internal. An internal error takes precedence, as the errors alongside
+    /// it may be caused by the same bug rather than by the query.
+    pub fn of(report: &miette::Report) -> Self {
+        let Some(errors) = report.downcast_ref::<CLIErrors>() else {
+            return ExitStatus::Failure;
+        };
+        let mut statuses = errors.related.iter().map(CLIError::exit_status);
+        let first = statuses.next().unwrap_or(ExitStatus::Failure);
+        if statuses.any(|status| status == ExitStatus::Internal) {
+            ExitStatus::Internal
+        } else {
+            first
+        }
+    }
+}
+
+impl From<ExitStatus> for std::process::ExitCode {
+    fn from(status: ExitStatus) -> Self {
+        std::process::ExitCode::from(status as u8)
+    }
+}
+
+/// Writes `report` to stderr as a record in `format`, with a record of each of its related errors
+/// if it is a [`CLIErrors`].
+pub fn report_error(format: ErrorFormat, report: &miette::Report) {
+    let record = match report.downcast_ref::<CLIErrors>() {
+        Some(errors) => ErrorRecord::of(errors),
+        None => ErrorRecord {
+            query: None,
+            message: report.to_string(),
+            list: "errors",
+            errors: vec![],
+        },
+    };
+    match format {
+        ErrorFormat::Human => eprintln!("Error: {report:?}"),
+        ErrorFormat::Json => eprintln!("{}", record.to_json()),
+        ErrorFormat::Ion => record.eprint_ion(),
+    }
+}
+
+/// Writes a record of the panic `info` to stderr in `format`, in place of the default panic message.
+pub fn report_panic(format: ErrorFormat, info: &std::panic::PanicHookInfo) {
+    let payload = info.payload();
+    let msg = match (
+        payload.downcast_ref::<&str>(),
+        payload.downcast_ref::<String>(),
+    ) {
+        (Some(msg), _) => msg.to_string(),
+        (_, Some(msg)) => msg.clone(),
+        _ => "Box<dyn Any>".to_string(),
+    };
+    let msg = match info.location() {
+        Some(location) => format!("{msg} at {location}"),
+        None => msg,
+    };
+    report_error(format, &CLIErrors::from(CLIError::Panic { msg }).into());
+}
+
+/// A failure, or the warnings of a query, as reported by `--error-format json` or `ion`.
+pub(crate) struct ErrorRecord {
+    pub(crate) query: Option<String>,
+    pub(crate) message: String,
+    /// The name of the record's list of `errors`; `warnings` for warnings
+    pub(crate) list: &'static str,
+    pub(crate) errors: Vec<ErrorEntry>,
+}
+
+/// One of the errors of a failure, or one of the warnings of a query.
+pub(crate) struct ErrorEntry {
+    kind: &'static str,
+    code: Option<String>,
+    message: String,
+    help: Option<String>,
+    /// The byte range of the query that the error is about
+    span: Option<Range<usize>>,
+    /// The 1-based line and column of the start of `span`
+    position: Option<(usize, usize)>,
+}
+
+impl ErrorEntry {
+    /// The entry for `diagnostic`, a `kind` of error in `query` that says `message`.
+    pub(crate) fn of(
+        query: &str,
+        kind: &'static str,
+        message: String,
+        diagnostic: &dyn Diagnostic,
+    ) -> Self {
+        let span = diagnostic
+            .labels()
+            .and_then(|mut labels| labels.next())
+            .map(|label| label.offset()..label.offset() + label.len());
+        let position = span.as_ref().map(|span| line_column(query, span.start));
+        ErrorEntry {
+            kind,
+            code: diagnostic.code().map(|code| code.to_string()),
+            message,
+            help: diagnostic.help().map(|help| help.to_string()),
+            span,
+            position,
+        }
+    }
+}
+
+impl ErrorRecord {
+    fn of(errors: &CLIErrors) -> Self {
+        let entries = errors
+            .related
+            .iter()
+            .map(|err| ErrorEntry::of(&errors.query, err.kind(), err.message(), err))
+            .collect();
+        ErrorRecord {
+            query: (!errors.query.is_empty()).then(|| errors.query.clone()),
+            message: errors.to_string(),
+            list: "errors",
+            errors: entries,
+        }
+    }
+
+    pub(crate) fn to_json(&self) -> String {
+        let opt = |s: &Option<String>| s.as_deref().map_or("null".to_string(), json_string);
+        let errors: Vec<_> = self
+            .errors
+            .iter()
+            .map(|e| {
+                let span = e.span.as_ref().map_or("null".to_string(), |span| {
+                    format!("{{\"start\":{},\"end\":{}}}", span.start, span.end)
+                });
+                let (line, column) = match e.position {
+                    Some((line, column)) => (line.to_string(), column.to_string()),
+                    None => ("null".to_string(), "null".to_string()),
+                };
+                let fields = [
+                    ("kind", json_string(e.kind)),
+                    ("code", opt(&e.code)),
+                    ("message", json_string(&e.message)),
+                    ("help", opt(&e.help)),
+                    ("span", span),
+                    ("line", line),
+                    ("column", column),
+                ];
+                let fields: Vec<_> = fields.iter().map(|(k, v)| format!("\"{k}\":{v}")).collect();
+                format!("{{{}}}", fields.join(","))
+            })
+            .collect();
+        format!(
+            "{{\"query\":{},\"message\":{},\"{}\":[{}]}}",
+            opt(&self.query),
+            json_string(&self.message),
+            self.list,
+            errors.join(",")
+        )
+    }
+
+    /// Writes the record to stderr as a single line of Ion.
+    pub(crate) fn eprint_ion(&self) {
+        let mut err = std::io::stderr().lock();
+        let mut writer = ion_rs::TextWriterBuilder::lines()
+            .build(&mut err)
+            .expect("ion writer");
+        self.write_ion(&mut writer).expect("ion write");
+        writer.flush().expect("ion flush");
+        drop(writer);
+        writeln!(err).expect("ion newline");
+    }
+
+    fn write_ion<W: IonWriter>(&self, writer: &mut W) -> ion_rs::IonResult<()> {
+        fn write_opt<W: IonWriter>(
+            writer: &mut W,
+            name: &str,
+            s: &Option<String>,
+        ) -> ion_rs::IonResult<()> {
+            writer.set_field_name(name);
+            match s {
+                Some(s) => writer.write_string(s),
+                None => writer.write_null(IonType::String),
+            }
+        }
+
+        writer.step_in(IonType::Struct)?;
+        write_opt(writer, "query", &self.query)?;
+        writer.set_field_name("message");
+        writer.write_string(&self.message)?;
+        writer.set_field_name(self.list);
+        writer.step_in(IonType::List)?;
+        for e in &self.errors {
+            writer.step_in(IonType::Struct)?;
+            writer.set_field_name("kind");
+            writer.write_string(e.kind)?;
+            write_opt(writer, "code", &e.code)?;
+            writer.set_field_name("message");
+            writer.write_string(&e.message)?;
+            write_opt(writer, "help", &e.help)?;
+            writer.set_field_name("span");
+            match &e.span {
+                Some(span) => {
+                    writer.step_in(IonType::Struct)?;
+                    writer.set_field_name("start");
+                    writer.write_i64(span.start as i64)?;
+                    writer.set_field_name("end");
+                    writer.write_i64(span.end as i64)?;
+                    writer.step_out()?;
+                }
+                None => writer.write_null(IonType::Struct)?,
+            }
+            let (line, column) = e.position.unzip();
+            for (name, value) in [("line", line), ("column", column)] {
+                writer.set_field_name(name);
+                match value {
+                    Some(value) => writer.write_i64(value as i64)?,
+                    None => writer.write_null(IonType::Int)?,
+                }
+            }
+            writer.step_out()?;
+        }
+        writer.step_out()?;
+        writer.step_out()
+    }
+}
+
+/// The 1-based line and column (in characters) of byte `offset` of `text`.
+fn line_column(text: &str, offset: usize) -> (usize, usize) {
+    let before = &text[..offset.min(text.len())];
+    let line_start = before.rfind('\n').map_or(0, |idx| idx + 1);
+    let line = before.matches('\n').count() + 1;
+    let column = before[line_start..].chars().count() + 1;
+    (line, column)
+}
+
+/// `s` as a JSON string literal.
+fn json_string(s: &str) -> String {
+    let mut out = String::with_capacity(s.len() + 2);
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if c.is_control() => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
+
 #[derive(Debug, Error)]
 pub enum CLIError {
     #[error("PartiQL syntax error:")]
@@ -76,30 +408,141 @@ pub enum CLIError {
         loc: Location<BytePosition>,
     },
 
-    #[error("PartiQL compile error:")]
+    #[error("PartiQL compile error: {msg}")]
     CompileError {
         src: String,
         msg: String,
-        // TODO loc: Location<BytePosition>,
+        loc: Option<Location<BytePosition>>,
     },
 
     #[error("Internal Compiler Error: `{msg}`\nplease report this (https://github.com/partiql/partiql-lang-rust/issues).")]
-    InternalCompilerError { src: String, msg: String },
+    InternalCompilerError {
+        src: String,
+        msg: String,
+        loc: Option<Location<BytePosition>>,
+    },
+
+    #[error("PartiQL planning error: {msg}")]
+    PlanningError {
+        src: String,
+        msg: String,
+        help: Option<String>,
+        loc: Option<Location<BytePosition>>,
+    },
+
+    #[error("PartiQL planning error: {feature} is not yet implemented")]
+    PlanningNotYetImplemented {
+        src: String,
+        feature: String,
+        loc: Option<Location<BytePosition>>,
+    },
+
+    #[error("PartiQL evaluation error: {msg}")]
+    EvaluationError {
+        src: String,
+        msg: String,
+        help: Option<String>,
+    },
+
+    #[error("PartiQL evaluation error: {feature} is not yet implemented")]
+    EvaluationNotYetImplemented {
+        src: String,
+        feature: String,
+        help: Option<String>,
+        loc: Option<Location<BytePosition>>,
+    },
+
+    #[error("PartiQL extension function error: {msg}")]
+    ExtensionError { src: String, msg: String },
+
+    #[error(
+        "PartiQL evaluation produced {count} warning(s), which `--warnings-as-errors` makes fatal"
+    )]
+    WarningsAsErrors { count: usize },
 
     #[error("I/O Error reading input environment")]
     IOReadError,
 
+    #[error("Invalid input environment `{path}`: {msg}")]
+    EnvironmentError { path: String, msg: String },
+
+    #[error("Could not render the graph: {msg}")]
+    RenderError { msg: String },
+
+    #[error("The CLI panicked: {msg}")]
+    Panic { msg: String },
+
     #[error("Unknown error: {0}")]
     UnknownError(String),
 }
 
 impl Diagnostic for CLIError {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let code = match self {
+            CLIError::SyntaxError { .. } => "partiql::syntax",
+            CLIError::CompileError { .. } => "partiql::compile",
+            CLIError::InternalCompilerError { .. } => "partiql::internal",
+            CLIError::PlanningError { .. } => "partiql::plan",
+            CLIError::PlanningNotYetImplemented { .. } => "partiql::plan::not_yet_implemented",
+            CLIError::EvaluationError { .. } => "partiql::eval",
+            CLIError::EvaluationNotYetImplemented { .. } => "partiql::eval::not_yet_implemented",
+            CLIError::ExtensionError { .. } => "partiql::eval::extension",
+            CLIError::WarningsAsErrors { .. } => "partiql::eval::warnings",
+            CLIError::IOReadError => "partiql::io",
+            CLIError::EnvironmentError { .. } => "partiql::environment",
+            CLIError::RenderError { .. } => "partiql::render",
+            CLIError::Panic { .. } => "partiql::panic",
+            CLIError::UnknownError(_) => "partiql::unknown",
+        };
+        Some(Box::new(code))
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let help: &str = match self {
+            // with no query to point into, the label saying what is wrong can't be shown
+            CLIError::SyntaxError { src, .. } if src.trim().is_empty() => "the query is empty",
+            CLIError::PlanningError { help, .. }
+            | CLIError::EvaluationError { help, .. }
+            | CLIError::EvaluationNotYetImplemented { help, .. } => help.as_deref()?,
+            CLIError::PlanningNotYetImplemented { .. } => {
+                "the evaluator doesn't support this yet; rewrite the query without it"
+            }
+            CLIError::ExtensionError { .. } => {
+                "check the extension function's arguments, e.g. that the file passed to `read_ion` exists and holds Ion"
+            }
+            CLIError::WarningsAsErrors { .. } => {
+                "fix the data or the query so the warnings above go away, or drop `--warnings-as-errors`"
+            }
+            CLIError::IOReadError => "check that the environment file exists and is readable",
+            CLIError::EnvironmentError { .. } => {
+                "the environment is a struct of global bindings, as a PartiQL value in a `.env` file or an Ion value in an `.ion` file"
+            }
+            CLIError::RenderError { .. } => {
+                "this is likely a bug in the graph's output; `-T dot` still prints the graph"
+            }
+            CLIError::Panic { .. } => {
+                "this is a bug; please report it (https://github.com/partiql/partiql-rust-cli/issues)"
+            }
+            _ => return None,
+        };
+        Some(Box::new(help))
+    }
+
     fn source_code(&self) -> Option<&dyn SourceCode> {
         match self {
             CLIError::SyntaxError { src, .. } => Some(src),
             CLIError::InternalCompilerError { src, .. } => Some(src),
             CLIError::IOReadError => None,
-            CLIError::CompileError { msg, src } => Some(src),
+            CLIError::EnvironmentError { .. } => None,
+            CLIError::CompileError { src, .. } => Some(src),
+            CLIError::PlanningError { src, .. } => Some(src),
+            CLIError::PlanningNotYetImplemented { src, .. } => Some(src),
+            CLIError::EvaluationError { src, .. } => Some(src),
+            CLIError::EvaluationNotYetImplemented { src, .. } => Some(src),
+            CLIError::ExtensionError { src, .. } => Some(src),
+            CLIError::WarningsAsErrors { .. } => None,
+            CLIError::RenderError { .. } => None,
+            CLIError::Panic { .. } => None,
             CLIError::UnknownError(_) => None,
         }
     }
@@ -107,20 +550,110 @@ impl Diagnostic for CLIError {
     fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
         match self {
             CLIError::SyntaxError { msg, loc, .. } => {
-                Some(Box::new(std::iter::once(LabeledSpan::new(
-                    Some(msg.to_string()),
-                    loc.start.0 .0 as usize,
-                    loc.end.0 .0 as usize - loc.start.0 .0 as usize,
-                ))))
+                Some(Box::new(std::iter::once(label(msg, loc))))
             }
-            CLIError::InternalCompilerError { .. } => None,
+            CLIError::InternalCompilerError { msg, loc, .. } => loc
+                .as_ref()
+                .map(|loc| Box::new(std::iter::once(label(msg, loc))) as Box<_>),
             CLIError::IOReadError => None,
-            CLIError::CompileError { .. } => None,
+            CLIError::EnvironmentError { .. } => None,
+            CLIError::CompileError { loc, .. }
+            | CLIError::PlanningError { loc, .. }
+            | CLIError::PlanningNotYetImplemented { loc, .. }
+            | CLIError::EvaluationNotYetImplemented { loc, .. } => loc
+                .as_ref()
+                .map(|loc| Box::new(std::iter::once(underline(loc))) as Box<_>),
+            CLIError::EvaluationError { .. } => None,
+            CLIError::ExtensionError { .. } => None,
+            CLIError::WarningsAsErrors { .. } => None,
+            CLIError::RenderError { .. } => None,
+            CLIError::Panic { .. } => None,
             CLIError::UnknownError(_) => None,
         }
     }
 }
 
+/// Underlines `loc`, for errors whose message already says what is wrong there.
+pub(crate) fn underline(loc: &Location<BytePosition>) -> LabeledSpan {
+    LabeledSpan::underline(loc.start.0 .0 as usize..loc.end.0 .0 as usize)
+}
+
+fn label(msg: &str, loc: &Location<BytePosition>) -> LabeledSpan {
+    LabeledSpan::new(
+        Some(msg.to_string()),
+        loc.start.0 .0 as usize,
+        loc.end.0 .0 as usize - loc.start.0 .0 as usize,
+    )
+}
+
+impl CLIError {
+    /// The name of the kind of error.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CLIError::SyntaxError { .. } => "syntax_error",
+            CLIError::CompileError { .. } => "compile_error",
+            CLIError::InternalCompilerError { .. } => "internal_compiler_error",
+            CLIError::PlanningError { .. } => "planning_error",
+            CLIError::PlanningNotYetImplemented { .. } => "planning_not_yet_implemented",
+            CLIError::EvaluationError { .. } => "evaluation_error",
+            CLIError::EvaluationNotYetImplemented { .. } => "evaluation_not_yet_implemented",
+            CLIError::ExtensionError { .. } => "extension_error",
+            CLIError::WarningsAsErrors { .. } => "warnings_as_errors",
+            CLIError::IOReadError => "io_read_error",
+            CLIError::EnvironmentError { .. } => "environment_error",
+            CLIError::RenderError { .. } => "render_error",
+            CLIError::Panic { .. } => "panic",
+            CLIError::UnknownError(_) => "unknown_error",
+        }
+    }
+
+    /// The class of failure the error is, for the CLI's exit status.
+    pub fn exit_status(&self) -> ExitStatus {
+        match self {
+            CLIError::SyntaxError { .. } => ExitStatus::Syntax,
+            CLIError::CompileError { .. }
+            | CLIError::PlanningError { .. }
+            | CLIError::PlanningNotYetImplemented { .. } => ExitStatus::Compile,
+            CLIError::EvaluationError { .. }
+            | CLIError::EvaluationNotYetImplemented { .. }
+            | CLIError::ExtensionError { .. }
+            | CLIError::WarningsAsErrors { .. } => ExitStatus::Evaluation,
+            CLIError::IOReadError | CLIError::EnvironmentError { .. } => ExitStatus::Environment,
+            CLIError::InternalCompilerError { .. }
+            | CLIError::RenderError { .. }
+            | CLIError::Panic { .. }
+            | CLIError::UnknownError(_) => ExitStatus::Internal,
+        }
+    }
+
+    /// What went wrong; for errors whose description is generic, the message of their label.
+    pub fn message(&self) -> String {
+        match self {
+            CLIError::SyntaxError { msg, .. } | CLIError::InternalCompilerError { msg, .. } => {
+                msg.clone()
+            }
+            other => other.to_string(),
+        }
+    }
+
+    /// Points a compile, planning or evaluation error at `loc` in its query, if it isn't already.
+    fn located(mut self, loc: Option<Location<BytePosition>>) -> Self {
+        match &mut self {
+            CLIError::CompileError { loc: at, .. }
+            | CLIError::InternalCompilerError { loc: at, .. }
+            | CLIError::PlanningError { loc: at, .. }
+            | CLIError::PlanningNotYetImplemented { loc: at, .. }
+            | CLIError::EvaluationNotYetImplemented { loc: at, .. }
+                if at.is_none() =>
+            {
+                *at = loc;
+            }
+            _ => {}
+        }
+        self
+    }
+}
+
 impl From<std::io::Error> for CLIError {
     fn from(_: Error) -> Self {
         CLIError::IOReadError
@@ -163,12 +696,13 @@ impl<'a> From<(&str, ParseError<'a>)> for CLIError {
             ParseError::IllegalState(error) => CLIError::InternalCompilerError {
                 msg: format!("Parser Illegal State: {error}"),
                 src: source.to_string(),
+                loc: None,
             },
             ParseError::UnexpectedEndOfInput => {
                 // Since `UnexpectedEndOfInput` doesn't include a source location, have the CLIError
                 // point to the end of the input source. Tracking issue to add source location
                 // to `UnexpectedEndOfInput`: https://github.com/partiql/partiql-lang-rust/issues/350
-                let last_char = (source.len() - 1) as u32;
+                let last_char = source.len().saturating_sub(1) as u32;
                 CLIError::SyntaxError {
                     src: source.to_string(),
                     msg: "Unexpected end of input".to_string(),
@@ -189,26 +723,41 @@ impl From<(&str, AstTransformError)> for CLIError {
             AstTransformError::IllegalState(error) => CLIError::InternalCompilerError {
                 msg: format!("Compiler Illegal State: {error}"),
                 src: source.to_string(),
+                loc: None,
             },
             AstTransformError::Literal { literal, error } => CLIError::CompileError {
                 msg: format!(
                     "Compiler literal value error. Literal: `{literal}`. Error: `{error}`"
                 ),
                 src: source.to_string(),
+                loc: None,
             },
             AstTransformError::InvalidNumberOfArguments(error) => CLIError::CompileError {
                 msg: format!("Compiler function error: Invalid number of args. Error: `{error}`"),
                 src: source.to_string(),
+                loc: None,
             },
             AstTransformError::UnsupportedFunction(error) => CLIError::CompileError {
                 msg: format!("Compiler function error: Unsupported function. Error: `{error}`"),
                 src: source.to_string(),
+                loc: None,
             },
             AstTransformError::UnsupportedAggregationFunction(error) => CLIError::CompileError {
                 msg: format!(
                     "Compiler function error: Unsupported aggregate function. Error: `{error}`"
                 ),
                 src: source.to_string(),
+                loc: None,
+            },
+            AstTransformError::NotYetImplemented(feature) => CLIError::CompileError {
+                msg: format!("Compiler error: Not yet implemented. Feature: `{feature}`"),
+                src: source.to_string(),
+                loc: None,
+            },
+            AstTransformError::HavingWithoutGroupBy => CLIError::CompileError {
+                msg: "Compiler error: HAVING clause provided without GROUP BY".to_string(),
+                src: source.to_string(),
+                loc: None,
             },
             other => CLIError::UnknownError(other.to_string()),
         }
@@ -218,23 +767,510 @@ impl From<(&str, AstTransformError)> for CLIError {
 impl From<(&str, PlanningError)> for CLIError {
     fn from((source, err): (&str, PlanningError)) -> Self {
         match err {
-            PlanningError::IllegalState(error) => CLIError::InternalCompilerError {
-                msg: format!("Planner Illegal State: {error}"),
+            PlanningError::IllegalState(error) => match planning_help(&error) {
+                Some(help) => CLIError::PlanningError {
+                    src: source.to_string(),
+                    msg: error,
+                    help: Some(help),
+                    loc: None,
+                },
+                None => CLIError::InternalCompilerError {
+                    msg: format!("Planner Illegal State: {error}"),
+                    src: source.to_string(),
+                    loc: None,
+                },
+            },
+            PlanningError::NotYetImplemented(feature) => CLIError::PlanningNotYetImplemented {
                 src: source.to_string(),
+                feature: feature
+                    .trim_end_matches(" not yet implemented in evaluator")
+                    .to_string(),
+                loc: None,
             },
             other => CLIError::UnknownError(other.to_string()),
         }
     }
 }
 
+/// A fix for a planning error that is caused by the query rather than the planner, if it is one.
+fn planning_help(msg: &str) -> Option<String> {
+    if let Some(pattern) = msg.strip_prefix("Invalid LIKE expression pattern: ") {
+        return Some(if pattern.contains(". Regex error: ") {
+            "simplify the LIKE pattern; it is too large to match against".to_string()
+        } else {
+            "a LIKE expression's ESCAPE must be a single character".to_string()
+        });
+    }
+    if msg == "Wrong number of arguments to coalesce" {
+        return Some("COALESCE takes at least one argument".to_string());
+    }
+    if let Some(name) = msg.strip_prefix("Wrong number of arguments for ") {
+        return Some(format!(
+            "check the number of arguments `{name}` is called with"
+        ));
+    }
+    if let Some(name) = msg.strip_prefix("Unknown error binding ") {
+        return Some(format!("check the arguments `{name}` is called with"));
+    }
+    let name = msg
+        .strip_prefix("Function call spec ")?
+        .split_whitespace()
+        .next()?;
+    Some(format!(
+        "`{name}` isn't a function the evaluator knows; check its name"
+    ))
+}
+
 impl From<(&str, EvaluationError)> for CLIError {
     fn from((query, err): (&str, EvaluationError)) -> Self {
         match err {
+            EvaluationError::IllegalState(error) => CLIError::EvaluationError {
+                help: evaluation_help(&error),
+                src: query.to_string(),
+                msg: error,
+            },
             EvaluationError::InvalidEvaluationPlan(error) => CLIError::InternalCompilerError {
                 msg: format!("Compiler function error: Invalid Plan. Error: `{error}`"),
                 src: query.to_string(),
+                loc: None,
+            },
+            EvaluationError::NotYetImplemented(feature) => CLIError::EvaluationNotYetImplemented {
+                help: Some(evaluation_nyi_help(&feature).to_string()),
+                src: query.to_string(),
+                feature,
+                loc: None,
+            },
+            EvaluationError::ExtensionResultError(error) => CLIError::ExtensionError {
+                src: query.to_string(),
+                msg: error.to_string(),
             },
             other => CLIError::UnknownError(other.to_string()),
         }
     }
 }
+
+/// A fix for an evaluation error: for a type mismatch, one in the query's data; for one of the
+/// evaluator's own invariants failing, a report of the bug. Other errors get no help.
+fn evaluation_help(msg: &str) -> Option<String> {
+    let help = if msg.starts_with("expected `(") {
+        "a function was called with an argument of the wrong type; check the types of the data it is called on"
+    } else if msg.starts_with("# of evaluated arguments") {
+        "a function was called with the wrong number of arguments; check the arguments it is called with"
+    } else if msg.starts_with("Left side of FROM source") {
+        "the left side of a join must be a collection of bindings, e.g. a bag of tuples"
+    } else if msg.starts_with("Error in retrieving ")
+        || msg.starts_with("Bad finalize state for ")
+        || msg == "Invalid branch number"
+    {
+        "this is likely a bug in the evaluator; please report it (https://github.com/partiql/partiql-lang-rust/issues)"
+    } else {
+        return None;
+    };
+    Some(help.to_string())
+}
+
+/// A way to avoid a feature that the evaluator doesn't implement.
+fn evaluation_nyi_help(feature: &str) -> &'static str {
+    match feature {
+        "FULL and RIGHT JOIN" => {
+            "rewrite a RIGHT JOIN as a LEFT JOIN with its sides swapped; a FULL JOIN isn't supported"
+        }
+        "GROUP PARTIAL" => "use GROUP BY without PARTIAL",
+        "`IS` for other types" => "only `IS NULL` and `IS MISSING` are supported",
+        _ => "the evaluator doesn't support this yet; rewrite the query without it",
+    }
+}
+
+/// A construct of a query that a compile error can be blamed on.
+#[derive(Debug, PartialEq, Eq)]
+enum Culprit {
+    /// A call of the function with the (lowercase) name
+    Call(String),
+    /// An embedded Ion literal with the contents
+    IonLit(String),
+    /// A literal of a kind lowering doesn't implement, named as in its errors (e.g., `Lit::TypedLit`)
+    Lit(String),
+    /// A `HAVING` clause without a `GROUP BY`
+    Having,
+    /// A `LIKE` expression
+    Like,
+    /// A `RIGHT` or `FULL` join
+    OuterJoin,
+    /// A `GROUP PARTIAL` clause
+    GroupPartial,
+}
+
+/// The construct that a lowering error is about, if it names one.
+fn ast_transform_culprit(err: &AstTransformError) -> Option<Culprit> {
+    match err {
+        AstTransformError::InvalidNumberOfArguments(name)
+        | AstTransformError::UnsupportedFunction(name)
+        | AstTransformError::UnsupportedAggregationFunction(name) => {
+            Some(Culprit::Call(name.to_lowercase()))
+        }
+        AstTransformError::Literal { literal, .. } => Some(Culprit::IonLit(literal.clone())),
+        AstTransformError::NotYetImplemented(feature) => Some(Culprit::Lit(feature.clone())),
+        AstTransformError::HavingWithoutGroupBy => Some(Culprit::Having),
+        _ => None,
+    }
+}
+
+/// The construct that a planning error is about, if its message names one.
+fn planning_culprit(err: &PlanningError) -> Option<Culprit> {
+    let msg = match err {
+        PlanningError::IllegalState(msg) => msg,
+        // features are named after the functions that aren't implemented, if any
+        PlanningError::NotYetImplemented(feature) => {
+            return Some(Culprit::Call(feature.to_lowercase()))
+        }
+        _ => return None,
+    };
+    if msg.starts_with("Invalid LIKE expression pattern") {
+        return Some(Culprit::Like);
+    }
+    if msg == "Wrong number of arguments to coalesce" {
+        return Some(Culprit::Call("coalesce".to_string()));
+    }
+    let name = [
+        "Wrong number of arguments for ",
+        "Unknown error binding ",
+        "Function call spec ",
+    ]
+    .iter()
+    .find_map(|prefix| msg.strip_prefix(prefix))?;
+    let name = name.split_whitespace().next()?;
+    Some(Culprit::Call(name.to_lowercase()))
+}
+
+/// The construct that an evaluation error is about, if it names one.
+fn evaluation_culprit(err: &EvaluationError) -> Option<Culprit> {
+    match err {
+        EvaluationError::NotYetImplemented(feature) => match feature.as_str() {
+            "FULL and RIGHT JOIN" => Some(Culprit::OuterJoin),
+            "GROUP PARTIAL" => Some(Culprit::GroupPartial),
+            _ => None,
+        },
+        _ => None,
+    }
+}
+
+/// The constructs of a query that errors can be blamed on, in the order lowering visits them.
+///
+/// Neither lowering nor planning errors carry the location of what caused them, so each error is
+/// instead blamed on the next construct it names, at that construct's location.
+struct Culprits<'a> {
+    parsed: &'a Parsed<'a>,
+    found: Vec<(Culprit, NodeId)>,
+}
+
+impl<'a> Culprits<'a> {
+    fn of(parsed: &'a Parsed<'a>) -> Self {
+        let mut finder = CulpritFinder::default();
+        parsed.ast.visit(&mut finder);
+        Culprits {
+            parsed,
+            found: finder.found,
+        }
+    }
+
+    /// The location of the next construct that is `culprit`, which isn't blamed for later errors.
+    fn take(&mut self, culprit: &Culprit) -> Option<Location<BytePosition>> {
+        let idx = self.found.iter().position(|(c, _)| c == culprit)?;
+        let (_, id) = self.found.remove(idx);
+        self.parsed.locations.get(&id).cloned()
+    }
+}
+
+#[derive(Default)]
+struct CulpritFinder {
+    /// The id of the most recently entered [`ast::AstNode`]
+    pending: Option<NodeId>,
+    /// The ids of the calls being visited; calls are found as they are exited, as lowering does
+    calls: Vec<Option<NodeId>>,
+    found: Vec<(Culprit, NodeId)>,
+}
+
+impl CulpritFinder {
+    fn exit_call_named(&mut self, name: &ast::SymbolPrimitive) -> Traverse {
+        if let Some(id) = self.calls.pop().flatten() {
+            self.found
+                .push((Culprit::Call(name.value.to_lowercase()), id));
+        }
+        Traverse::Continue
+    }
+}
+
+impl<'ast> Visitor<'ast> for CulpritFinder {
+    fn enter_ast_node(&mut self, id: NodeId) -> Traverse {
+        self.pending = Some(id);
+        Traverse::Continue
+    }
+
+    fn enter_select(&mut self, select: &'ast ast::Select) -> Traverse {
+        if let (Some(having), None) = (&select.having, &select.group_by) {
+            self.found.push((Culprit::Having, having.id));
+        }
+        Traverse::Continue
+    }
+
+    fn enter_join(&mut self, join: &'ast ast::Join) -> Traverse {
+        if matches!(join.kind, ast::JoinKind::Right | ast::JoinKind::Full) {
+            if let Some(id) = self.pending {
+                self.found.push((Culprit::OuterJoin, id));
+            }
+        }
+        Traverse::Continue
+    }
+
+    fn enter_group_by_expr(&mut self, group_by: &'ast ast::GroupByExpr) -> Traverse {
+        if let Some(ast::GroupingStrategy::GroupPartial) = group_by.strategy {
+            if let Some(id) = self.pending {
+                self.found.push((Culprit::GroupPartial, id));
+            }
+        }
+        Traverse::Continue
+    }
+
+    fn enter_lit(&mut self, lit: &'ast ast::Lit) -> Traverse {
+        let culprit = match lit {
+            ast::Lit::IonStringLit(contents) => Culprit::IonLit(contents.clone()),
+            ast::Lit::BitStringLit(_) => Culprit::Lit("Lit::BitStringLit".to_string()),
+            ast::Lit::HexStringLit(_) => Culprit::Lit("Lit::HexStringLit".to_string()),
+            ast::Lit::TypedLit(..) => Culprit::Lit("Lit::TypedLit".to_string()),
+            _ => return Traverse::Continue,
+        };
+        if let Some(id) = self.pending {
+            self.found.push((culprit, id));
+        }
+        Traverse::Continue
+    }
+
+    fn enter_like(&mut self, _like: &'ast ast::Like) -> Traverse {
+        if let Some(id) = self.pending {
+            self.found.push((Culprit::Like, id));
+        }
+        Traverse::Continue
+    }
+
+    fn enter_call(&mut self, _call: &'ast ast::Call) -> Traverse {
+        self.calls.push(self.pending);
+        Traverse::Continue
+    }
+    fn exit_call(&mut self, call: &'ast ast::Call) -> Traverse {
+        self.exit_call_named(&call.func_name)
+    }
+
+    fn enter_call_agg(&mut self, _call_agg: &'ast ast::CallAgg) -> Traverse {
+        self.calls.push(self.pending);
+        Traverse::Continue
+    }
+    fn exit_call_agg(&mut self, call_agg: &'ast ast::CallAgg) -> Traverse {
+        self.exit_call_named(&call_agg.func_name)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The span and help of each error of planning `query` that fails with `errors`.
+    fn plan_errors(
+        query: &str,
+        errors: &[PlanningError],
+    ) -> Vec<(Option<Range<usize>>, Option<String>)> {
+        let parsed = partiql_parser::Parser::default()
+            .parse(query)
+            .expect("parse");
+        let err = PlanErr {
+            errors: errors.to_vec(),
+        };
+        CLIErrors::from((&parsed, err))
+            .related
+            .iter()
+            .map(|err| {
+                let span = err
+                    .labels()
+                    .and_then(|mut labels| labels.next())
+                    .map(|label| label.offset()..label.offset() + label.len());
+                (span, err.help().map(|help| help.to_string()))
+            })
+            .collect()
+    }
+
+    fn illegal(msg: &str) -> PlanningError {
+        PlanningError::IllegalState(msg.to_string())
+    }
+
+    /// The byte range of the first `text` in `query`.
+    fn span_of(query: &str, text: &str) -> Option<Range<usize>> {
+        let start = query.find(text).expect("text in query");
+        Some(start..start + text.len())
+    }
+
+    #[test]
+    fn unknown_functions_are_located() {
+        let query = "SELECT a, foo(a) FROM t";
+        for msg in [
+            "Function call spec foo does not exist in catalog",
+            "Function call spec foo overload #1 does not exist in catalog",
+        ] {
+            let planned = plan_errors(query, &[illegal(msg)]);
+            assert_eq!(planned[0].0, span_of(query, "foo(a)"), "{msg}");
+            assert_eq!(
+                planned[0].1.as_deref(),
+                Some("`foo` isn't a function the evaluator knows; check its name")
+            );
+        }
+    }
+
+    #[test]
+    fn argument_errors_are_located() {
+        let query = "SELECT LOWER(a), UPPER(a, b) FROM t";
+        let planned = plan_errors(query, &[illegal("Wrong number of arguments for upper")]);
+        assert_eq!(planned[0].0, span_of(query, "UPPER(a, b)"));
+        assert_eq!(
+            planned[0].1.as_deref(),
+            Some("check the number of arguments `upper` is called with")
+        );
+
+        let planned = plan_errors(query, &[illegal("Unknown error binding lower")]);
+        assert_eq!(planned[0].0, span_of(query, "LOWER(a)"));
+        assert_eq!(
+            planned[0].1.as_deref(),
+            Some("check the arguments `lower` is called with")
+        );
+
+        let planned = plan_errors(query, &[illegal("Wrong number of arguments to coalesce")]);
+        assert_eq!(planned[0].0, None);
+        let query = "SELECT COALESCE() FROM t";
+        let planned = plan_errors(query, &[illegal("Wrong number of arguments to coalesce")]);
+        assert_eq!(planned[0].0, span_of(query, "COALESCE()"));
+        assert_eq!(
+            planned[0].1.as_deref(),
+            Some("COALESCE takes at least one argument")
+        );
+    }
+
+    #[test]
+    fn like_patterns_are_located() {
+        let query = "SELECT a FROM t WHERE a LIKE 'x' ESCAPE 'ab'";
+        let planned = plan_errors(query, &[illegal("Invalid LIKE expression pattern: ab")]);
+        assert_eq!(planned[0].0, span_of(query, "a LIKE 'x' ESCAPE 'ab'"));
+        assert_eq!(
+            planned[0].1.as_deref(),
+            Some("a LIKE expression's ESCAPE must be a single character")
+        );
+    }
+
+    #[test]
+    fn unimplemented_functions_are_located() {
+        let query = "SELECT bar(a) FROM t";
+        let planned = plan_errors(
+            query,
+            &[PlanningError::NotYetImplemented("BAR".to_string())],
+        );
+        assert_eq!(planned[0].0, span_of(query, "bar(a)"));
+    }
+
+    #[test]
+    fn each_call_is_blamed_once() {
+        let query = "SELECT foo(a), foo(b) FROM t";
+        let msg = "Function call spec foo does not exist in catalog";
+        let planned = plan_errors(query, &[illegal(msg), illegal(msg), illegal(msg)]);
+        let spans: Vec<_> = planned.into_iter().map(|(span, _)| span).collect();
+        assert_eq!(
+            spans,
+            vec![span_of(query, "foo(a)"), span_of(query, "foo(b)"), None]
+        );
+    }
+
+    #[test]
+    fn unmatched_errors_are_unlabeled() {
+        let query = "SELECT foo(a) FROM t";
+        // a message that names no construct is an internal error, without a label
+        let planned = plan_errors(query, &[illegal("Something unexpected")]);
+        assert_eq!(planned, vec![(None, None)]);
+        // as is a message naming a function the query doesn't call
+        let planned = plan_errors(
+            query,
+            &[illegal("Function call spec baz does not exist in catalog")],
+        );
+        assert_eq!(planned[0].0, None);
+        assert!(planned[0].1.is_some());
+    }
+
+    #[test]
+    fn only_internal_errors_are_blamed_on_the_evaluator() {
+        let bug = |msg: &str| evaluation_help(msg).is_some_and(|help| help.contains("bug"));
+        assert!(bug("Error in retrieving input value"));
+        assert!(bug("Bad finalize state for Avg"));
+        assert!(bug("Invalid branch number"));
+        assert!(!bug("expected `(int, int)`, found `(MISSING, int)`"));
+        assert!(!bug(
+            "# of evaluated arguments (1) does not match expectation 2"
+        ));
+        assert!(!bug(
+            "Left side of FROM source should result in a bag of bindings"
+        ));
+        assert_eq!(evaluation_help("Something unexpected"), None);
+    }
+
+    #[test]
+    fn internal_errors_take_precedence_in_the_exit_status() {
+        let status = |related: Vec<CLIError>| {
+            let report = CLIErrors {
+                query: String::new(),
+                related,
+            };
+            ExitStatus::of(&report.into())
+        };
+        let planning = || CLIError::PlanningError {
+            src: String::new(),
+            msg: String::new(),
+            help: None,
+            loc: None,
+        };
+        let render = || CLIError::RenderError { msg: String::new() };
+        assert_eq!(status(vec![]), ExitStatus::Failure);
+        assert_eq!(
+            status(vec![planning(), CLIError::IOReadError]),
+            ExitStatus::Compile
+        );
+        assert_eq!(status(vec![planning(), render()]), ExitStatus::Internal);
+        assert_eq!(status(vec![render(), planning()]), ExitStatus::Internal);
+    }
+
+    /// The errors of planning `query`, which is expected to fail.
+    fn plan_failure(query: &str) -> CLIErrors {
+        let compiler = crate::evaluate::Compiler::default();
+        let parsed = compiler.parse(query).expect("parse");
+        compiler.plan(&parsed).expect_err("plan to fail")
+    }
+
+    #[test]
+    fn compile_errors_say_what_is_wrong_where_they_can_be_located_or_not() {
+        let query = "SELECT a FROM t HAVING a > 1";
+        let errors = plan_failure(query);
+        let err = &errors.related[0];
+        assert!(err
+            .to_string()
+            .contains("HAVING clause provided without GROUP BY"));
+        let span = err.labels().and_then(|mut labels| labels.next());
+        let span = span.map(|label| label.offset()..label.offset() + label.len());
+        assert_eq!(span, span_of(query, "HAVING a > 1"));
+
+        let errors = plan_failure("SELECT (SELECT VALUE 1 FROM u) AS s FROM t");
+        let err = &errors.related[0];
+        assert!(err.labels().is_none());
+        assert!(err.to_string().contains("Subquery within project"), "{err}");
+    }
+
+    #[test]
+    fn empty_queries_are_syntax_errors() {
+        let compiler = crate::evaluate::Compiler::default();
+        for query in ["", " "] {
+            let report = compiler.parse(query).expect_err("parse to fail").into();
+            assert_eq!(ExitStatus::of(&report), ExitStatus::Syntax, "`{query}`");
+        }
+    }
+}
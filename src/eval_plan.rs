@@ -0,0 +1,230 @@
+//! Reads the operator graph of a compiled evaluation plan, to show what actually executes for a
+//! query.
+
+use partiql_eval::eval::EvalPlan;
+#[cfg(feature = "serde")]
+use serde::Serialize;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// An operator of an evaluation plan.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EvalOp {
+    /// The operator's index in the evaluation plan's graph
+    pub id: usize,
+    /// The evaluator's own description of the operator
+    pub label: String,
+}
+
+/// A flow of values from one operator of an evaluation plan into a branch of another.
+#[derive(Debug, Copy, Clone)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EvalFlow {
+    pub src: usize,
+    pub dst: usize,
+    pub branch: u8,
+}
+
+/// The inputs of each of an evaluation plan's operators, by branch, and the operators with no
+/// outputs.
+type EvalInputs = (HashMap<usize, Vec<(u8, usize)>>, Vec<usize>);
+
+/// The operators of an evaluation plan and the flows between them.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(Serialize))]
+pub struct EvalGraph {
+    pub operators: Vec<EvalOp>,
+    pub flows: Vec<EvalFlow>,
+}
+
+impl EvalGraph {
+    /// The operator with index `id`.
+    pub fn operator(&self, id: usize) -> Option<&EvalOp> {
+        self.operators.iter().find(|op| op.id == id)
+    }
+
+    /// The inputs of each operator, ordered by branch, and the operators with no outputs.
+    fn inputs(&self) -> EvalInputs {
+        let mut inputs: HashMap<usize, Vec<(u8, usize)>> = HashMap::new();
+        for flow in &self.flows {
+            inputs
+                .entry(flow.dst)
+                .or_default()
+                .push((flow.branch, flow.src));
+        }
+        for srcs in inputs.values_mut() {
+            srcs.sort_by_key(|(branch, _)| *branch);
+        }
+        let roots = self
+            .operators
+            .iter()
+            .map(|op| op.id)
+            .filter(|id| !self.flows.iter().any(|flow| flow.src == *id))
+            .collect();
+        (inputs, roots)
+    }
+}
+
+/// Reads the operator graph of a compiled evaluation plan.
+///
+/// The evaluator doesn't expose its plan's graph other than as a dot-formatted string, so that is
+/// read back into operators (labelled with their `Debug` descriptions) and flows.
+pub fn eval_graph(plan: &EvalPlan) -> EvalGraph {
+    let mut graph = EvalGraph::default();
+    for line in plan.to_dot_graph().lines() {
+        let Some((head, label)) = line.trim().split_once(" [ label = \"") else {
+            continue;
+        };
+        let Some(label) = label.strip_suffix("\" ]") else {
+            continue;
+        };
+        let label = unescape(label);
+        match head.split_once(" -> ") {
+            Some((src, dst)) => {
+                if let (Ok(src), Ok(dst), Ok(branch)) = (src.parse(), dst.parse(), label.parse()) {
+                    graph.flows.push(EvalFlow { src, dst, branch });
+                }
+            }
+            None => {
+                if let Ok(id) = head.parse() {
+                    graph.operators.push(EvalOp { id, label });
+                }
+            }
+        }
+    }
+    graph
+}
+
+/// Reverses the escaping of labels in `petgraph`'s dot output.
+fn unescape(label: &str) -> String {
+    let mut out = String::with_capacity(label.len());
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        if c != '\\' {
+            out.push(c);
+            continue;
+        }
+        match chars.next() {
+            // `\l` is a left-justified line break
+            Some('l') => out.push('\n'),
+            Some(c) => out.push(c),
+            None => out.push('\\'),
+        }
+    }
+    out
+}
+
+/// Renders an evaluation plan as an indented tree of operators, rooted at its sink.
+pub fn eval_graph_to_text(graph: &EvalGraph) -> String {
+    let (inputs, roots) = graph.inputs();
+
+    let mut out = String::new();
+    for id in roots {
+        write_op(&mut out, graph, &inputs, id, "", "", "").expect("eval plan text");
+    }
+    out
+}
+
+fn write_op(
+    out: &mut String,
+    graph: &EvalGraph,
+    inputs: &HashMap<usize, Vec<(u8, usize)>>,
+    id: usize,
+    branch: &str,
+    first_prefix: &str,
+    prefix: &str,
+) -> std::fmt::Result {
+    let label = graph.operator(id).map(|op| op.label.as_str()).unwrap_or("");
+    writeln!(out, "{first_prefix}{branch}#{id} {label}")?;
+
+    let srcs = inputs.get(&id).map(Vec::as_slice).unwrap_or_default();
+    for (idx, (branch, src)) in srcs.iter().enumerate() {
+        let last = idx == srcs.len() - 1;
+        let (first, rest) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let branch = if srcs.len() > 1 {
+            format!("[{branch}] ")
+        } else {
+            String::new()
+        };
+        write_op(
+            out,
+            graph,
+            inputs,
+            *src,
+            &branch,
+            &format!("{prefix}{first}"),
+            &format!("{prefix}{rest}"),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::Compiler;
+
+    fn graph_of(query: &str) -> EvalGraph {
+        let compiler = Compiler::default();
+        let parsed = compiler.parse(query).unwrap();
+        let plan = compiler.plan(&parsed).unwrap();
+        eval_graph(&compiler.compile(&parsed, &plan).unwrap())
+    }
+
+    #[test]
+    fn operators_are_read_with_their_labels() {
+        let graph = graph_of("SELECT a, count(*) AS c FROM t WHERE a > 1 GROUP BY a LIMIT 2");
+        assert_eq!(
+            eval_graph_to_text(&graph),
+            r#"#5 SINK
+└── #4 SELECT EvalDynamicLookup { lookups: [@a, ^a] } AS a, @"$__agg_1" AS c
+    └── #3 EvalLimitOffset { limit: Some(2), offset: None, input: None }
+        └── #2 EvalGroupBy { strategy: GroupFull, group: [EvalDynamicLookup { lookups: [@t.a, ^a] }], aliases: ["a"], aggs: [AggregateExpression { name: "$__agg_1", expr: 1, func: Count }], distinct_aggs: [], group_as_alias: None, input: None }
+            └── #1 EvalFilter { expr: BinaryValueExpr(EvalDynamicLookup { lookups: [@a, ^a] }, 1), input: None }
+                └── #0 SCAN EvalDynamicLookup { lookups: [^t] } AS t
+"#
+        );
+    }
+
+    #[test]
+    fn inputs_are_ordered_by_branch() {
+        let graph = graph_of("SELECT x.a FROM t AS x JOIN u AS y ON x.a = y.a");
+        assert_eq!(
+            eval_graph_to_text(&graph),
+            r#"#4 SINK
+└── #3 SELECT EvalDynamicLookup { lookups: [@x, ^x] }.a AS a
+    └── #1 Inner JOINON BinaryValueExpr(EvalDynamicLookup { lookups: [@x, ^x] }.a, EvalDynamicLookup { lookups: [@y, ^y] }.a)
+        ├── [0] #0 SCAN EvalDynamicLookup { lookups: [^t] } AS x
+        └── [1] #2 SCAN EvalDynamicLookup { lookups: [^u, @x.u] } AS y
+"#
+        );
+    }
+
+    #[test]
+    fn flows_are_read_with_their_branches() {
+        let graph = graph_of("SELECT a FROM t OUTER UNION SELECT b FROM u");
+        let mut flows: Vec<_> = graph
+            .flows
+            .iter()
+            .map(|flow| (flow.src, flow.dst, flow.branch))
+            .collect();
+        flows.sort();
+        assert_eq!(
+            flows,
+            [(0, 1, 0), (1, 4, 0), (2, 3, 0), (3, 4, 1), (4, 5, 0)]
+        );
+        assert_eq!(graph.operators.len(), 6);
+        assert_eq!(graph.operator(5).unwrap().label, "SINK");
+    }
+
+    #[test]
+    fn labels_are_unescaped() {
+        assert_eq!(unescape(r#"@\"a\"\l\\"#), "@\"a\"\n\\");
+        assert_eq!(unescape(r"trailing\"), r"trailing\");
+    }
+}
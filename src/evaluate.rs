@@ -1,9 +1,11 @@
 use crate::error::{CLIError, CLIErrors};
 
+use partiql_ast_passes::name_resolver::{KeyRegistry, NameResolver};
 use partiql_catalog::extension::Extension;
 use partiql_catalog::catalog::PartiqlCatalog;
 use partiql_eval::env::basic::MapBindings;
-use partiql_eval::eval::{BasicContext, EvalPlan, Evaluated};
+use partiql_eval::error::EvaluationError;
+use partiql_eval::eval::{BasicContext, EvalContext, EvalPlan, Evaluated};
 use partiql_eval::plan::EvaluationMode;
 use partiql_extension_ion::decode::IonDecoderConfig;
 use partiql_extension_ion::Encoding;
@@ -32,12 +34,19 @@ impl Compiler {
             .map_err(CLIErrors::from)
     }
 
+    /// Runs name resolution, the AST pass that lowering starts with, on its own.
+    pub fn resolve_names(&self, query: &Parsed) -> Result<KeyRegistry, CLIErrors> {
+        NameResolver::new(&self.catalog)
+            .resolve(&query.ast)
+            .map_err(|err| CLIErrors::from((query, err)))
+    }
+
     pub fn plan(&self, query: &Parsed) -> Result<LogicalPlan<BindingsOp>, CLIErrors> {
         let planner = partiql_logical_planner::LogicalPlanner::new(&self.catalog);
         let lowered = planner.lower(query);
         match lowered {
             Ok(plan) => Ok(plan),
-            Err(err) => Err(CLIErrors::from((query.text, err))),
+            Err(err) => Err(CLIErrors::from((query, err))),
         }
     }
 
@@ -50,22 +59,91 @@ impl Compiler {
             partiql_eval::plan::EvaluatorPlanner::new(EvaluationMode::Permissive, &self.catalog);
         compiler
             .compile(&plan)
-            .map_err(|err| CLIErrors::from((query.text, err)))
+            .map_err(|err| CLIErrors::from((query, err)))
     }
 
     pub fn evaluate(
         &self,
         query: &Parsed,
-        mut eval_plan: EvalPlan,
+        eval_plan: EvalPlan,
         bindings: MapBindings<Value>,
     ) -> Result<Evaluated, CLIErrors> {
+        self.evaluate_recorded(query, eval_plan, bindings)
+            .map(|(evaluated, _)| evaluated)
+    }
+
+    /// Evaluates `eval_plan`, along with the errors that permissive evaluation recorded rather than
+    /// failing on (e.g., from features the evaluator doesn't implement, or from failing extension
+    /// functions).
+    pub fn evaluate_recorded(
+        &self,
+        query: &Parsed,
+        mut eval_plan: EvalPlan,
+        bindings: MapBindings<Value>,
+    ) -> Result<(Evaluated, Vec<EvaluationError>), CLIErrors> {
         let sys = SystemContext {
             now: DateTime::from_system_now_utc(),
         };
         let ctx = BasicContext::new(bindings, sys);
-        eval_plan
+        let evaluated = eval_plan
             .execute_mut(&ctx)
-            .map_err(|err| CLIErrors::from((query.text, err)))
+            .map_err(|err| CLIErrors::from((query, err)))?;
+        Ok((evaluated, ctx.errors()))
+    }
+
+    /// Evaluates `plan` in strict mode, for the errors that permissive evaluation turns into
+    /// MISSING instead (e.g. type mismatches). Strict evaluation stops at the first of them.
+    pub fn strict_errors(
+        &self,
+        plan: &LogicalPlan<BindingsOp>,
+        bindings: MapBindings<Value>,
+    ) -> Vec<EvaluationError> {
+        let mut compiler =
+            partiql_eval::plan::EvaluatorPlanner::new(EvaluationMode::Strict, &self.catalog);
+        let Ok(mut eval_plan) = compiler.compile(plan) else {
+            return vec![];
+        };
+        let sys = SystemContext {
+            now: DateTime::from_system_now_utc(),
+        };
+        let ctx = BasicContext::new(bindings, sys);
+        match eval_plan.execute_mut(&ctx) {
+            Ok(_) => ctx.errors(),
+            Err(err) => err.errors,
+        }
+    }
+}
+
+/// Returns the names of the columns projected by a query's `SELECT` list, in order.
+///
+/// Walks back from the plan's sink through operators that do not change the shape of the output
+/// (e.g. `DISTINCT`, `ORDER BY`, `LIMIT`). Returns `None` if the output shape cannot be determined
+/// statically, e.g. for `SELECT *`, `SELECT VALUE`, or set operations.
+pub fn projected_columns(plan: &LogicalPlan<BindingsOp>) -> Option<Vec<String>> {
+    let (mut current, _) = plan
+        .operators_by_id()
+        .find(|(_, op)| matches!(op, BindingsOp::Sink))?;
+    loop {
+        let mut inputs = plan
+            .flows()
+            .iter()
+            .filter(|(_, dst, _)| *dst == current)
+            .map(|(src, _, _)| *src);
+        current = match (inputs.next(), inputs.next()) {
+            (Some(input), None) => input,
+            _ => return None,
+        };
+        match plan.operator(current)? {
+            BindingsOp::Project(project) => {
+                return Some(project.exprs.iter().map(|(k, _)| k.clone()).collect())
+            }
+            BindingsOp::Distinct
+            | BindingsOp::OrderBy(_)
+            | BindingsOp::LimitOffset(_)
+            | BindingsOp::Filter(_)
+            | BindingsOp::Having(_) => continue,
+            _ => return None,
+        }
     }
 }
 
@@ -90,40 +168,41 @@ pub fn get_bindings(environment: &Option<String>) -> Result<MapBindings<Value>,
         None => MapBindings::default(),
         Some(path) => {
             let path = Path::new(path);
-            match path.extension() {
-                // TODO: can replace panics with an actual `CLIError`
-                None => panic!("Expected path"),
-                Some(extension) => match extension.to_str() {
-                    Some("env") => {
-                        let buf =
-                            fs::read_to_string(path).map_err(|err| CLIErrors::from(("", err)))?;
-                        let env = evaluate(&buf, MapBindings::default())?.result;
-                        match env {
-                            Value::Tuple(t) => MapBindings::from(*t),
-                            _ => panic!("Expected a struct containing the input environment"),
-                        }
-                    }
-                    Some("ion") => {
-                        let buf =
-                            fs::read_to_string(path).map_err(|err| CLIErrors::from(("", err)))?;
-                        let reader = ion_rs::ReaderBuilder::new().build(buf).expect("ion reader");
-                        let mut decoder = partiql_extension_ion::decode::IonDecoderBuilder::new(
-                            IonDecoderConfig::default().with_mode(Encoding::PartiqlEncodedAsIon),
-                        )
-                            .build(reader)
-                            .expect("expected ion file");
-                        let env = decoder
-                            .next()
-                            .expect("expected single environment value in ion stream")
-                            .expect("expected single environment value in ion stream");
-
-                        match env {
-                            Value::Tuple(t) => MapBindings::from(*t),
-                            _ => panic!("Expected a struct containing the input environment"),
-                        }
-                    }
-                    _ => panic!("Expected one of `.env` or `.ion`"),
-                },
+            let env_err = |msg: String| {
+                CLIErrors::from(CLIError::EnvironmentError {
+                    path: path.display().to_string(),
+                    msg,
+                })
+            };
+            let env = match path.extension().and_then(|extension| extension.to_str()) {
+                Some("env") => {
+                    let buf = fs::read_to_string(path).map_err(|err| CLIErrors::from(("", err)))?;
+                    evaluate(&buf, MapBindings::default())?.result
+                }
+                Some("ion") => {
+                    let buf = fs::read_to_string(path).map_err(|err| CLIErrors::from(("", err)))?;
+                    let reader = ion_rs::ReaderBuilder::new()
+                        .build(buf)
+                        .map_err(|err| env_err(format!("invalid Ion: {err}")))?;
+                    let mut decoder = partiql_extension_ion::decode::IonDecoderBuilder::new(
+                        IonDecoderConfig::default().with_mode(Encoding::PartiqlEncodedAsIon),
+                    )
+                    .build(reader)
+                    .map_err(|err| env_err(format!("invalid Ion: {err}")))?;
+                    decoder
+                        .next()
+                        .ok_or_else(|| env_err("expected a value, found none".to_string()))?
+                        .map_err(|err| env_err(format!("invalid Ion: {err}")))?
+                }
+                _ => return Err(env_err("expected a `.env` or `.ion` file".to_string())),
+            };
+            match env {
+                Value::Tuple(t) => MapBindings::from(*t),
+                _ => {
+                    return Err(env_err(
+                        "expected a struct containing the input environment".to_string(),
+                    ))
+                }
             }
         }
     };
@@ -0,0 +1,941 @@
+use itertools::Itertools;
+use partiql_logical::{
+    AggFunc, AggregateExpression, BagOperator, BinaryOp, BindingsOp, CallExpr, CallName,
+    GroupingStrategy, JoinKind, LogicalPlan, OpId, PathComponent, Pattern, SetQuantifier,
+    SortSpecNullOrder, SortSpecOrder, Type, UnaryOp, ValueExpr,
+};
+use partiql_value::{BindingsName, Value};
+use std::cell::RefCell;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+/// The label of a plan operator: its name, followed by the details of its expressions.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(crate) struct OpLabel {
+    pub name: String,
+    pub fields: Vec<String>,
+}
+
+impl OpLabel {
+    fn new(name: impl Into<String>, fields: Vec<String>) -> Self {
+        OpLabel {
+            name: name.into(),
+            fields,
+        }
+    }
+}
+
+/// The subqueries of a plan, and of its subqueries in turn, numbered from 1 so that expressions can
+/// refer to them.
+///
+/// Subqueries are numbered in the order they appear in the labels of the plan's operators, listed
+/// by id, followed by those that first appear in the subqueries' own plans.
+pub(crate) struct Subqueries<'a> {
+    plans: RefCell<Vec<&'a LogicalPlan<BindingsOp>>>,
+}
+
+impl<'a> Subqueries<'a> {
+    pub(crate) fn of(plan: &'a LogicalPlan<BindingsOp>) -> Self {
+        let subqueries = Subqueries {
+            plans: RefCell::new(vec![]),
+        };
+        let mut next = Some(plan);
+        let mut idx = 0;
+        while let Some(plan) = next {
+            for (_, op) in plan.operators_by_id() {
+                op_label(op, &subqueries);
+            }
+            next = subqueries.plans.borrow().get(idx).copied();
+            idx += 1;
+        }
+        subqueries
+    }
+
+    /// The number of the subquery with the given plan.
+    fn number(&self, plan: &'a LogicalPlan<BindingsOp>) -> usize {
+        let mut plans = self.plans.borrow_mut();
+        match plans.iter().position(|p| std::ptr::eq(*p, plan)) {
+            Some(idx) => idx + 1,
+            None => {
+                plans.push(plan);
+                plans.len()
+            }
+        }
+    }
+
+    /// The subqueries' plans, with their numbers.
+    pub(crate) fn plans(&self) -> Vec<(usize, &'a LogicalPlan<BindingsOp>)> {
+        (1..).zip(self.plans.borrow().iter().copied()).collect()
+    }
+}
+
+pub(crate) fn op_label<'a>(op: &'a BindingsOp, subqueries: &Subqueries<'a>) -> OpLabel {
+    match op {
+        BindingsOp::Scan(s) => OpLabel::new(
+            "scan",
+            vec![expr_to_str(&s.expr, subqueries), format!("as {}", s.as_key)],
+        ),
+        BindingsOp::Pivot(p) => OpLabel::new(
+            "pivot",
+            vec![
+                expr_to_str(&p.value, subqueries),
+                format!("at {}", expr_to_str(&p.key, subqueries)),
+            ],
+        ),
+        BindingsOp::Unpivot(u) => {
+            let mut fields = vec![
+                expr_to_str(&u.expr, subqueries),
+                format!("as {}", &u.as_key),
+            ];
+            fields.extend(u.at_key.as_ref().map(|at| format!("at {at}")));
+            OpLabel::new("unpivot", fields)
+        }
+        BindingsOp::Filter(f) => OpLabel::new("filter", vec![expr_to_str(&f.expr, subqueries)]),
+        BindingsOp::OrderBy(o) => OpLabel::new(
+            "order by",
+            o.specs
+                .iter()
+                .map(|s| {
+                    let order = match s.order {
+                        SortSpecOrder::Asc => "ASC",
+                        SortSpecOrder::Desc => "DESC",
+                    };
+                    let null_order = match s.null_order {
+                        SortSpecNullOrder::First => "FIRST",
+                        SortSpecNullOrder::Last => "LAST",
+                    };
+                    format!(
+                        "{} {order} NULLS {null_order}",
+                        expr_to_str(&s.expr, subqueries)
+                    )
+                })
+                .collect(),
+        ),
+        BindingsOp::LimitOffset(lo) => {
+            let clauses = [
+                lo.limit
+                    .as_ref()
+                    .map(|e| format!("limit {}", expr_to_str(e, subqueries))),
+                lo.offset
+                    .as_ref()
+                    .map(|e| format!("offset {}", expr_to_str(e, subqueries))),
+            ];
+            let mut clauses = clauses.into_iter().flatten();
+            let name = clauses.next().unwrap_or_default();
+            OpLabel::new(name, clauses.collect())
+        }
+        BindingsOp::Join(join) => {
+            let kind = match join.kind {
+                JoinKind::Inner => "inner",
+                JoinKind::Left => "left",
+                JoinKind::Right => "right",
+                JoinKind::Full => "full",
+                JoinKind::Cross => "cross",
+            };
+            OpLabel::new(
+                format!("{kind} join"),
+                join.on.iter().map(|e| expr_to_str(e, subqueries)).collect(),
+            )
+        }
+        BindingsOp::BagOp(bag_op) => {
+            let kind = match bag_op.bag_op {
+                BagOperator::Union => "union",
+                BagOperator::Except => "except",
+                BagOperator::Intersect => "intersect",
+                BagOperator::OuterUnion => "outer union",
+                BagOperator::OuterExcept => "outer except",
+                BagOperator::OuterIntersect => "outer intersect",
+            };
+            let setq = match bag_op.setq {
+                SetQuantifier::All => "all",
+                SetQuantifier::Distinct => "distinct",
+            };
+            OpLabel::new(format!("{kind} {setq}"), vec![])
+        }
+        BindingsOp::Project(p) => OpLabel::new(
+            "project",
+            p.exprs
+                .iter()
+                .map(|(k, e)| format!("{}:{}", k, expr_to_str(e, subqueries)))
+                .collect(),
+        ),
+        BindingsOp::ProjectAll => OpLabel::new("project *", vec![]),
+        BindingsOp::ProjectValue(pv) => {
+            OpLabel::new("project value", vec![expr_to_str(&pv.expr, subqueries)])
+        }
+        BindingsOp::ExprQuery(eq) => OpLabel::new(expr_to_str(&eq.expr, subqueries), vec![]),
+        BindingsOp::Distinct => OpLabel::new("distinct", vec![]),
+        BindingsOp::GroupBy(g) => {
+            let mut fields = vec![
+                match g.strategy {
+                    GroupingStrategy::GroupFull => "FULL",
+                    GroupingStrategy::GroupPartial => "PARTIAL",
+                }
+                .to_string(),
+                format!(
+                    "keys: {}",
+                    g.exprs
+                        .iter()
+                        .map(|(k, e)| format!("{}:{}", k, expr_to_str(e, subqueries)))
+                        .join(", ")
+                ),
+                format!(
+                    "aggs: {}",
+                    g.aggregate_exprs
+                        .iter()
+                        .map(|agg| agg_expr_to_str(agg, subqueries))
+                        .join(", ")
+                ),
+            ];
+            fields.extend(g.group_as_alias.as_ref().map(|alias| format!("as {alias}")));
+            OpLabel::new("group by", fields)
+        }
+        BindingsOp::Having(h) => OpLabel::new("having", vec![expr_to_str(&h.expr, subqueries)]),
+        BindingsOp::Sink => OpLabel::new("sink", vec![]),
+    }
+}
+
+/// Renders an expression as PartiQL-like text; subqueries are shown as references to their number
+/// in `subqueries` rather than inlined.
+pub(crate) fn expr_to_str<'a>(expr: &'a ValueExpr, subqueries: &Subqueries<'a>) -> String {
+    match expr {
+        ValueExpr::UnExpr(op, operand) => match op {
+            UnaryOp::Not => format!("NOT {}", sub_expr(operand, Prec::Not, subqueries)),
+            UnaryOp::Neg => format!("-{}", sub_expr(operand, Prec::Unary, subqueries)),
+            UnaryOp::Pos => format!("+{}", sub_expr(operand, Prec::Unary, subqueries)),
+        },
+        ValueExpr::BinaryExpr(op, lhs, rhs) => {
+            let prec = bin_op_prec(op);
+            // operators are left-associative, so a right operand of equal precedence is grouped
+            format!(
+                "{} {} {}",
+                sub_expr(lhs, prec, subqueries),
+                bin_op_to_str(op),
+                sub_expr(rhs, prec.next(), subqueries)
+            )
+        }
+        ValueExpr::Lit(value) => format!("{value:?}"),
+        ValueExpr::DynamicLookup(lookups) => {
+            // a lookup resolves to the first alternative that's bound; the plain variable
+            // reference is the name as written in the query
+            let lookup = lookups
+                .iter()
+                .find(|l| matches!(l, ValueExpr::VarRef(..)))
+                .or_else(|| lookups.first());
+            lookup
+                .map(|e| expr_to_str(e, subqueries))
+                .unwrap_or_default()
+        }
+        ValueExpr::Path(root, steps) => {
+            let mut out = sub_expr(root, Prec::Primary, subqueries);
+            for step in steps {
+                match step {
+                    PathComponent::Key(name) => write!(out, ".{}", name_to_str(name)),
+                    PathComponent::Index(idx) => write!(out, "[{idx}]"),
+                    PathComponent::KeyExpr(e) | PathComponent::IndexExpr(e) => {
+                        write!(out, "[{}]", expr_to_str(e, subqueries))
+                    }
+                }
+                .expect("write path");
+            }
+            out
+        }
+        ValueExpr::VarRef(name, _) => name_to_str(name),
+        ValueExpr::TupleExpr(tuple) => {
+            let pairs = tuple.attrs.iter().zip(&tuple.values).map(|(k, v)| {
+                format!(
+                    "{}: {}",
+                    expr_to_str(k, subqueries),
+                    expr_to_str(v, subqueries)
+                )
+            });
+            format!("{{{}}}", pairs.format(", "))
+        }
+        ValueExpr::ListExpr(list) => format!("[{}]", exprs_to_str(&list.elements, subqueries)),
+        ValueExpr::BagExpr(bag) => format!("<<{}>>", exprs_to_str(&bag.elements, subqueries)),
+        ValueExpr::BetweenExpr(between) => format!(
+            "{} BETWEEN {} AND {}",
+            sub_expr(&between.value, Prec::Concat, subqueries),
+            sub_expr(&between.from, Prec::Concat, subqueries),
+            sub_expr(&between.to, Prec::Concat, subqueries)
+        ),
+        ValueExpr::PatternMatchExpr(pattern_match) => {
+            let value = sub_expr(&pattern_match.value, Prec::Concat, subqueries);
+            let (pattern, escape) = match &pattern_match.pattern {
+                Pattern::Like(like) => (
+                    quote(&like.pattern),
+                    (!like.escape.is_empty()).then(|| quote(&like.escape)),
+                ),
+                Pattern::LikeNonStringNonLiteral(like) => {
+                    // the planner fills in an empty escape when none is given
+                    let escape = match like.escape.as_ref() {
+                        ValueExpr::Lit(lit) if matches!(lit.as_ref(), Value::String(e) if e.is_empty()) => {
+                            None
+                        }
+                        escape => Some(sub_expr(escape, Prec::Concat, subqueries)),
+                    };
+                    (sub_expr(&like.pattern, Prec::Concat, subqueries), escape)
+                }
+            };
+            match escape {
+                Some(escape) => format!("{value} LIKE {pattern} ESCAPE {escape}"),
+                None => format!("{value} LIKE {pattern}"),
+            }
+        }
+        ValueExpr::SubQueryExpr(sub) => format!("(subquery #{})", subqueries.number(&sub.plan)),
+        ValueExpr::SimpleCase(case) => {
+            let mut out = format!("CASE {}", expr_to_str(&case.expr, subqueries));
+            write_cases(&mut out, &case.cases, &case.default, subqueries);
+            out
+        }
+        ValueExpr::SearchedCase(case) => {
+            let mut out = "CASE".to_string();
+            write_cases(&mut out, &case.cases, &case.default, subqueries);
+            out
+        }
+        ValueExpr::IsTypeExpr(is) => format!(
+            "{} IS {}{}",
+            sub_expr(&is.expr, Prec::Concat, subqueries),
+            if is.not { "NOT " } else { "" },
+            type_to_str(&is.is_type)
+        ),
+        ValueExpr::NullIfExpr(nullif) => format!(
+            "NULLIF({}, {})",
+            expr_to_str(&nullif.lhs, subqueries),
+            expr_to_str(&nullif.rhs, subqueries)
+        ),
+        ValueExpr::CoalesceExpr(coalesce) => {
+            format!("COALESCE({})", exprs_to_str(&coalesce.elements, subqueries))
+        }
+        ValueExpr::Call(call) => call_to_str(call, subqueries),
+    }
+}
+
+/// Operator precedence, from loosest to tightest binding.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, PartialOrd, Ord)]
+enum Prec {
+    Or,
+    And,
+    Not,
+    Comparison,
+    Concat,
+    Additive,
+    Multiplicative,
+    Exponent,
+    Unary,
+    Primary,
+}
+
+impl Prec {
+    fn next(self) -> Prec {
+        match self {
+            Prec::Or => Prec::And,
+            Prec::And => Prec::Not,
+            Prec::Not => Prec::Comparison,
+            Prec::Comparison => Prec::Concat,
+            Prec::Concat => Prec::Additive,
+            Prec::Additive => Prec::Multiplicative,
+            Prec::Multiplicative => Prec::Exponent,
+            Prec::Exponent => Prec::Unary,
+            Prec::Unary | Prec::Primary => Prec::Primary,
+        }
+    }
+}
+
+fn expr_prec(expr: &ValueExpr) -> Prec {
+    match expr {
+        ValueExpr::UnExpr(UnaryOp::Not, _) => Prec::Not,
+        ValueExpr::UnExpr(..) => Prec::Unary,
+        ValueExpr::BinaryExpr(op, ..) => bin_op_prec(op),
+        ValueExpr::BetweenExpr(_) | ValueExpr::PatternMatchExpr(_) | ValueExpr::IsTypeExpr(_) => {
+            Prec::Comparison
+        }
+        _ => Prec::Primary,
+    }
+}
+
+fn bin_op_prec(op: &BinaryOp) -> Prec {
+    match op {
+        BinaryOp::Or => Prec::Or,
+        BinaryOp::And => Prec::And,
+        BinaryOp::Eq
+        | BinaryOp::Neq
+        | BinaryOp::Gt
+        | BinaryOp::Gteq
+        | BinaryOp::Lt
+        | BinaryOp::Lteq
+        | BinaryOp::In => Prec::Comparison,
+        BinaryOp::Concat => Prec::Concat,
+        BinaryOp::Add | BinaryOp::Sub => Prec::Additive,
+        BinaryOp::Mul | BinaryOp::Div | BinaryOp::Mod => Prec::Multiplicative,
+        BinaryOp::Exp => Prec::Exponent,
+    }
+}
+
+fn bin_op_to_str(op: &BinaryOp) -> &'static str {
+    match op {
+        BinaryOp::And => "AND",
+        BinaryOp::Or => "OR",
+        BinaryOp::Concat => "||",
+        BinaryOp::Eq => "=",
+        BinaryOp::Neq => "<>",
+        BinaryOp::Gt => ">",
+        BinaryOp::Gteq => ">=",
+        BinaryOp::Lt => "<",
+        BinaryOp::Lteq => "<=",
+        BinaryOp::Add => "+",
+        BinaryOp::Sub => "-",
+        BinaryOp::Mul => "*",
+        BinaryOp::Div => "/",
+        BinaryOp::Mod => "%",
+        BinaryOp::Exp => "^",
+        BinaryOp::In => "IN",
+    }
+}
+
+/// Renders an operand, parenthesized if it binds more loosely than `prec`.
+fn sub_expr<'a>(expr: &'a ValueExpr, prec: Prec, subqueries: &Subqueries<'a>) -> String {
+    let out = expr_to_str(expr, subqueries);
+    if expr_prec(expr) < prec {
+        format!("({out})")
+    } else {
+        out
+    }
+}
+
+fn exprs_to_str<'a>(exprs: &'a [ValueExpr], subqueries: &Subqueries<'a>) -> String {
+    exprs.iter().map(|e| expr_to_str(e, subqueries)).join(", ")
+}
+
+fn name_to_str(name: &BindingsName<'_>) -> String {
+    match name {
+        BindingsName::CaseInsensitive(name) => name.to_string(),
+        BindingsName::CaseSensitive(name) => format!("\"{}\"", name.replace('"', "\"\"")),
+    }
+}
+
+fn quote(text: &str) -> String {
+    format!("'{}'", text.replace('\'', "''"))
+}
+
+fn write_cases<'a>(
+    out: &mut String,
+    cases: &'a [(Box<ValueExpr>, Box<ValueExpr>)],
+    default: &'a Option<Box<ValueExpr>>,
+    subqueries: &Subqueries<'a>,
+) {
+    for (when, then) in cases {
+        write!(
+            out,
+            " WHEN {} THEN {}",
+            expr_to_str(when, subqueries),
+            expr_to_str(then, subqueries)
+        )
+        .expect("write case");
+    }
+    if let Some(default) = default {
+        write!(out, " ELSE {}", expr_to_str(default, subqueries)).expect("write case");
+    }
+    out.push_str(" END");
+}
+
+fn type_to_str(ty: &Type) -> &'static str {
+    match ty {
+        Type::NullType => "NULL",
+        Type::BooleanType => "BOOL",
+        Type::Integer2Type => "SMALLINT",
+        Type::Integer4Type => "INT",
+        Type::Integer8Type => "BIGINT",
+        Type::DecimalType => "DECIMAL",
+        Type::NumericType => "NUMERIC",
+        Type::RealType => "REAL",
+        Type::DoublePrecisionType => "DOUBLE PRECISION",
+        Type::TimestampType => "TIMESTAMP",
+        Type::CharacterType => "CHAR",
+        Type::CharacterVaryingType => "VARCHAR",
+        Type::MissingType => "MISSING",
+        Type::StringType => "STRING",
+        Type::SymbolType => "SYMBOL",
+        Type::BlobType => "BLOB",
+        Type::ClobType => "CLOB",
+        Type::DateType => "DATE",
+        Type::TimeType => "TIME",
+        Type::ZonedTimestampType => "TIMESTAMP WITH TIME ZONE",
+        Type::StructType => "STRUCT",
+        Type::TupleType => "TUPLE",
+        Type::ListType => "LIST",
+        Type::SexpType => "SEXP",
+        Type::BagType => "BAG",
+        Type::AnyType => "ANY",
+    }
+}
+
+fn call_to_str<'a>(call: &'a CallExpr, subqueries: &Subqueries<'a>) -> String {
+    let extract = |field: &str| {
+        format!(
+            "extract({field} FROM {})",
+            exprs_to_str(&call.arguments, subqueries)
+        )
+    };
+    let coll = |name: &str, setq: &SetQuantifier| {
+        format!(
+            "{name}({}{})",
+            setq_prefix(setq),
+            exprs_to_str(&call.arguments, subqueries)
+        )
+    };
+    let name = match &call.name {
+        CallName::Lower => "lower",
+        CallName::Upper => "upper",
+        CallName::CharLength => "char_length",
+        CallName::OctetLength => "octet_length",
+        CallName::BitLength => "bit_length",
+        CallName::LTrim => "ltrim",
+        CallName::BTrim => "trim",
+        CallName::RTrim => "rtrim",
+        CallName::Substring => "substring",
+        CallName::Position => "position",
+        CallName::Overlay => "overlay",
+        CallName::Exists => "exists",
+        CallName::Abs => "abs",
+        CallName::Mod => "mod",
+        CallName::Cardinality => "cardinality",
+        CallName::ExtractYear => return extract("YEAR"),
+        CallName::ExtractMonth => return extract("MONTH"),
+        CallName::ExtractDay => return extract("DAY"),
+        CallName::ExtractHour => return extract("HOUR"),
+        CallName::ExtractMinute => return extract("MINUTE"),
+        CallName::ExtractSecond => return extract("SECOND"),
+        CallName::ExtractTimezoneHour => return extract("TIMEZONE_HOUR"),
+        CallName::ExtractTimezoneMinute => return extract("TIMEZONE_MINUTE"),
+        CallName::CollAvg(setq) => return coll("coll_avg", setq),
+        CallName::CollCount(setq) => return coll("coll_count", setq),
+        CallName::CollMax(setq) => return coll("coll_max", setq),
+        CallName::CollMin(setq) => return coll("coll_min", setq),
+        CallName::CollSum(setq) => return coll("coll_sum", setq),
+        CallName::CollAny(setq) => return coll("coll_any", setq),
+        CallName::CollEvery(setq) => return coll("coll_every", setq),
+        CallName::ByName(name) | CallName::ById(name, ..) => name,
+    };
+    format!("{name}({})", exprs_to_str(&call.arguments, subqueries))
+}
+
+fn setq_prefix(setq: &SetQuantifier) -> &'static str {
+    match setq {
+        SetQuantifier::All => "",
+        SetQuantifier::Distinct => "DISTINCT ",
+    }
+}
+
+pub(crate) fn agg_expr_to_str<'a>(
+    agg_expr: &'a AggregateExpression,
+    subqueries: &Subqueries<'a>,
+) -> String {
+    let func = match agg_expr.func {
+        AggFunc::AggAvg => "avg",
+        AggFunc::AggCount => "count",
+        AggFunc::AggMax => "max",
+        AggFunc::AggMin => "min",
+        AggFunc::AggSum => "sum",
+        AggFunc::AggAny => "any",
+        AggFunc::AggEvery => "every",
+    };
+    format!(
+        "{}:{func}({}{})",
+        agg_expr.name,
+        setq_prefix(&agg_expr.setq),
+        expr_to_str(&agg_expr.expr, subqueries)
+    )
+}
+
+/// The label of the flow into `op` on the given branch; the inputs of binary operators are
+/// labelled by side.
+pub(crate) fn branch_label(op: &BindingsOp, branch: u8) -> String {
+    match (op, branch) {
+        (BindingsOp::Join(_) | BindingsOp::BagOp(_), 0) => "left".to_string(),
+        (BindingsOp::Join(_) | BindingsOp::BagOp(_), 1) => "right".to_string(),
+        _ => branch.to_string(),
+    }
+}
+
+/// The inputs of each of a plan's operators, by branch, and the operators whose output isn't
+/// consumed by another.
+pub(crate) type PlanInputs = (HashMap<OpId, Vec<(u8, OpId)>>, Vec<OpId>);
+
+/// The inputs of each of a plan's operators, by branch, and the operators whose output isn't
+/// consumed by another, e.g. its sink.
+pub(crate) fn plan_inputs(plan: &LogicalPlan<BindingsOp>) -> PlanInputs {
+    let mut inputs: HashMap<OpId, Vec<(u8, OpId)>> = HashMap::new();
+    let mut has_output = vec![false; plan.operator_count()];
+    for (src, dst, branch) in plan.flows() {
+        inputs.entry(*dst).or_default().push((*branch, *src));
+        has_output[src.index() - 1] = true;
+    }
+    for srcs in inputs.values_mut() {
+        srcs.sort_by_key(|(branch, _)| *branch);
+    }
+    let roots = plan
+        .operators_by_id()
+        .map(|(id, _)| id)
+        .filter(|id| !has_output[id.index() - 1])
+        .collect();
+    (inputs, roots)
+}
+
+/// Renders a plan as an indented tree of operators, rooted at its sink, followed by the plans of
+/// the subqueries it refers to.
+pub fn plan_to_text(plan: &LogicalPlan<BindingsOp>) -> String {
+    plan_to_text_with(plan, |_| vec![])
+}
+
+/// Renders a plan as an indented tree of operators, with the fields given by `annotate` appended to
+/// each operator's label, followed by the plans of the subqueries it refers to.
+pub(crate) fn plan_to_text_with<F>(plan: &LogicalPlan<BindingsOp>, annotate: F) -> String
+where
+    F: Fn(OpId) -> Vec<String>,
+{
+    let subqueries = Subqueries::of(plan);
+    let mut out = String::new();
+    write_tree(&mut out, plan, &annotate, &subqueries);
+    for (number, subplan) in subqueries.plans() {
+        writeln!(out, "\nsubquery #{number}:").expect("plan text");
+        write_tree(&mut out, subplan, &|_| vec![], &subqueries);
+    }
+    out
+}
+
+fn write_tree<'a>(
+    out: &mut String,
+    plan: &'a LogicalPlan<BindingsOp>,
+    annotate: &dyn Fn(OpId) -> Vec<String>,
+    subqueries: &Subqueries<'a>,
+) {
+    let (inputs, roots) = plan_inputs(plan);
+    let tree = TextTree {
+        plan,
+        inputs,
+        annotate,
+        subqueries,
+    };
+    for id in roots {
+        tree.write_op(out, id, "", "", "").expect("plan text");
+    }
+}
+
+/// A plan, indexed for writing as a tree from its sinks towards its scans.
+struct TextTree<'a, 's> {
+    plan: &'a LogicalPlan<BindingsOp>,
+    inputs: HashMap<OpId, Vec<(u8, OpId)>>,
+    annotate: &'s dyn Fn(OpId) -> Vec<String>,
+    subqueries: &'s Subqueries<'a>,
+}
+
+impl TextTree<'_, '_> {
+    fn write_op(
+        &self,
+        out: &mut String,
+        id: OpId,
+        branch: &str,
+        first_prefix: &str,
+        prefix: &str,
+    ) -> std::fmt::Result {
+        let op = self.plan.operator(id).expect("plan operator");
+        let OpLabel { name, fields } = op_label(op, self.subqueries);
+        write!(out, "{first_prefix}{branch}{name}")?;
+        for field in fields.into_iter().chain((self.annotate)(id)) {
+            write!(out, " | {field}")?;
+        }
+        writeln!(out)?;
+
+        let srcs = self.inputs.get(&id).map(Vec::as_slice).unwrap_or_default();
+        for (idx, (branch, src)) in srcs.iter().enumerate() {
+            let last = idx == srcs.len() - 1;
+            let (first, rest) = if last {
+                ("└── ", "    ")
+            } else {
+                ("├── ", "│   ")
+            };
+            let branch = if srcs.len() > 1 {
+                format!("[{}] ", branch_label(op, *branch))
+            } else {
+                String::new()
+            };
+            self.write_op(
+                out,
+                *src,
+                &branch,
+                &format!("{prefix}{first}"),
+                &format!("{prefix}{rest}"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use partiql_logical::{Filter, Scan, SubQueryExpr, VarRefType};
+
+    fn var(name: &str) -> ValueExpr {
+        ValueExpr::VarRef(
+            BindingsName::CaseInsensitive(name.to_string().into()),
+            VarRefType::Local,
+        )
+    }
+
+    fn scan_plan(expr: ValueExpr, as_key: &str) -> LogicalPlan<BindingsOp> {
+        let mut plan = LogicalPlan::new();
+        let scan = plan.add_operator(BindingsOp::Scan(Scan {
+            expr,
+            as_key: as_key.to_string(),
+            at_key: None,
+        }));
+        let sink = plan.add_operator(BindingsOp::Sink);
+        plan.add_flow(scan, sink);
+        plan
+    }
+
+    /// Plans `query`, and checks the text rendering of its plan; the expected rendering starts with
+    /// a line break, for readability.
+    fn assert_text(query: &str, text: &str) {
+        let compiler = crate::evaluate::Compiler::default();
+        let parsed = compiler.parse(query).unwrap();
+        let plan = compiler.plan(&parsed).unwrap();
+        assert_eq!(plan_to_text(&plan), text[1..]);
+    }
+
+    #[test]
+    fn subqueries_are_numbered_and_listed() {
+        // SELECT * FROM (SELECT * FROM (SELECT * FROM w) AS v) AS u WHERE EXISTS (SELECT * FROM t)
+        let inner = scan_plan(var("w"), "w");
+        let middle = scan_plan(ValueExpr::SubQueryExpr(SubQueryExpr { plan: inner }), "v");
+        let exists = scan_plan(var("t"), "t");
+
+        let mut plan = LogicalPlan::new();
+        let scan = plan.add_operator(BindingsOp::Scan(Scan {
+            expr: ValueExpr::SubQueryExpr(SubQueryExpr { plan: middle }),
+            as_key: "u".to_string(),
+            at_key: None,
+        }));
+        let filter = plan.add_operator(BindingsOp::Filter(Filter {
+            expr: ValueExpr::Call(CallExpr {
+                name: CallName::Exists,
+                arguments: vec![ValueExpr::SubQueryExpr(SubQueryExpr { plan: exists })],
+            }),
+        }));
+        let sink = plan.add_operator(BindingsOp::Sink);
+        plan.add_flow(scan, filter);
+        plan.add_flow(filter, sink);
+
+        assert_eq!(
+            plan_to_text(&plan),
+            "\
+sink
+└── filter | exists((subquery #2))
+    └── scan | (subquery #1) | as u
+
+subquery #1:
+sink
+└── scan | (subquery #3) | as v
+
+subquery #2:
+sink
+└── scan | t | as t
+
+subquery #3:
+sink
+└── scan | w | as w
+"
+        );
+    }
+
+    #[test]
+    fn scan_and_project() {
+        assert_text(
+            "SELECT a FROM t",
+            r#"
+sink
+└── project | a:a
+    └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn pivot() {
+        assert_text(
+            "PIVOT v.a AT v.b FROM t AS v",
+            r#"
+sink
+└── pivot | v.a | at v.b
+    └── scan | t | as v
+"#,
+        );
+    }
+
+    #[test]
+    fn unpivot() {
+        assert_text(
+            "SELECT k, v FROM UNPIVOT t AS v AT k",
+            r#"
+sink
+└── project | k:k | v:v
+    └── unpivot | t | as v | at k
+"#,
+        );
+    }
+
+    #[test]
+    fn filter() {
+        assert_text(
+            "SELECT a FROM t WHERE a > 1",
+            r#"
+sink
+└── project | a:a
+    └── filter | a > 1
+        └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn order_by() {
+        assert_text(
+            "SELECT a FROM t ORDER BY a DESC",
+            r#"
+sink
+└── project | a:a
+    └── order by | a DESC NULLS FIRST
+        └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn limit_offset() {
+        assert_text(
+            "SELECT a FROM t LIMIT 10 OFFSET 2",
+            r#"
+sink
+└── project | a:a
+    └── limit 10 | offset 2
+        └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn join() {
+        assert_text(
+            "SELECT t.a, u.b FROM t JOIN u ON t.a = u.b",
+            r#"
+sink
+└── project | a:t.a | b:u.b
+    └── inner join | t.a = u.b
+        ├── [left] scan | t | as t
+        └── [right] scan | u | as u
+"#,
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        for (op, label) in [
+            ("UNION", "union distinct"),
+            ("UNION ALL", "union all"),
+            ("INTERSECT", "intersect distinct"),
+            ("INTERSECT ALL", "intersect all"),
+            ("EXCEPT", "except distinct"),
+            ("EXCEPT ALL", "except all"),
+        ] {
+            let query = format!("SELECT a FROM t {op} SELECT a FROM u");
+            let text = format!(
+                r#"
+sink
+└── {label}
+    ├── [left] project | a:a
+    │   └── scan | t | as t
+    └── [right] project | a:a
+        └── scan | u | as u
+"#
+            );
+            assert_text(&query, &text);
+        }
+    }
+
+    #[test]
+    fn project_all() {
+        assert_text(
+            "SELECT * FROM t",
+            r#"
+sink
+└── project *
+    └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn project_value() {
+        assert_text(
+            "SELECT VALUE a FROM t",
+            r#"
+sink
+└── project value | a
+    └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn expr_query() {
+        assert_text(
+            "1 + 1",
+            r#"
+sink
+└── 1 + 1
+"#,
+        );
+    }
+
+    #[test]
+    fn distinct() {
+        assert_text(
+            "SELECT DISTINCT a FROM t",
+            r#"
+sink
+└── distinct
+    └── project | a:a
+        └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn group_by() {
+        assert_text(
+            "SELECT a, COUNT(*) AS n FROM t GROUP BY a",
+            r#"
+sink
+└── project | a:a | n:"$__agg_1"
+    └── group by | FULL | keys: a:a | aggs: $__agg_1:count(1)
+        └── scan | t | as t
+"#,
+        );
+    }
+
+    #[test]
+    fn having() {
+        assert_text(
+            "SELECT a, COUNT(*) AS n FROM t GROUP BY a HAVING COUNT(*) > 1",
+            r#"
+sink
+└── project | a:a | n:"$__agg_1"
+    └── having | "$__agg_2" > 1
+        └── group by | FULL | keys: a:a | aggs: $__agg_1:count(1)
+            └── scan | t | as t
+"#,
+        );
+    }
+}
@@ -1,15 +1,48 @@
-use crate::args::OutputFormat;
+use crate::args::{Expanded, OutputFormat, TableNesting};
 use crate::pretty::PrettyPrint;
 
 use comfy_table::{Cell, Color, Table};
+use console::{measure_text_width, pad_str, Alignment, Term};
 use ion_rs::IonWriter;
+use itertools::Itertools;
 use partiql_extension_ion::encode::{IonEncoderBuilder, IonEncoderConfig};
 use partiql_extension_ion::Encoding;
 use partiql_value::Value;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt::Write as _;
 use std::io::Write;
+use std::time::Duration;
 
-pub fn print_value(format: &OutputFormat, value: &Value) {
+/// Options controlling how evaluation results are printed.
+#[derive(Debug, Clone, Default)]
+pub struct FormatOptions {
+    /// How nested values are rendered in [`OutputFormat::Table`] output
+    pub table_nesting: TableNesting,
+    /// Whether [`OutputFormat::Table`] output is printed as vertical records instead
+    pub expanded: Expanded,
+    /// Column order from the query's projection, when known statically; otherwise columns are
+    /// ordered as they are first seen in the result
+    pub columns: Option<Vec<String>>,
+    /// Query text and timing to include with [`OutputFormat::Markdown`] and
+    /// [`OutputFormat::Html`] output
+    pub caption: Option<Caption>,
+}
+
+/// The query text and evaluation time of a result.
+#[derive(Debug, Clone)]
+pub struct Caption {
+    pub query: String,
+    pub elapsed: Duration,
+}
+
+impl Caption {
+    fn summary(&self, rows: usize) -> String {
+        let plural = if rows == 1 { "" } else { "s" };
+        format!("{rows} row{plural} in {:?}", self.elapsed)
+    }
+}
+
+pub fn print_value(format: &OutputFormat, options: &FormatOptions, value: &Value) {
     match format {
         OutputFormat::Partiql => {
             partiql_pretty_print(value);
@@ -29,65 +62,362 @@ pub fn print_value(format: &OutputFormat, value: &Value) {
             ion_encode(&mut writer, value);
         }
         OutputFormat::Table => {
-            // TODO: this should really be based on static analysis of the query's returned 'columns'
-            let mut columns = vec![];
-            let mut columns_to_id = HashMap::new();
-            for v in value.iter() {
-                if let Value::Tuple(t) = v {
-                    for (k, _v) in t.pairs() {
-                        columns_to_id.entry(k).or_insert_with(|| {
-                            columns.push(k);
-
-                            columns.len() - 1
-                        });
-                    }
-                }
+            let projection = options.columns.as_deref();
+            let table = value_table(value, options.table_nesting, projection).to_string();
+            let vertical = match options.expanded {
+                Expanded::Off => false,
+                Expanded::On => true,
+                Expanded::Auto => wider_than_terminal(&table),
+            };
+            if vertical {
+                print_vertical(value, options.table_nesting, projection);
+            } else {
+                println!("{table}");
             }
-            let empty_row: Vec<_> = std::iter::repeat(Value::Null)
-                .take(columns.len())
-                .map(|v| Cell::new(partiql_pretty(&v)).fg(Color::DarkRed))
+        }
+        OutputFormat::Vertical => {
+            print_vertical(value, options.table_nesting, options.columns.as_deref())
+        }
+        OutputFormat::Markdown => {
+            let table = markdown_table(value, options).expect("markdown print");
+            print!("{table}");
+        }
+        OutputFormat::Html => {
+            let table = html_table(
+                value,
+                options.table_nesting,
+                options.columns.as_deref(),
+                options.caption.as_ref(),
+            )
+            .expect("html print");
+            print!("{table}");
+        }
+    }
+}
+
+/// A result laid out as rows of columns; shared by the tabular output formats.
+struct Tabular<'a> {
+    columns: Vec<String>,
+    /// One entry per column; `None` where a row has no value for that column
+    rows: Vec<Vec<Option<&'a Value>>>,
+}
+
+fn tabulate<'a>(
+    value: &'a Value,
+    nesting: TableNesting,
+    projection: Option<&[String]>,
+) -> Tabular<'a> {
+    let cells: Vec<_> = value.iter().map(|v| row_cells(v, nesting)).collect();
+    let mut columns = vec![];
+    if value.iter().any(|v| v.is_tuple()) {
+        let mut seen = HashSet::new();
+        for (k, _v) in cells.iter().flatten() {
+            if seen.insert(k.as_str()) {
+                columns.push(k.clone());
+            }
+        }
+        if let Some(projection) = projection {
+            let projection: Vec<_> = projection
+                .iter()
+                .map(|p| column_name(p, nesting))
                 .collect();
+            columns = order_by_projection(columns, &projection);
+        }
+    }
+    let columns_to_id: HashMap<_, _> = columns
+        .iter()
+        .enumerate()
+        .map(|(idx, k)| (k.clone(), idx))
+        .collect();
 
-            if columns.is_empty() {
-                let mut table = Table::new();
-                table.set_header(vec!["Value"]);
-                for v in value.iter() {
-                    table.add_row(&[partiql_table_pretty(v)]);
+    if columns.is_empty() {
+        Tabular {
+            columns: vec!["Value".to_string()],
+            rows: value.iter().map(|v| vec![Some(v)]).collect(),
+        }
+    } else {
+        let rows = cells
+            .into_iter()
+            .map(|cells| {
+                let mut row = vec![None; columns.len()];
+                for (k, v) in cells {
+                    // Every key was registered during column discovery above.
+                    if let Some(idx) = columns_to_id.get(&k) {
+                        row[*idx] = Some(v);
+                    }
                 }
+                row
+            })
+            .collect();
+        Tabular { columns, rows }
+    }
+}
 
-                println!("{table}");
-            } else {
-                let mut table = Table::new();
-                table.set_header(columns);
-                for v in value.iter() {
-                    let mut row = empty_row.clone();
-                    for (k, v) in v.as_tuple_ref().as_ref().pairs() {
-                        match columns_to_id.get(&k) {
-                            None => {
-                                todo!("error mapping key to column")
-                            }
-                            Some(idx) => {
-                                let col = Cell::new(partiql_table_pretty(v));
-                                let col = match v {
-                                    // Color Null & Missing red
-                                    Value::Null | Value::Missing => col.fg(Color::DarkRed),
-                                    _ => col,
-                                };
-                                row[*idx] = col
-                            }
-                        }
+/// Orders discovered columns by their position in the query's projection; projected columns not
+/// present in any row are included, and columns not in the projection are kept last.
+fn order_by_projection(discovered: Vec<String>, projection: &[String]) -> Vec<String> {
+    let projects = |p: &String, col: &String| {
+        col == p || col.strip_prefix(p.as_str()).is_some_and(|r| r.starts_with('.'))
+    };
+    let mut columns = vec![];
+    for p in projection {
+        let matching: Vec<_> = discovered.iter().filter(|col| projects(p, col)).collect();
+        if matching.is_empty() {
+            columns.push(p.clone());
+        } else {
+            columns.extend(matching.into_iter().cloned());
+        }
+    }
+    columns.extend(
+        discovered
+            .into_iter()
+            .filter(|col| !projection.iter().any(|p| projects(p, col))),
+    );
+    columns
+}
+
+fn value_table(value: &Value, nesting: TableNesting, projection: Option<&[String]>) -> Table {
+    let Tabular { columns, rows } = tabulate(value, nesting, projection);
+    let mut table = Table::new();
+    table.set_header(columns);
+    for row in rows {
+        table.add_row(row.into_iter().map(|v| match v {
+            Some(v) => value_cell(v, nesting),
+            None => Cell::new(partiql_pretty(&Value::Null)).fg(Color::DarkRed),
+        }));
+    }
+    table
+}
+
+/// Splits a row into `(column, value)` cells. Non-tuple rows are placed into a `_1` column, as
+/// they would be by tuple coercion.
+fn row_cells(value: &Value, nesting: TableNesting) -> Vec<(String, &Value)> {
+    let mut cells = vec![];
+    match value {
+        Value::Tuple(t) => {
+            for (k, v) in t.pairs() {
+                push_cell(&mut cells, column_name(k, nesting), v, nesting);
+            }
+        }
+        Value::Missing => {}
+        other => cells.push(("_1".to_string(), other)),
+    }
+    cells
+}
+
+fn push_cell<'a>(
+    cells: &mut Vec<(String, &'a Value)>,
+    key: String,
+    value: &'a Value,
+    nesting: TableNesting,
+) {
+    match value {
+        Value::Tuple(t) if nesting == TableNesting::Flatten && !t.is_empty() => {
+            for (k, v) in t.pairs() {
+                push_cell(cells, format!("{key}.{}", column_name(k, nesting)), v, nesting);
+            }
+        }
+        _ => cells.push((key, value)),
+    }
+}
+
+/// The column for an attribute named `key`. When flattening, a key containing `.` or `"` is
+/// quoted as a PartiQL identifier, so that e.g. an `a.b` attribute doesn't share a column with
+/// attribute `b` of a nested `a`.
+fn column_name(key: &str, nesting: TableNesting) -> String {
+    if nesting == TableNesting::Flatten && key.contains(['.', '"']) {
+        format!("\"{}\"", key.replace('"', "\"\""))
+    } else {
+        key.to_string()
+    }
+}
+
+fn value_cell(value: &Value, nesting: TableNesting) -> Cell {
+    let cell = Cell::new(cell_text(value, nesting));
+    match value {
+        // Color Null & Missing red
+        Value::Null | Value::Missing => cell.fg(Color::DarkRed),
+        _ => cell,
+    }
+}
+
+fn cell_text(value: &Value, nesting: TableNesting) -> String {
+    match (nesting, value) {
+        (TableNesting::Pretty, _) => partiql_table_pretty(value),
+        (TableNesting::Compact | TableNesting::Flatten, _) => partiql_table_compact(value),
+        (TableNesting::Expand, Value::Tuple(_) | Value::List(_) | Value::Bag(_)) => {
+            let mut table = value_table(value, nesting, None);
+            // Styling escapes would throw off the enclosing table's column widths.
+            table.force_no_tty();
+            table.to_string()
+        }
+        (TableNesting::Expand, _) => partiql_table_pretty(value),
+    }
+}
+
+fn wider_than_terminal(text: &str) -> bool {
+    match Term::stdout().size_checked() {
+        Some((_rows, cols)) => text
+            .lines()
+            .any(|line| measure_text_width(line) > cols as usize),
+        None => false,
+    }
+}
+
+fn print_vertical(value: &Value, nesting: TableNesting, projection: Option<&[String]>) {
+    let records = vertical_records(value, nesting, projection).expect("vertical print");
+    print!("{records}");
+}
+
+/// Prints each row as a block of `key | value` lines, similar to `psql`'s expanded display.
+fn vertical_records(
+    value: &Value,
+    nesting: TableNesting,
+    projection: Option<&[String]>,
+) -> Result<String, std::fmt::Error> {
+    let Tabular { columns, rows } = tabulate(value, nesting, projection);
+    let key_width = columns
+        .iter()
+        .map(|c| measure_text_width(c))
+        .max()
+        .unwrap_or(0);
+    let mut out = String::new();
+    if rows.is_empty() {
+        // Unlike a table, there is no header to show that the result was printed at all
+        writeln!(out, "(0 rows)")?;
+    }
+    for (idx, row) in rows.into_iter().enumerate() {
+        let title = format!("-[ RECORD {} ]", idx + 1);
+        let fill = (key_width + 3).saturating_sub(title.len());
+        writeln!(out, "{title}{}", "-".repeat(fill))?;
+        for (column, v) in columns.iter().zip(row) {
+            let text = cell_text(v.unwrap_or(&Value::Null), nesting);
+            let mut lines = text.split('\n');
+            let key = pad_str(column, key_width, Alignment::Left, None);
+            writeln!(out, "{key} | {}", lines.next().unwrap_or(""))?;
+            for line in lines {
+                writeln!(out, "{:key_width$} | {line}", "")?;
+            }
+        }
+    }
+    Ok(out)
+}
+
+/// Renders `value` as a markdown table. Cells are single lines, so nested values are rendered
+/// compact unless flattened; `pretty` and `expand` nesting render as `compact`.
+fn markdown_table(value: &Value, options: &FormatOptions) -> Result<String, std::fmt::Error> {
+    let projection = options.columns.as_deref();
+    let Tabular { columns, rows } = tabulate(value, options.table_nesting, projection);
+    let mut out = String::new();
+    if let Some(caption) = &options.caption {
+        let query = caption.query.trim();
+        let fence = "`".repeat(longest_backtick_run(query).max(2) + 1);
+        writeln!(out, "{fence}partiql\n{query}\n{fence}\n")?;
+        writeln!(out, "_{}_\n", caption.summary(rows.len()))?;
+    }
+
+    let header = columns.iter().map(|c| markdown_escape(c)).join(" | ");
+    writeln!(out, "| {header} |")?;
+    writeln!(out, "|{}", " --- |".repeat(columns.len()))?;
+    for row in rows {
+        let row = row
+            .into_iter()
+            .map(|v| markdown_escape(&partiql_table_compact(v.unwrap_or(&Value::Null))))
+            .join(" | ");
+        writeln!(out, "| {row} |")?;
+    }
+    Ok(out)
+}
+
+/// The length of the longest run of backticks in `text`, which a code fence around it must exceed.
+fn longest_backtick_run(text: &str) -> usize {
+    text.split(|c| c != '`').map(str::len).max().unwrap_or(0)
+}
+
+fn markdown_escape(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('|', "\\|")
+        .replace('\n', "<br>")
+}
+
+fn html_table(
+    value: &Value,
+    nesting: TableNesting,
+    projection: Option<&[String]>,
+    caption: Option<&Caption>,
+) -> Result<String, std::fmt::Error> {
+    let Tabular { columns, rows } = tabulate(value, nesting, projection);
+    let mut out = String::new();
+    writeln!(out, "<table>")?;
+    if let Some(caption) = caption {
+        writeln!(
+            out,
+            "<caption><pre><code>{}</code></pre>{}</caption>",
+            html_escape(caption.query.trim()),
+            html_escape(&caption.summary(rows.len()))
+        )?;
+    }
+
+    let header = columns
+        .iter()
+        .map(|c| format!("<th>{}</th>", html_escape(c)))
+        .join("");
+    writeln!(out, "<thead><tr>{header}</tr></thead>")?;
+    writeln!(out, "<tbody>")?;
+    let null = Value::Null;
+    for row in rows {
+        let mut cells = String::new();
+        for v in row {
+            let v = v.unwrap_or(&null);
+            let content = match (nesting, v) {
+                (TableNesting::Expand, Value::Tuple(_) | Value::List(_) | Value::Bag(_)) => {
+                    html_table(v, nesting, None, None)?
+                }
+                (TableNesting::Pretty | TableNesting::Expand, _) => {
+                    let pretty = partiql_table_pretty(v);
+                    if pretty.contains('\n') {
+                        format!("<pre>{}</pre>", html_escape(&pretty))
+                    } else {
+                        html_escape(&pretty)
                     }
-                    table.add_row(row);
                 }
+                (TableNesting::Compact | TableNesting::Flatten, _) => {
+                    html_escape(&partiql_table_compact(v))
+                }
+            };
+            write!(cells, "<td>{content}</td>")?;
+        }
+        writeln!(out, "<tr>{cells}</tr>")?;
+    }
+    writeln!(out, "</tbody>")?;
+    writeln!(out, "</table>")?;
+    Ok(out)
+}
 
-                println!("{table}");
-            }
+fn html_escape(text: &str) -> String {
+    let mut escaped = String::with_capacity(text.len());
+    for c in text.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&#39;"),
+            c => escaped.push(c),
         }
     }
+    escaped
 }
 
 fn partiql_table_pretty(value: &Value) -> String {
-    let pretty = partiql_pretty(value);
+    trim_string_quotes(partiql_pretty(value))
+}
+
+fn partiql_table_compact(value: &Value) -> String {
+    trim_string_quotes(format!("{value:?}"))
+}
+
+fn trim_string_quotes(pretty: String) -> String {
     if pretty.starts_with('\'') && pretty.ends_with('\'') {
         pretty.trim_matches('\'').to_string()
     } else {
@@ -121,3 +451,93 @@ where
         .iter()
         .for_each(|v| encoder.write_value(v).expect("ion encoder write"));
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::evaluate;
+
+    /// The result of evaluating `query`, which needs no globals.
+    fn value(query: &str) -> Value {
+        evaluate(query, Default::default()).unwrap().result
+    }
+
+    #[test]
+    fn markdown_escapes_cells() {
+        let options = FormatOptions::default();
+        let table = markdown_table(&value(r"<<{'a': 'x|y', 'b': 'back\slash'}>>"), &options);
+        assert_eq!(
+            table.unwrap(),
+            "| a | b |\n| --- | --- |\n| x\\|y | back\\\\slash |\n"
+        );
+    }
+
+    #[test]
+    fn markdown_caption_fences_the_query() {
+        let options = FormatOptions {
+            caption: Some(Caption {
+                query: "SELECT ```a``` FROM t".to_string(),
+                elapsed: Duration::from_millis(2),
+            }),
+            ..Default::default()
+        };
+        let table = markdown_table(&value("<<{'a': 1}>>"), &options);
+        assert_eq!(
+            table.unwrap(),
+            "````partiql\nSELECT ```a``` FROM t\n````\n\n_1 row in 2ms_\n\n| a |\n| --- |\n| 1 |\n"
+        );
+    }
+
+    #[test]
+    fn html_escapes_cells_and_caption() {
+        let caption = Caption {
+            query: "SELECT a FROM t WHERE a < 'x'".to_string(),
+            elapsed: Duration::from_millis(2),
+        };
+        let value = value("<<{'<a>': 'x & \"y\"'}, {'<a>': 'z'}>>");
+        let table = html_table(&value, TableNesting::Pretty, None, Some(&caption));
+        assert_eq!(
+            table.unwrap(),
+            "<table>\n\
+             <caption><pre><code>SELECT a FROM t WHERE a &lt; &#39;x&#39;</code></pre>2 rows in 2ms</caption>\n\
+             <thead><tr><th>&lt;a&gt;</th></tr></thead>\n\
+             <tbody>\n\
+             <tr><td>x &amp; &quot;y&quot;</td></tr>\n\
+             <tr><td>z</td></tr>\n\
+             </tbody>\n\
+             </table>\n"
+        );
+    }
+
+    #[test]
+    fn flattening_quotes_dotted_keys() {
+        let value = value(r#"<<{'a': {'b': 1}, 'a.b': 2, 'q"t': {'c': 3}}>>"#);
+        let Tabular { columns, .. } = tabulate(&value, TableNesting::Flatten, None);
+        assert_eq!(columns, ["a.b", "\"a.b\"", "\"q\"\"t\".c"]);
+
+        // columns of other nestings are never joined, so keys are kept as they are
+        let Tabular { columns, .. } = tabulate(&value, TableNesting::Compact, None);
+        assert_eq!(columns, ["a", "a.b", "q\"t"]);
+    }
+
+    #[test]
+    fn vertical_output() {
+        let value = value("<<{'a': 1, 'long': 'x'}, {'a': 2}>>");
+        let records = vertical_records(&value, TableNesting::Compact, None);
+        assert_eq!(
+            records.unwrap(),
+            "-[ RECORD 1 ]\n\
+             a    | 1\n\
+             long | x\n\
+             -[ RECORD 2 ]\n\
+             a    | 2\n\
+             long | NULL\n"
+        );
+    }
+
+    #[test]
+    fn vertical_output_of_an_empty_result() {
+        let records = vertical_records(&value("<<>>"), TableNesting::Compact, None);
+        assert_eq!(records.unwrap(), "(0 rows)\n");
+    }
+}
@@ -0,0 +1,45 @@
+use crate::ast_tree::node_span;
+use partiql_common::node::NodeId;
+use partiql_parser::Parsed;
+use serde::Serialize;
+use serde_json::{json, Value};
+
+/// Convert an AST or plan into JSON
+#[inline]
+pub fn to_json<T>(data: &T) -> String
+where
+    T: ?Sized + Serialize,
+{
+    serde_json::to_string_pretty(&data).expect("json print")
+}
+
+/// Convert a parsed query's AST into JSON, adding a `span` of byte offsets into the query text to
+/// each AST node.
+pub fn ast_to_json(parsed: &Parsed) -> String {
+    let mut ast = serde_json::to_value(&parsed.ast).expect("json value");
+    add_spans(&mut ast, parsed);
+    to_json(&ast)
+}
+
+/// Adds spans to the `{"id": .., "node": ..}` objects that [`partiql_ast::ast::AstNode`]s
+/// serialize as.
+fn add_spans(value: &mut Value, parsed: &Parsed) {
+    match value {
+        Value::Object(obj) => {
+            let id = match (obj.get("id"), obj.contains_key("node")) {
+                (Some(Value::Number(id)), true) => id.as_u64(),
+                _ => None,
+            };
+            let span = id.and_then(|id| node_span(parsed, NodeId(id as u32)));
+            if let Some(span) = span {
+                obj.insert(
+                    "span".to_string(),
+                    json!({"start": span.start, "end": span.end}),
+                );
+            }
+            obj.values_mut().for_each(|v| add_spans(v, parsed));
+        }
+        Value::Array(arr) => arr.iter_mut().for_each(|v| add_spans(v, parsed)),
+        _ => {}
+    }
+}
@@ -1,4 +1,7 @@
+pub mod analyze;
 pub mod args;
+pub mod ast_passes;
+pub mod ast_tree;
 
 pub mod error;
 pub mod repl;
@@ -6,6 +9,13 @@ pub mod repl;
 #[cfg(feature = "visualize")]
 pub mod visualize;
 
+pub mod eval_plan;
 pub mod evaluate;
+pub mod explain;
 pub mod formatting;
+#[cfg(feature = "serde")]
+pub mod json;
+pub mod plan_diff;
 pub mod pretty;
+pub mod timing;
+pub mod warning;
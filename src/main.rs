@@ -1,56 +1,222 @@
 #![deny(rustdoc::broken_intra_doc_links)]
 
 use clap::Parser;
-use partiql_cli::args::Commands;
-use partiql_cli::evaluate::{get_bindings, Compiler};
-use partiql_cli::formatting::print_value;
+use partiql_cli::args::{Commands, ErrorFormat, Format};
+use partiql_cli::error::{report_error, report_panic, CLIError, CLIErrors, ExitStatus};
+use partiql_cli::evaluate::{get_bindings, projected_columns, Compiler};
+use partiql_cli::formatting::{print_value, Caption, FormatOptions};
+use partiql_cli::timing::{timed, QueryStats};
+use partiql_cli::warning::{report_warnings, CLIWarnings};
 use partiql_cli::{args, repl};
+use std::panic::{self, AssertUnwindSafe};
+use std::process::ExitCode;
 
-fn main() -> miette::Result<()> {
+#[cfg(feature = "serde")]
+use partiql_cli::json::{ast_to_json, to_json};
+#[cfg(feature = "visualize")]
+use partiql_cli::args::{DisplayArgs, Layout};
+#[cfg(feature = "visualize")]
+use partiql_cli::visualize::render::{
+    ast_to_html, display, plan_to_html, to_dot, to_mermaid, to_png, to_svg, IntoGraph,
+};
+#[cfg(feature = "visualize")]
+use std::io::Write;
+
+fn main() -> ExitCode {
     let args = args::Args::parse();
 
+    // Panics exit as internal failures; they are reported by the default hook, unless a record of
+    // them is needed in another format
+    if args.error_format != ErrorFormat::Human {
+        let format = args.error_format;
+        panic::set_hook(Box::new(move |info| report_panic(format, info)));
+    }
+    match panic::catch_unwind(AssertUnwindSafe(|| run(&args))) {
+        Ok(Ok(())) => ExitCode::SUCCESS,
+        Ok(Err(report)) => {
+            report_error(args.error_format, &report);
+            ExitStatus::of(&report).into()
+        }
+        Err(_) => ExitStatus::Internal.into(),
+    }
+}
+
+fn run(args: &args::Args) -> miette::Result<()> {
     match &args.command {
         Commands::Repl { environment } => repl::repl(environment),
 
-        #[cfg(feature = "visualize")]
-        Commands::Ast { format, query } => {
-            use partiql_cli::args::Format;
-            use partiql_cli::visualize::render::{display, to_dot, to_json, to_png, to_svg};
-            use std::io::Write;
+        Commands::Ast {
+            format,
+            query,
+            #[cfg(feature = "visualize")]
+            layout,
+            #[cfg(feature = "visualize")]
+            display: display_args,
+            pass,
+            diff,
+            ..
+        } => {
+            use partiql_cli::args::AstPass;
+            use partiql_cli::ast_passes::{ast_stages, ast_stages_to_text};
+            use partiql_cli::ast_tree::ast_to_tree;
 
             let compiler = Compiler::default();
-            let parsed = compiler.parse(&query)?;
+            let parsed = compiler.parse(query)?;
+            if *pass != AstPass::Parsed || *diff {
+                if *format != Format::Text {
+                    miette::bail!("`--pass` and `--diff` are only supported by the `text` format");
+                }
+                let stages = ast_stages(&compiler, &parsed)?;
+                print!("{}", ast_stages_to_text(&parsed, &stages, *pass, *diff));
+                return Ok(());
+            }
             match format {
-                Format::Json => println!("{}", to_json(&parsed.ast)),
-                Format::Dot => println!("{}", to_dot(&parsed.ast)),
-                Format::Svg => println!("{}", to_svg(&parsed.ast)),
-                Format::Png => {
-                    std::io::stdout()
-                        .write(&to_png(&parsed.ast))
-                        .expect("png write");
+                Format::Text => print!("{}", ast_to_tree(&parsed)),
+                #[cfg(feature = "serde")]
+                Format::Json => println!("{}", ast_to_json(&parsed)),
+                #[cfg(feature = "visualize")]
+                Format::Html => {
+                    println!("{}", ast_to_html(*layout, &repl::graph_theme(), &parsed)?)
                 }
-                Format::Display => display(&parsed.ast),
+                #[cfg(feature = "visualize")]
+                format => render_graph(*format, *layout, display_args, &parsed, || {
+                    ast_to_tree(&parsed)
+                })?,
+                #[cfg(not(feature = "visualize"))]
+                format => return unavailable_format(*format),
             }
 
             Ok(())
         }
-        #[cfg(feature = "visualize")]
-        Commands::Plan { format, query } => {
-            use partiql_cli::args::Format;
-            use partiql_cli::visualize::render::{display, to_dot, to_json, to_png, to_svg};
-            use std::io::Write;
+        Commands::Plan {
+            format,
+            query,
+            #[cfg(feature = "visualize")]
+            layout,
+            #[cfg(feature = "visualize")]
+            display: display_args,
+            ..
+        } => {
+            use partiql_cli::explain::plan_to_text;
 
             let compiler = Compiler::default();
-            let parsed = compiler.parse(&query)?;
+            let parsed = compiler.parse(query)?;
             let plan = compiler.plan(&parsed)?;
             match format {
+                Format::Text => print!("{}", plan_to_text(&plan)),
+                #[cfg(feature = "serde")]
                 Format::Json => println!("{}", to_json(&plan)),
-                Format::Dot => println!("{}", to_dot(&plan)),
-                Format::Svg => println!("{}", to_svg(&plan)),
-                Format::Png => {
-                    std::io::stdout().write(&to_png(&plan)).expect("png write");
+                #[cfg(feature = "visualize")]
+                Format::Html => {
+                    let theme = repl::graph_theme();
+                    println!("{}", plan_to_html(*layout, &theme, &plan, &parsed)?)
+                }
+                #[cfg(feature = "visualize")]
+                format => render_graph(*format, *layout, display_args, &plan, || {
+                    plan_to_text(&plan)
+                })?,
+                #[cfg(not(feature = "visualize"))]
+                format => return unavailable_format(*format),
+            }
+
+            Ok(())
+        }
+        Commands::EvalPlan {
+            format,
+            query,
+            #[cfg(feature = "visualize")]
+            layout,
+            #[cfg(feature = "visualize")]
+            display: display_args,
+        } => {
+            use partiql_cli::eval_plan::{eval_graph, eval_graph_to_text};
+
+            let compiler = Compiler::default();
+            let parsed = compiler.parse(query)?;
+            let plan = compiler.plan(&parsed)?;
+            let graph = eval_graph(&compiler.compile(&parsed, &plan)?);
+            match format {
+                Format::Text => print!("{}", eval_graph_to_text(&graph)),
+                #[cfg(feature = "serde")]
+                Format::Json => println!("{}", to_json(&graph)),
+                #[cfg(feature = "visualize")]
+                Format::Html => {
+                    let theme = repl::graph_theme();
+                    println!("{}", plan_to_html(*layout, &theme, &graph, &parsed)?)
+                }
+                #[cfg(feature = "visualize")]
+                format => render_graph(*format, *layout, display_args, &graph, || {
+                    eval_graph_to_text(&graph)
+                })?,
+                #[cfg(not(feature = "visualize"))]
+                format => return unavailable_format(*format),
+            }
+
+            Ok(())
+        }
+        Commands::PlanDiff {
+            format,
+            query_a,
+            query_b,
+            #[cfg(feature = "visualize")]
+            layout,
+            #[cfg(feature = "visualize")]
+            display: display_args,
+        } => {
+            use partiql_cli::plan_diff::{diff_plans, plan_diff_to_text};
+
+            let compiler = Compiler::default();
+            let plan_a = compiler.plan(&compiler.parse(query_a)?)?;
+            let plan_b = compiler.plan(&compiler.parse(query_b)?)?;
+            let diff = diff_plans(&plan_a, &plan_b);
+            match format {
+                Format::Text => print!("{}", plan_diff_to_text(&diff)),
+                #[cfg(feature = "visualize")]
+                Format::Json | Format::Mermaid | Format::Html => {
+                    return unsupported_format("plan-diff", *format)
                 }
-                Format::Display => display(&plan),
+                #[cfg(feature = "visualize")]
+                format => render_graph(*format, *layout, display_args, &diff, || {
+                    plan_diff_to_text(&diff)
+                })?,
+                #[cfg(not(feature = "visualize"))]
+                Format::Json => return unsupported_format("plan-diff", *format),
+                #[cfg(not(feature = "visualize"))]
+                format => return unavailable_format(*format),
+            }
+
+            Ok(())
+        }
+        Commands::ExplainAnalyze {
+            format,
+            environment,
+            query,
+            #[cfg(feature = "visualize")]
+            layout,
+            #[cfg(feature = "visualize")]
+            display: display_args,
+        } => {
+            use partiql_cli::analyze::{analyze_plan, analyzed_plan_to_text};
+
+            let bindings = get_bindings(environment)?;
+            let compiler = Compiler::default();
+            let parsed = compiler.parse(query)?;
+            let plan = compiler.plan(&parsed)?;
+            let analyzed = analyze_plan(&compiler, &parsed, plan, &bindings)?;
+            match format {
+                Format::Text => print!("{}", analyzed_plan_to_text(&analyzed)),
+                Format::Json => return unsupported_format("explain-analyze", *format),
+                #[cfg(feature = "visualize")]
+                Format::Html => {
+                    let theme = repl::graph_theme();
+                    println!("{}", plan_to_html(*layout, &theme, &analyzed, &parsed)?)
+                }
+                #[cfg(feature = "visualize")]
+                format => render_graph(*format, *layout, display_args, &analyzed, || {
+                    analyzed_plan_to_text(&analyzed)
+                })?,
+                #[cfg(not(feature = "visualize"))]
+                format => return unavailable_format(*format),
             }
 
             Ok(())
@@ -58,16 +224,125 @@ fn main() -> miette::Result<()> {
         Commands::Eval {
             query,
             output,
+            table_nesting,
+            expanded,
+            caption,
+            timing,
             environment,
         } => {
             let bindings = get_bindings(environment)?;
             let compiler = Compiler::default();
-            let parsed = compiler.parse(&query)?;
-            let plan = compiler.plan(&parsed)?;
-            let eval = compiler.compile(&parsed, &plan)?;
-            let evaluated = compiler.evaluate(&parsed, eval, bindings)?.result;
-            print_value(output, &evaluated);
+            let (parsed, parse) = timed(|| compiler.parse(query));
+            let parsed = parsed?;
+            let (plan, plan_time) = timed(|| compiler.plan(&parsed));
+            let plan = plan?;
+            let (eval, compile) = timed(|| compiler.compile(&parsed, &plan));
+            let eval = eval?;
+            // The warnings' checks evaluate the query again, over bindings of their own
+            let check_bindings =
+                (args.warnings || args.warnings_as_errors).then(|| bindings.clone());
+            let (evaluated, elapsed) =
+                timed(|| compiler.evaluate_recorded(&parsed, eval, bindings));
+            let (evaluated, recorded) = evaluated?;
+            let evaluated = evaluated.result;
+            let stats = QueryStats {
+                parse,
+                plan: plan_time,
+                compile,
+                evaluate: elapsed,
+                rows: evaluated.iter().count(),
+            };
+            let options = FormatOptions {
+                table_nesting: *table_nesting,
+                expanded: *expanded,
+                columns: projected_columns(&plan),
+                caption: caption.then(|| Caption {
+                    query: query.clone(),
+                    elapsed,
+                }),
+            };
+            print_value(output, &options, &evaluated);
+            if let Some(format) = timing {
+                stats.report(*format);
+            }
+
+            let mut warnings = CLIWarnings::recorded(&parsed, recorded);
+            if let Some(bindings) = check_bindings {
+                warnings.check(&compiler, &parsed, &plan, bindings, &evaluated);
+            }
+            if !warnings.is_empty() {
+                let count = warnings.len();
+                report_warnings(args.error_format, warnings);
+                if args.warnings_as_errors {
+                    let err = CLIError::WarningsAsErrors { count };
+                    return Err(CLIErrors::from((query.as_str(), err)).into());
+                }
+            }
             Ok(())
         }
     }
 }
+
+/// Prints `graph` in one of the formats drawn the same way for every command: `dot`, `svg`, `png`,
+/// `display` and `mermaid`. `display` prints `text` instead when the terminal can't show images.
+#[cfg(feature = "visualize")]
+fn render_graph<G>(
+    format: Format,
+    layout: Layout,
+    display_args: &DisplayArgs,
+    graph: G,
+    text: impl FnOnce() -> String,
+) -> miette::Result<()>
+where
+    G: IntoGraph + Copy,
+{
+    let theme = repl::graph_theme();
+    match format {
+        Format::Dot => println!("{}", to_dot(layout, &theme, graph)),
+        Format::Svg => println!("{}", to_svg(layout, &theme, graph)?),
+        Format::Png => {
+            std::io::stdout()
+                .write_all(&to_png(layout, &theme, graph)?)
+                .expect("png write");
+        }
+        Format::Display => {
+            let options = repl::display_options().with_args(display_args);
+            if !display(layout, &theme, &options, graph)? {
+                print!("{}", text());
+            }
+        }
+        Format::Mermaid => println!("{}", to_mermaid(graph)),
+        // the remaining formats differ between commands, and are printed by each
+        Format::Text | Format::Json | Format::Html => {
+            unreachable!("{format:?} is printed by the command")
+        }
+    }
+    Ok(())
+}
+
+/// Reports a `format` that `command` can't produce.
+fn unsupported_format(command: &str, format: Format) -> miette::Result<()> {
+    use clap::ValueEnum;
+
+    let format = format.to_possible_value().expect("format value");
+    miette::bail!(
+        "the `{}` format is not supported by `{command}`",
+        format.get_name()
+    )
+}
+
+/// Reports a `format` that needs a feature this binary was built without.
+#[cfg(not(feature = "visualize"))]
+fn unavailable_format(format: Format) -> miette::Result<()> {
+    use clap::ValueEnum;
+
+    let feature = match format {
+        Format::Json => "serde",
+        _ => "visualize",
+    };
+    let format = format.to_possible_value().expect("format value");
+    miette::bail!(
+        "the `{}` format requires the `{feature}` feature",
+        format.get_name()
+    )
+}
@@ -0,0 +1,519 @@
+//! Aligns the operators of two logical plans, to report how a plan changed between two queries.
+
+use crate::explain::{branch_label, op_label, plan_inputs, OpLabel, Subqueries};
+use partiql_logical::{BindingsOp, LogicalPlan, OpId};
+use std::collections::HashMap;
+use std::fmt::Write;
+use std::mem::discriminant;
+
+/// How an operator differs between two plans.
+#[derive(Debug, Copy, Clone, PartialEq, Eq)]
+pub enum Change {
+    Unchanged,
+    /// The operator's details or kind differ
+    Changed,
+    /// The operator is only in the second plan
+    Added,
+    /// The operator is only in the first plan
+    Removed,
+}
+
+/// An operator of either plan, aligned with its counterpart in the other plan if it has one.
+#[derive(Debug)]
+pub struct DiffOp {
+    pub change: Change,
+    /// The operator's label in the first plan, unless it was added.
+    pub(crate) before: Option<OpLabel>,
+    /// The operator's label in the second plan, unless it was removed.
+    pub(crate) after: Option<OpLabel>,
+    /// The operator's inputs, with the labels of the branches they are consumed by.
+    pub inputs: Vec<(String, DiffOp)>,
+}
+
+/// The operators of two plans, aligned from their sinks towards their scans.
+#[derive(Debug)]
+pub struct PlanDiff {
+    pub roots: Vec<DiffOp>,
+    /// The diffs of the plans' subqueries, paired by number.
+    pub subqueries: Vec<(usize, PlanDiff)>,
+}
+
+impl DiffOp {
+    /// The operator's label, with any changed details given as `before → after`.
+    pub(crate) fn label(&self) -> OpLabel {
+        match (&self.before, &self.after) {
+            (Some(before), Some(after)) => {
+                let name = if before.name == after.name {
+                    before.name.clone()
+                } else {
+                    format!("{} → {}", before.name, after.name)
+                };
+                let len = before.fields.len().max(after.fields.len());
+                let fields = (0..len)
+                    .map(
+                        |idx| match (before.fields.get(idx), after.fields.get(idx)) {
+                            (Some(b), Some(a)) if b == a => b.clone(),
+                            (Some(b), Some(a)) => format!("{b} → {a}"),
+                            (Some(b), None) => format!("- {b}"),
+                            (None, Some(a)) => format!("+ {a}"),
+                            (None, None) => unreachable!(),
+                        },
+                    )
+                    .collect();
+                OpLabel { name, fields }
+            }
+            (Some(label), None) | (None, Some(label)) => label.clone(),
+            (None, None) => unreachable!("diffed operator is in neither plan"),
+        }
+    }
+}
+
+impl PlanDiff {
+    /// The number of added, removed and changed operators.
+    pub fn counts(&self) -> (usize, usize, usize) {
+        fn count(op: &DiffOp, counts: &mut (usize, usize, usize)) {
+            match op.change {
+                Change::Unchanged => {}
+                Change::Added => counts.0 += 1,
+                Change::Removed => counts.1 += 1,
+                Change::Changed => counts.2 += 1,
+            }
+            op.inputs.iter().for_each(|(_, input)| count(input, counts));
+        }
+        let mut counts = (0, 0, 0);
+        self.roots.iter().for_each(|root| count(root, &mut counts));
+        for (_, subquery) in &self.subqueries {
+            let (added, removed, changed) = subquery.counts();
+            counts = (counts.0 + added, counts.1 + removed, counts.2 + changed);
+        }
+        counts
+    }
+}
+
+/// A plan, indexed for walking from its sink towards its scans.
+struct Indexed<'a, 's> {
+    plan: &'a LogicalPlan<BindingsOp>,
+    inputs: HashMap<OpId, Vec<(u8, OpId)>>,
+    subqueries: &'s Subqueries<'a>,
+}
+
+impl<'a, 's> Indexed<'a, 's> {
+    fn of(plan: &'a LogicalPlan<BindingsOp>, subqueries: &'s Subqueries<'a>) -> (Self, Vec<OpId>) {
+        let (inputs, roots) = plan_inputs(plan);
+        let indexed = Indexed {
+            plan,
+            inputs,
+            subqueries,
+        };
+        (indexed, roots)
+    }
+
+    fn label(&self, op: &'a BindingsOp) -> OpLabel {
+        op_label(op, self.subqueries)
+    }
+
+    fn op(&self, id: OpId) -> &'a BindingsOp {
+        self.plan.operator(id).expect("plan operator")
+    }
+
+    fn inputs(&self, id: OpId) -> &[(u8, OpId)] {
+        self.inputs.get(&id).map(Vec::as_slice).unwrap_or_default()
+    }
+
+    /// The depth of the nearest operator below `id` of the same kind as `op`, and the index of
+    /// the input of `id` it's found through.
+    fn find(&self, id: OpId, op: &BindingsOp) -> Option<(usize, usize)> {
+        self.inputs(id)
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, (_, input))| {
+                let depth = if discriminant(self.op(*input)) == discriminant(op) {
+                    1
+                } else {
+                    self.find(*input, op)?.0 + 1
+                };
+                Some((depth, idx))
+            })
+            .min()
+    }
+}
+
+/// Aligns the operators of plan `a` with those of plan `b`.
+///
+/// Plans are walked from their sinks towards their scans, pairing operators of the same kind.
+/// Where the kinds differ, operators inserted above (or removed from above) an operator of the
+/// other plan's kind are skipped over as added (or removed); otherwise the operator is reported as
+/// changed.
+///
+/// Subqueries are diffed with the subquery of the same number in the other plan.
+pub fn diff_plans(a: &LogicalPlan<BindingsOp>, b: &LogicalPlan<BindingsOp>) -> PlanDiff {
+    let (a_subqueries, b_subqueries) = (Subqueries::of(a), Subqueries::of(b));
+    let mut diff = diff_with(a, b, &a_subqueries, &b_subqueries);
+
+    let (a_plans, b_plans) = (a_subqueries.plans(), b_subqueries.plans());
+    let len = a_plans.len().max(b_plans.len());
+    diff.subqueries = (0..len)
+        .map(|idx| {
+            let subquery = match (a_plans.get(idx), b_plans.get(idx)) {
+                (Some((_, a)), Some((_, b))) => diff_with(a, b, &a_subqueries, &b_subqueries),
+                (Some((_, a)), None) => diff_only(a, &a_subqueries, Change::Removed),
+                (None, Some((_, b))) => diff_only(b, &b_subqueries, Change::Added),
+                (None, None) => unreachable!(),
+            };
+            (idx + 1, subquery)
+        })
+        .collect();
+    diff
+}
+
+fn diff_with<'a, 'b>(
+    a: &'a LogicalPlan<BindingsOp>,
+    b: &'b LogicalPlan<BindingsOp>,
+    a_subqueries: &Subqueries<'a>,
+    b_subqueries: &Subqueries<'b>,
+) -> PlanDiff {
+    let (a, a_roots) = Indexed::of(a, a_subqueries);
+    let (b, b_roots) = Indexed::of(b, b_subqueries);
+    PlanDiff {
+        roots: align_all(&a, &b, &a_roots, &b_roots),
+        subqueries: vec![],
+    }
+}
+
+/// The diff of a plan against a plan that doesn't exist; each operator is added or removed.
+fn diff_only<'a>(
+    plan: &'a LogicalPlan<BindingsOp>,
+    subqueries: &Subqueries<'a>,
+    change: Change,
+) -> PlanDiff {
+    let (plan, roots) = Indexed::of(plan, subqueries);
+    PlanDiff {
+        roots: roots.iter().map(|id| only(&plan, *id, change)).collect(),
+        subqueries: vec![],
+    }
+}
+
+fn align_all(a: &Indexed, b: &Indexed, a_ids: &[OpId], b_ids: &[OpId]) -> Vec<DiffOp> {
+    let len = a_ids.len().max(b_ids.len());
+    (0..len)
+        .map(|idx| match (a_ids.get(idx), b_ids.get(idx)) {
+            (Some(a_id), Some(b_id)) => align(a, b, *a_id, *b_id),
+            (Some(a_id), None) => only(a, *a_id, Change::Removed),
+            (None, Some(b_id)) => only(b, *b_id, Change::Added),
+            (None, None) => unreachable!(),
+        })
+        .collect()
+}
+
+fn align(a: &Indexed, b: &Indexed, a_id: OpId, b_id: OpId) -> DiffOp {
+    let (a_op, b_op) = (a.op(a_id), b.op(b_id));
+    if discriminant(a_op) != discriminant(b_op) {
+        let added = b.find(b_id, a_op);
+        let removed = a.find(a_id, b_op);
+        match (added, removed) {
+            (Some((depth, through)), removed) if removed.is_none_or(|(r, _)| depth <= r) => {
+                let inputs = b
+                    .inputs(b_id)
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, (branch, input))| {
+                        let input = if idx == through {
+                            align(a, b, a_id, *input)
+                        } else {
+                            only(b, *input, Change::Added)
+                        };
+                        (branch_label(b_op, *branch), input)
+                    })
+                    .collect();
+                return DiffOp {
+                    change: Change::Added,
+                    before: None,
+                    after: Some(b.label(b_op)),
+                    inputs,
+                };
+            }
+            (_, Some((_, through))) => {
+                let inputs = a
+                    .inputs(a_id)
+                    .iter()
+                    .enumerate()
+                    .map(|(idx, (branch, input))| {
+                        let input = if idx == through {
+                            align(a, b, *input, b_id)
+                        } else {
+                            only(a, *input, Change::Removed)
+                        };
+                        (branch_label(a_op, *branch), input)
+                    })
+                    .collect();
+                return DiffOp {
+                    change: Change::Removed,
+                    before: Some(a.label(a_op)),
+                    after: None,
+                    inputs,
+                };
+            }
+            _ => {}
+        }
+    }
+
+    let (before, after) = (a.label(a_op), b.label(b_op));
+    let change = if discriminant(a_op) == discriminant(b_op) && before == after {
+        Change::Unchanged
+    } else {
+        Change::Changed
+    };
+    let a_inputs: Vec<_> = a.inputs(a_id).iter().map(|(_, id)| *id).collect();
+    let b_inputs: Vec<_> = b.inputs(b_id).iter().map(|(_, id)| *id).collect();
+    let branches = b
+        .inputs(b_id)
+        .iter()
+        .map(|(branch, _)| branch_label(b_op, *branch));
+    let removed_branches = a
+        .inputs(a_id)
+        .iter()
+        .skip(b_inputs.len())
+        .map(|(branch, _)| branch_label(a_op, *branch));
+    let inputs = branches
+        .chain(removed_branches)
+        .zip(align_all(a, b, &a_inputs, &b_inputs))
+        .collect();
+    DiffOp {
+        change,
+        before: Some(before),
+        after: Some(after),
+        inputs,
+    }
+}
+
+/// An operator, and its inputs, that is only in one of the plans.
+fn only(plan: &Indexed, id: OpId, change: Change) -> DiffOp {
+    let op = plan.op(id);
+    let label = plan.label(op);
+    let (before, after) = match change {
+        Change::Removed => (Some(label), None),
+        _ => (None, Some(label)),
+    };
+    let inputs = plan
+        .inputs(id)
+        .iter()
+        .map(|(branch, input)| (branch_label(op, *branch), only(plan, *input, change)))
+        .collect();
+    DiffOp {
+        change,
+        before,
+        after,
+        inputs,
+    }
+}
+
+/// Renders a plan diff as an indented tree of operators, marking each as added (`+`), removed
+/// (`-`) or changed (`~`), followed by a summary of the changes.
+pub fn plan_diff_to_text(diff: &PlanDiff) -> String {
+    let mut out = String::new();
+    for root in &diff.roots {
+        write_op(&mut out, root, "", "", "").expect("plan diff text");
+    }
+    for (number, subquery) in &diff.subqueries {
+        writeln!(out, "\n  subquery #{number}:").expect("plan diff text");
+        for root in &subquery.roots {
+            write_op(&mut out, root, "", "", "").expect("plan diff text");
+        }
+    }
+
+    let (added, removed, changed) = diff.counts();
+    if added + removed + changed == 0 {
+        writeln!(out, "\nno differences").expect("plan diff text");
+    } else {
+        writeln!(out, "\n{added} added, {removed} removed, {changed} changed")
+            .expect("plan diff text");
+    }
+    out
+}
+
+fn write_op(
+    out: &mut String,
+    op: &DiffOp,
+    branch: &str,
+    first_prefix: &str,
+    prefix: &str,
+) -> std::fmt::Result {
+    let marker = match op.change {
+        Change::Unchanged => ' ',
+        Change::Changed => '~',
+        Change::Added => '+',
+        Change::Removed => '-',
+    };
+    let OpLabel { name, fields } = op.label();
+    write!(out, "{marker} {first_prefix}{branch}{name}")?;
+    for field in fields {
+        write!(out, " | {field}")?;
+    }
+    writeln!(out)?;
+
+    for (idx, (branch, input)) in op.inputs.iter().enumerate() {
+        let last = idx == op.inputs.len() - 1;
+        let (first, rest) = if last {
+            ("└── ", "    ")
+        } else {
+            ("├── ", "│   ")
+        };
+        let branch = if op.inputs.len() > 1 {
+            format!("[{branch}] ")
+        } else {
+            String::new()
+        };
+        write_op(
+            out,
+            input,
+            &branch,
+            &format!("{prefix}{first}"),
+            &format!("{prefix}{rest}"),
+        )?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::Compiler;
+
+    /// Plans `a` and `b`, and checks the text rendering of their diff; the expected rendering
+    /// starts with a line break, for readability.
+    #[track_caller]
+    fn assert_diff(a: &str, b: &str, text: &str) {
+        let compiler = Compiler::default();
+        let plan = |query| compiler.plan(&compiler.parse(query).unwrap()).unwrap();
+        let diff = diff_plans(&plan(a), &plan(b));
+        assert_eq!(plan_diff_to_text(&diff), text[1..]);
+    }
+
+    #[test]
+    fn same_plans() {
+        assert_diff(
+            "SELECT a FROM t",
+            "SELECT a FROM t",
+            r#"
+  sink
+  └── project | a:a
+      └── scan | t | as t
+
+no differences
+"#,
+        );
+    }
+
+    #[test]
+    fn changed_details() {
+        assert_diff(
+            "SELECT a FROM t WHERE a > 1",
+            "SELECT a, b FROM t WHERE a > 2",
+            r#"
+  sink
+~ └── project | a:a | + b:b
+~     └── filter | a > 1 → a > 2
+          └── scan | t | as t
+
+0 added, 0 removed, 2 changed
+"#,
+        );
+    }
+
+    #[test]
+    fn changed_kind() {
+        assert_diff(
+            "SELECT a FROM t",
+            "SELECT VALUE a FROM t",
+            r#"
+  sink
+~ └── project → project value | a:a → a
+      └── scan | t | as t
+
+0 added, 0 removed, 1 changed
+"#,
+        );
+    }
+
+    #[test]
+    fn added_operator() {
+        assert_diff(
+            "SELECT a FROM t",
+            "SELECT a FROM t WHERE a > 1",
+            r#"
+  sink
+  └── project | a:a
++     └── filter | a > 1
+          └── scan | t | as t
+
+1 added, 0 removed, 0 changed
+"#,
+        );
+    }
+
+    #[test]
+    fn removed_operators() {
+        assert_diff(
+            "SELECT a FROM t WHERE a > 1 ORDER BY a",
+            "SELECT a FROM t",
+            r#"
+  sink
+  └── project | a:a
+-     └── order by | a ASC NULLS LAST
+-         └── filter | a > 1
+              └── scan | t | as t
+
+0 added, 2 removed, 0 changed
+"#,
+        );
+    }
+
+    #[test]
+    fn added_branches() {
+        assert_diff(
+            "SELECT a FROM t",
+            "SELECT x.a FROM t AS x, u AS y",
+            r#"
+  sink
+~ └── project | a:a → a:x.a
++     └── cross join
+~         ├── [left] scan | t | as t → as x
++         └── [right] scan | u | as y
+
+2 added, 0 removed, 2 changed
+"#,
+        );
+        assert_diff(
+            "SELECT a FROM t",
+            "SELECT a FROM t UNION SELECT a FROM u",
+            r#"
+  sink
++ └── union distinct
+      ├── [left] project | a:a
+      │   └── scan | t | as t
++     └── [right] project | a:a
++         └── scan | u | as u
+
+3 added, 0 removed, 0 changed
+"#,
+        );
+    }
+
+    #[test]
+    fn removed_branches() {
+        assert_diff(
+            "SELECT a FROM t UNION SELECT a FROM u",
+            "SELECT a FROM t",
+            r#"
+  sink
+- └── union distinct
+      ├── [left] project | a:a
+      │   └── scan | t | as t
+-     └── [right] project | a:a
+-         └── scan | u | as u
+
+0 added, 3 removed, 0 changed
+"#,
+        );
+    }
+}
@@ -75,6 +75,36 @@ pub(crate) fn repl_config() -> ReplConfig {
     }
 }
 
+/// The theme for graph visualizations, from the user's configuration file if one exists.
+///
+/// Unlike the REPL, this doesn't probe the terminal for its theme, so graphs default to the dark
+/// palette unless `repl.theme` is set.
+#[cfg(feature = "visualize")]
+pub fn graph_theme() -> crate::visualize::render::Theme {
+    crate::visualize::render::Theme::from_config(&user_config())
+}
+
+/// How the `display` format shows graphs, from the user's configuration file if one exists.
+#[cfg(feature = "visualize")]
+pub fn display_options() -> crate::visualize::render::DisplayOptions {
+    crate::visualize::render::DisplayOptions::from_config(&user_config())
+}
+
+/// The default configuration, overridden by the user's configuration file if one exists.
+///
+/// Unlike [`repl_config`], this doesn't create the configuration file or any directories.
+#[cfg(feature = "visualize")]
+fn user_config() -> Config {
+    let mut builder =
+        Config::builder().add_source(config::File::from_str(DEFAULT_CONFIG, FileFormat::Toml));
+    if let Some(dirs) = ProjectDirs::from("org", "partiql", "partiql-cli") {
+        let conf = dirs.config_dir().join("partiql-cli.toml");
+        let conf = config::File::new(conf.to_str().unwrap(), FileFormat::Toml).required(false);
+        builder = builder.add_source(conf);
+    }
+    builder.build().expect("configuration files")
+}
+
 fn infer_theme() -> String {
     const TERM_TIMEOUT_MILLIS: u64 = 20;
     let timeout = std::time::Duration::from_millis(TERM_TIMEOUT_MILLIS);
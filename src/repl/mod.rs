@@ -1,4 +1,6 @@
 mod config;
 mod repl;
 
+#[cfg(feature = "visualize")]
+pub use config::{display_options, graph_theme};
 pub use repl::repl;
@@ -8,6 +8,7 @@ use rustyline::hint::{Hinter, HistoryHinter};
 use rustyline::validate::{ValidationContext, ValidationResult, Validator};
 use rustyline::{ColorMode, Context, Helper};
 use std::borrow::Cow;
+use std::cell::Cell;
 
 use std::io::Write;
 
@@ -15,7 +16,7 @@ use clap::ValueEnum;
 use config::Config;
 use indicatif::{HumanDuration, ProgressBar};
 use std::path::PathBuf;
-use std::time::{Duration, SystemTime};
+use std::time::Duration;
 
 use syntect::easy::HighlightLines;
 use syntect::highlighting::{Style, ThemeSet};
@@ -27,16 +28,24 @@ use owo_colors::OwoColorize;
 use partiql_eval::env::basic::MapBindings;
 use partiql_eval::eval::Evaluated;
 
-use crate::args::OutputFormat;
+use crate::analyze::{analyze_plan, analyzed_plan_to_text};
+use crate::args::{AstPass, ErrorFormat, Expanded, OutputFormat, TableNesting, TimingFormat};
+use partiql_common::node::NodeId;
 use partiql_value::Value;
 use rustyline::history::FileHistory;
 use tracing::field::DisplayValue;
 use tracing::{error, info, span, trace, Level};
 use uuid::Uuid;
 
+use crate::ast_passes::{ast_stages, ast_stages_to_text};
+use crate::ast_tree::node_span;
 use crate::error::CLIErrors;
-use crate::evaluate::{get_bindings, Compiler};
-use crate::formatting::print_value;
+use crate::eval_plan::eval_graph;
+use crate::evaluate::{get_bindings, projected_columns, Compiler};
+use crate::explain::plan_to_text;
+use crate::formatting::{print_value, Caption, FormatOptions};
+use crate::timing::{timed, QueryStats};
+use crate::warning::{report_warnings, CLIWarnings};
 use crate::repl::config::{repl_config, ReplConfig, ION_SYNTAX, PARTIQL_SYNTAX};
 
 struct PartiqlHelper {
@@ -44,6 +53,9 @@ struct PartiqlHelper {
     syntaxes: SyntaxSet,
     themes: ThemeSet,
     globals: MapBindings<Value>,
+    expanded: Cell<Expanded>,
+    timing: Cell<Option<TimingFormat>>,
+    warnings: Cell<bool>,
 }
 
 impl PartiqlHelper {
@@ -59,13 +71,100 @@ impl PartiqlHelper {
 
         let _ps = SyntaxSet::load_defaults_newlines();
         let themes = ThemeSet::load_defaults();
+
+        let config_x: Result<String, _> = config.config.get("repl.expanded");
+        let expanded = config_x
+            .ok()
+            .and_then(|x| Expanded::from_str(&x, true).ok())
+            .unwrap_or_default();
+        let config_timing: Result<String, _> = config.config.get("repl.timing");
+        let timing = match config_timing.map(|t| timing_setting(&t)) {
+            Ok(Ok(timing)) => timing,
+            Ok(Err(msg)) => {
+                eprintln!("Ignoring the `repl.timing` setting: {msg}");
+                None
+            }
+            Err(_) => None,
+        };
+        let config_warnings: Result<String, _> = config.config.get("repl.warnings");
+        let warnings = match config_warnings.map(|w| warnings_setting(&w)) {
+            Ok(Ok(warnings)) => warnings,
+            Ok(Err(msg)) => {
+                eprintln!("Ignoring the `repl.warnings` setting: {msg}");
+                false
+            }
+            Err(_) => false,
+        };
         Ok(PartiqlHelper {
             config,
             syntaxes,
             themes,
             globals,
+            expanded: Cell::new(expanded),
+            timing: Cell::new(timing),
+            warnings: Cell::new(warnings),
         })
     }
+
+    /// The graph layout to use for `\ast` and `\plan` visualizations.
+    #[cfg(feature = "visualize")]
+    fn layout(&self) -> crate::args::Layout {
+        let config_layout: Result<String, _> = self.config.config.get("repl.layout");
+        config_layout
+            .ok()
+            .and_then(|l| crate::args::Layout::from_str(&l, true).ok())
+            .unwrap_or_default()
+    }
+
+    /// The colors and fonts of `\ast` and `\plan` visualizations.
+    #[cfg(feature = "visualize")]
+    fn graph_theme(&self) -> crate::visualize::render::Theme {
+        crate::visualize::render::Theme::from_config(&self.config.config)
+    }
+
+    /// How `\ast` and `\plan` visualizations are shown in the terminal.
+    #[cfg(feature = "visualize")]
+    fn display_options(&self) -> crate::visualize::render::DisplayOptions {
+        crate::visualize::render::DisplayOptions::from_config(&self.config.config)
+    }
+}
+
+/// Parses a `repl.timing` setting or `\timing` argument: `on` (reporting `human` timing), `off`,
+/// or a [`TimingFormat`].
+fn timing_setting(value: &str) -> Result<Option<TimingFormat>, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" => Ok(Some(TimingFormat::Human)),
+        "off" => Ok(None),
+        _ => TimingFormat::from_str(value, true).map(Some).map_err(|_| {
+            format!("Expected one of `on`, `off`, `human`, `json`, or `ion`; found `{value}`")
+        }),
+    }
+}
+
+/// Parses a `repl.warnings` setting or `\warnings` argument: `on` or `off`.
+fn warnings_setting(value: &str) -> Result<bool, String> {
+    match value.to_ascii_lowercase().as_str() {
+        "on" => Ok(true),
+        "off" => Ok(false),
+        _ => Err(format!("Expected one of `on` or `off`; found `{value}`")),
+    }
+}
+
+/// `source` after its leading `keyword`, matched case-insensitively, and the whitespace that
+/// follows it; `None` if it doesn't start with the keyword.
+fn strip_keyword<'a>(source: &'a str, keyword: &str) -> Option<&'a str> {
+    let (word, rest) = source.split_once(char::is_whitespace)?;
+    word.eq_ignore_ascii_case(keyword).then_some(rest)
+}
+
+/// Whether a graph was displayed; a graph that couldn't be rendered is reported, and shown as text
+/// instead.
+#[cfg(feature = "visualize")]
+fn report_render(displayed: Result<bool, CLIErrors>) -> bool {
+    displayed.unwrap_or_else(|err| {
+        eprintln!("{:?}", Report::new(err));
+        false
+    })
 }
 
 impl Helper for PartiqlHelper {}
@@ -115,17 +214,126 @@ impl Validator for PartiqlHelper {
         let request_id = Uuid::new_v4();
         let mut source = ctx.input();
 
+        // `\x [on|off|auto]` toggles expanded display for subsequent queries
+        if let Some(arg) = source.strip_prefix("\\x") {
+            let arg = arg.trim().trim_end_matches(';').trim();
+            let expanded = match (arg, self.expanded.get()) {
+                ("", Expanded::Off) => Expanded::On,
+                ("", _) => Expanded::Off,
+                (arg, _) => match Expanded::from_str(arg, true) {
+                    Ok(expanded) => expanded,
+                    Err(_) => {
+                        return Ok(ValidationResult::Invalid(Some(format!(
+                            "\n\nExpected one of `on`, `off`, or `auto`; found `{arg}`"
+                        ))))
+                    }
+                },
+            };
+            self.expanded.set(expanded);
+            let name = expanded.to_possible_value().expect("expanded value");
+            println!("\nExpanded display is {}.", name.get_name());
+            return Ok(ValidationResult::Valid(None));
+        }
+
+        // `\timing [on|off|human|json|ion]` toggles reporting of query statistics
+        if let Some(arg) = source.strip_prefix("\\timing") {
+            let arg = arg.trim().trim_end_matches(';').trim();
+            let timing = match (arg, self.timing.get()) {
+                ("", None) => Some(TimingFormat::Human),
+                ("", Some(_)) => None,
+                (arg, _) => match timing_setting(arg) {
+                    Ok(timing) => timing,
+                    Err(msg) => return Ok(ValidationResult::Invalid(Some(format!("\n\n{msg}")))),
+                },
+            };
+            self.timing.set(timing);
+            match timing {
+                None => println!("\nTiming is off."),
+                Some(format) => {
+                    let name = format.to_possible_value().expect("timing value");
+                    println!("\nTiming is on ({}).", name.get_name());
+                }
+            }
+            return Ok(ValidationResult::Valid(None));
+        }
+
+        // `\warnings [on|off]` toggles warnings about MISSING values and type mismatches
+        if let Some(arg) = source.strip_prefix("\\warnings") {
+            let arg = arg.trim().trim_end_matches(';').trim();
+            let warnings = match arg {
+                "" => !self.warnings.get(),
+                arg => match warnings_setting(arg) {
+                    Ok(warnings) => warnings,
+                    Err(msg) => return Ok(ValidationResult::Invalid(Some(format!("\n\n{msg}")))),
+                },
+            };
+            self.warnings.set(warnings);
+            println!("\nWarnings are {}.", if warnings { "on" } else { "off" });
+            return Ok(ValidationResult::Valid(None));
+        }
+
+        // `\highlight <node id> <query>` shows the fragment of the query that produced an AST node
+        let mut highlight = None;
+        if let Some(arg) = source.strip_prefix("\\highlight") {
+            let arg = arg.trim_start();
+            let (id, query) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+            match id.trim_start_matches('#').parse() {
+                Ok(id) => highlight = Some(NodeId(id)),
+                Err(_) => {
+                    return Ok(ValidationResult::Invalid(Some(format!(
+                        "\n\nExpected an AST node id (as shown by `\\ast`); found `{id}`"
+                    ))))
+                }
+            }
+            source = query;
+        }
+
         // TODO remove this command parsing hack do something better
         let flag_ast = source.starts_with("\\ast");
         if flag_ast {
             source = source.trim_start_matches("\\ast");
         }
+        // `\ast:<pass> <query>` dumps the AST as of a pass, and `\ast:diff <query>` each pass as the
+        // changes from the pass before it
+        let mut ast_pass = None;
+        if let Some(arg) = source.strip_prefix(':').filter(|_| flag_ast) {
+            let (arg, query) = arg.split_once(char::is_whitespace).unwrap_or((arg, ""));
+            ast_pass = match (arg, AstPass::from_str(arg, true)) {
+                ("diff", _) => Some((AstPass::All, true)),
+                (_, Ok(pass)) => Some((pass, false)),
+                (arg, Err(_)) => {
+                    return Ok(ValidationResult::Invalid(Some(format!(
+                        "\n\nExpected one of `parsed`, `name-resolution`, `all`, or `diff`; found `{arg}`"
+                    ))))
+                }
+            };
+            source = query;
+        }
 
         let flag_plan = source.starts_with("\\plan");
         if flag_plan {
             source = source.trim_start_matches("\\plan");
         }
 
+        let flag_evalplan = source.starts_with("\\evalplan");
+        if flag_evalplan {
+            source = source.trim_start_matches("\\evalplan");
+        }
+
+        let explained = source
+            .strip_prefix("\\explain")
+            .or_else(|| strip_keyword(source, "explain"));
+        let flag_explain = explained.is_some();
+        if let Some(rest) = explained {
+            source = rest.trim_start();
+        }
+        // `EXPLAIN ANALYZE <query>` evaluates the query to annotate its plan with runtime stats
+        let analyzed = strip_keyword(source, "analyze").filter(|_| flag_explain);
+        let flag_analyze = analyzed.is_some();
+        if let Some(rest) = analyzed {
+            source = rest.trim_start();
+        }
+
         let config_of: Result<String, _> = self.config.config.get("repl.output_format");
         let mut output = if let Ok(Ok(fmt)) = config_of.map(|of| OutputFormat::from_str(&of, true))
         {
@@ -134,6 +342,19 @@ impl Validator for PartiqlHelper {
             OutputFormat::Partiql
         };
 
+        let config_tn: Result<String, _> = self.config.config.get("repl.table_nesting");
+        let table_nesting = config_tn
+            .ok()
+            .and_then(|tn| TableNesting::from_str(&tn, true).ok())
+            .unwrap_or_default();
+        let caption: bool = self.config.config.get("repl.caption").unwrap_or(false);
+        let mut options = FormatOptions {
+            table_nesting,
+            expanded: self.expanded.get(),
+            columns: None,
+            caption: None,
+        };
+
         if source.starts_with("\\table") {
             output = OutputFormat::Table;
             source = &source[6..];
@@ -150,6 +371,18 @@ impl Validator for PartiqlHelper {
             output = OutputFormat::Partiql;
             source = &source[8..];
         }
+        if source.starts_with("\\markdown") {
+            output = OutputFormat::Markdown;
+            source = &source[9..];
+        }
+        if source.starts_with("\\vertical") {
+            output = OutputFormat::Vertical;
+            source = &source[9..];
+        }
+        if source.starts_with("\\html") {
+            output = OutputFormat::Html;
+            source = &source[5..];
+        }
 
         let source_len = source.len();
         match source_len {
@@ -175,14 +408,54 @@ impl Validator for PartiqlHelper {
 
             info!("Parsing");
             let compiler = Compiler::default();
-            let result = compiler.parse(source);
+            let mut stats = QueryStats::default();
+            let (result, parse) = timed(|| compiler.parse(source));
+            stats.parse = parse;
             let globals = self.globals.clone();
             match result {
                 Ok(parsed) => {
-                    #[cfg(feature = "visualize")]
-                    if flag_ast {
-                        use crate::visualize::render::display;
-                        display(&parsed.ast);
+                    if let Some(id) = highlight {
+                        let Some(span) = node_span(&parsed, id) else {
+                            return Ok(ValidationResult::Invalid(Some(format!(
+                                "\n\nNo AST node #{} in query",
+                                id.0
+                            ))));
+                        };
+                        println!(
+                            "\n{}{}{}",
+                            &source[..span.start],
+                            (&source[span.clone()]).reversed(),
+                            &source[span.end..]
+                        );
+                        return Ok(ValidationResult::Valid(None));
+                    }
+
+                    if let Some((pass, diff)) = ast_pass {
+                        match ast_stages(&compiler, &parsed) {
+                            Ok(stages) => {
+                                print!("{}", ast_stages_to_text(&parsed, &stages, pass, diff))
+                            }
+                            Err(e) => {
+                                error!("Name resolution failed due to {e}");
+                                let err = Report::new(e);
+                                return Ok(ValidationResult::Invalid(Some(format!(
+                                    "\n\n{err:?}"
+                                ))));
+                            }
+                        }
+                    } else if flag_ast {
+                        #[cfg(feature = "visualize")]
+                        let displayed = report_render(crate::visualize::render::display(
+                            self.layout(),
+                            &self.graph_theme(),
+                            &self.display_options(),
+                            &parsed,
+                        ));
+                        #[cfg(not(feature = "visualize"))]
+                        let displayed = false;
+                        if !displayed {
+                            print!("{}", crate::ast_tree::ast_to_tree(&parsed));
+                        }
                     }
 
                     println!();
@@ -193,7 +466,8 @@ impl Validator for PartiqlHelper {
                     spinner.set_message("Query running");
 
                     info!("Planning");
-                    let plan = compiler.plan(&parsed);
+                    let (plan, plan_time) = timed(|| compiler.plan(&parsed));
+                    stats.plan = plan_time;
                     let plan = match plan {
                         Ok(plan) => plan,
                         Err(e) => {
@@ -202,14 +476,49 @@ impl Validator for PartiqlHelper {
                             return Ok(ValidationResult::Invalid(Some(format!("\n\n{err:?}"))));
                         }
                     };
-                    #[cfg(feature = "visualize")]
                     if flag_plan {
-                        use crate::visualize::render::display;
-                        display(&plan);
+                        #[cfg(feature = "visualize")]
+                        let displayed = report_render(crate::visualize::render::display(
+                            self.layout(),
+                            &self.graph_theme(),
+                            &self.display_options(),
+                            &plan,
+                        ));
+                        #[cfg(not(feature = "visualize"))]
+                        let displayed = false;
+                        if !displayed {
+                            print!("{}", plan_to_text(&plan));
+                        }
+                    }
+
+                    if flag_analyze {
+                        info!("Analyzing");
+                        let analyzed = analyze_plan(&compiler, &parsed, plan, &globals);
+                        spinner.finish_and_clear();
+                        return match analyzed {
+                            Ok(analyzed) => {
+                                print!("{}", analyzed_plan_to_text(&analyzed));
+                                Ok(ValidationResult::Valid(None))
+                            }
+                            Err(e) => {
+                                error!("Analyzing failed due to {e}");
+                                let err = Report::new(e);
+                                Ok(ValidationResult::Invalid(Some(format!("\n\n{err:?}"))))
+                            }
+                        };
                     }
 
+                    if flag_explain {
+                        spinner.finish_and_clear();
+                        print!("{}", plan_to_text(&plan));
+                        return Ok(ValidationResult::Valid(None));
+                    }
+
+                    options.columns = projected_columns(&plan);
+
                     info!("Compiling");
-                    let eval = compiler.compile(&parsed, &plan);
+                    let (eval, compile) = timed(|| compiler.compile(&parsed, &plan));
+                    stats.compile = compile;
                     let eval = match eval {
                         Ok(eval) => eval,
                         Err(e) => {
@@ -219,21 +528,54 @@ impl Validator for PartiqlHelper {
                         }
                     };
 
+                    if flag_evalplan {
+                        let graph = eval_graph(&eval);
+                        #[cfg(feature = "visualize")]
+                        let displayed = report_render(crate::visualize::render::display(
+                            self.layout(),
+                            &self.graph_theme(),
+                            &self.display_options(),
+                            &graph,
+                        ));
+                        #[cfg(not(feature = "visualize"))]
+                        let displayed = false;
+                        if !displayed {
+                            print!("{}", crate::eval_plan::eval_graph_to_text(&graph));
+                        }
+                    }
+
                     info!("Evaluating");
-                    let start = SystemTime::now();
-                    let evaluated = compiler.evaluate(&parsed, eval, globals);
-                    let end = SystemTime::now();
-                    let duration = end.duration_since(start).unwrap();
-                    let duration = HumanDuration(duration);
+                    let (evaluated, elapsed) =
+                        timed(|| compiler.evaluate_recorded(&parsed, eval, globals));
+                    stats.evaluate = elapsed;
+                    let duration = HumanDuration(elapsed);
 
                     match evaluated {
-                        Ok(Evaluated { result: v, .. }) => {
+                        Ok((Evaluated { result: v, .. }, recorded)) => {
                             info!("Evaluation finished in {duration}");
                             spinner.finish_with_message(format!("Query finished in {duration}"));
                             println!("\n==='\n");
 
+                            if caption {
+                                options.caption = Some(Caption {
+                                    query: source.to_string(),
+                                    elapsed,
+                                });
+                            }
+
                             info!(?output, "Printing");
-                            print_value(&output, &v);
+                            print_value(&output, &options, &v);
+                            if let Some(format) = self.timing.get() {
+                                stats.rows = v.iter().count();
+                                stats.report(format);
+                            }
+                            let mut warnings = CLIWarnings::recorded(&parsed, recorded);
+                            if self.warnings.get() {
+                                warnings.check(&compiler, &parsed, &plan, self.globals.clone(), &v);
+                            }
+                            if !warnings.is_empty() {
+                                report_warnings(ErrorFormat::Human, warnings);
+                            }
                             println!();
                             std::io::stdout().flush();
                             std::io::stderr().flush();
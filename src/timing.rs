@@ -0,0 +1,134 @@
+use crate::args::TimingFormat;
+
+use ion_rs::{IonType, IonWriter};
+use std::fmt::{Display, Formatter};
+use std::time::{Duration, Instant};
+
+/// Durations of each phase of running a query, along with the cardinality of its result.
+#[derive(Debug, Default, Copy, Clone)]
+pub struct QueryStats {
+    pub parse: Duration,
+    pub plan: Duration,
+    pub compile: Duration,
+    pub evaluate: Duration,
+    pub rows: usize,
+}
+
+/// Runs `f`, returning its result along with how long it took.
+#[inline]
+pub fn timed<T>(f: impl FnOnce() -> T) -> (T, Duration) {
+    let start = Instant::now();
+    let res = f();
+    (res, start.elapsed())
+}
+
+impl QueryStats {
+    pub fn total(&self) -> Duration {
+        self.parse + self.plan + self.compile + self.evaluate
+    }
+
+    /// Writes the stats to stderr in the given format.
+    pub fn report(&self, format: TimingFormat) {
+        match format {
+            TimingFormat::Human => eprintln!("{self}"),
+            TimingFormat::Json => eprintln!("{}", self.to_json()),
+            TimingFormat::Ion => eprintln!("{}", self.to_ion()),
+        }
+    }
+
+    fn fields(&self) -> [(&'static str, i64); 6] {
+        let micros = |d: Duration| d.as_micros() as i64;
+        [
+            ("parse_us", micros(self.parse)),
+            ("plan_us", micros(self.plan)),
+            ("compile_us", micros(self.compile)),
+            ("evaluate_us", micros(self.evaluate)),
+            ("total_us", micros(self.total())),
+            ("rows", self.rows as i64),
+        ]
+    }
+
+    fn to_json(self) -> String {
+        let fields: Vec<_> = self
+            .fields()
+            .iter()
+            .map(|(k, v)| format!("\"{k}\":{v}"))
+            .collect();
+        format!("{{{}}}", fields.join(","))
+    }
+
+    fn to_ion(self) -> String {
+        let mut buf = vec![];
+        let mut writer = ion_rs::TextWriterBuilder::lines()
+            .build(&mut buf)
+            .expect("ion writer");
+        self.write_ion(&mut writer).expect("ion write");
+        writer.flush().expect("ion flush");
+        drop(writer);
+        String::from_utf8(buf).expect("ion text")
+    }
+
+    fn write_ion<W: IonWriter>(&self, writer: &mut W) -> ion_rs::IonResult<()> {
+        writer.step_in(IonType::Struct)?;
+        for (k, v) in self.fields() {
+            writer.set_field_name(k);
+            writer.write_i64(v)?;
+        }
+        writer.step_out()
+    }
+}
+
+impl Display for QueryStats {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let plural = if self.rows == 1 { "" } else { "s" };
+        write!(
+            f,
+            "Time: {:?} (parse {:?}, plan {:?}, compile {:?}, evaluate {:?}); {} row{plural}",
+            self.total(),
+            self.parse,
+            self.plan,
+            self.compile,
+            self.evaluate,
+            self.rows,
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats() -> QueryStats {
+        QueryStats {
+            parse: Duration::from_micros(1),
+            plan: Duration::from_micros(20),
+            compile: Duration::from_micros(300),
+            evaluate: Duration::from_millis(4),
+            rows: 2,
+        }
+    }
+
+    #[test]
+    fn json() {
+        assert_eq!(
+            stats().to_json(),
+            r#"{"parse_us":1,"plan_us":20,"compile_us":300,"evaluate_us":4000,"total_us":4321,"rows":2}"#
+        );
+    }
+
+    #[test]
+    fn ion() {
+        assert_eq!(
+            stats().to_ion().trim_end(),
+            "{parse_us: 1, plan_us: 20, compile_us: 300, evaluate_us: 4000, total_us: 4321, rows: 2}"
+        );
+    }
+
+    #[test]
+    fn human() {
+        assert_eq!(
+            stats().to_string(),
+            "Time: 4.321ms (parse 1µs, plan 20µs, compile 300µs, evaluate 4ms); 2 rows"
+        );
+    }
+}
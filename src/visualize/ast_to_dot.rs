@@ -1,8 +1,15 @@
 use partiql_ast::ast;
 
-use crate::visualize::common::{ToDotGraph, BG_COLOR, FG_COLOR};
-use dot_writer::{Attributes, DotWriter, Node, NodeId, Scope, Shape};
+use crate::ast_tree::{
+    bag_op_to_str, bin_op_to_str, lit_to_str, node_span, symbol_primitive_to_label, type_to_str,
+    uni_op_to_str,
+};
+use crate::visualize::common::{NodeCategory, Theme, ToDotGraph};
+use dot_writer::{Attributes, DotWriter, Node, NodeId, Scope};
 use partiql_ast::ast::TopLevelQuery;
+use partiql_common::node::NodeId as AstNodeId;
+use partiql_parser::Parsed;
+use std::collections::{HashMap, HashSet};
 
 /*
 subgraph cluster_legend {
@@ -15,6 +22,12 @@ subgraph cluster_legend {
 
 trait ScopeExt<'d, 'w> {
     fn node_auto_labelled(&mut self, lbl: &str) -> Node<'_, 'w>;
+    fn node_categorized(
+        &mut self,
+        lbl: &str,
+        category: NodeCategory,
+        theme: &Theme,
+    ) -> Node<'_, 'w>;
     fn cluster_auto_labelled(&mut self, lbl: &str) -> Scope<'_, 'w>;
     fn with_cluster<F, R>(&mut self, lbl: &str, func: F) -> R
     where
@@ -29,6 +42,18 @@ impl<'d, 'w> ScopeExt<'d, 'w> for Scope<'d, 'w> {
         node
     }
 
+    fn node_categorized(
+        &mut self,
+        lbl: &str,
+        category: NodeCategory,
+        theme: &Theme,
+    ) -> Node<'_, 'w> {
+        let mut node = self.node_auto_labelled(lbl);
+        node.set("shape", category.shape(), false)
+            .set("color", theme.color(category), true);
+        node
+    }
+
     fn cluster_auto_labelled(&mut self, lbl: &str) -> Scope<'_, 'w> {
         let mut cluster = self.cluster();
         cluster.set("label", lbl, lbl.contains(" "));
@@ -59,12 +84,56 @@ impl ChildEdgeExt for Targets {
 
 type Targets = Vec<NodeId>;
 
-pub struct AstToDot {}
+pub struct AstToDot {
+    /// Tooltips giving the node id and source span of each AST node.
+    tooltips: HashMap<AstNodeId, String>,
+    /// Graph nodes that have been annotated with an AST node's tooltip.
+    annotated: HashSet<String>,
+    theme: Theme,
+}
 
 impl Default for AstToDot {
     fn default() -> Self {
-        AstToDot {}
+        AstToDot {
+            tooltips: HashMap::new(),
+            annotated: HashSet::new(),
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl AstToDot {
+    /// Creates an `AstToDot` that annotates nodes with their spans in the parsed query's text.
+    pub fn with_source(parsed: &Parsed) -> Self {
+        let tooltips = parsed
+            .locations
+            .keys()
+            .filter_map(|id| {
+                let span = node_span(parsed, *id)?;
+                let loc = &parsed.locations[id];
+                let text = parsed.text.get(span)?;
+                Some((*id, format!("#{} {loc}: {text}", id.0)))
+            })
+            .collect();
+        AstToDot {
+            tooltips,
+            annotated: HashSet::new(),
+            theme: Theme::default(),
+        }
     }
+
+    /// Draws nodes using `theme`'s colors and fonts.
+    pub fn with_theme(mut self, theme: Theme) -> Self {
+        self.theme = theme;
+        self
+    }
+}
+
+/// Escapes text for use within a quoted graphviz string.
+fn escape_quoted(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
 }
 
 impl<T> ToDotGraph<T> for AstToDot
@@ -78,23 +147,28 @@ where
             let mut writer = DotWriter::from(&mut output_bytes);
             writer.set_pretty_print(true);
             let mut digraph = writer.digraph();
+            let theme = &self.theme;
             digraph
                 .graph_attributes()
                 .set_rank_direction(dot_writer::RankDirection::TopBottom)
                 .set("rankdir", "0.05", false)
-                .set("bgcolor", BG_COLOR, false)
-                .set("fontcolor", FG_COLOR, false)
-                .set("pencolor", FG_COLOR, false);
-            digraph.node_attributes().set("color", FG_COLOR, false).set(
-                "fontcolor",
-                FG_COLOR,
-                false,
-            );
-            digraph.edge_attributes().set("color", FG_COLOR, false).set(
-                "fontcolor",
-                FG_COLOR,
-                false,
-            );
+                .set("bgcolor", &theme.background, true)
+                .set("fontcolor", &theme.foreground, true)
+                .set("pencolor", &theme.foreground, true)
+                .set("fontname", &theme.font, true)
+                .set("fontsize", &theme.font_size.to_string(), false);
+            digraph
+                .node_attributes()
+                .set("color", theme.color(NodeCategory::Clause), true)
+                .set("fontcolor", &theme.foreground, true)
+                .set("fontname", &theme.font, true)
+                .set("fontsize", &theme.font_size.to_string(), false);
+            digraph
+                .edge_attributes()
+                .set("color", &theme.foreground, true)
+                .set("fontcolor", &theme.foreground, true)
+                .set("fontname", &theme.font, true)
+                .set("fontsize", &theme.font_size.to_string(), false);
 
             self.to_dot(&mut digraph, ast);
         }
@@ -146,7 +220,19 @@ where
     AstToDot: ToDot<T>,
 {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::AstNode<T>) -> Targets {
-        self.to_dot(out, &ast.node)
+        let targets = self.to_dot(out, &ast.node);
+        if let (Some(target), Some(tooltip)) = (targets.first(), self.tooltips.get(&ast.id)) {
+            // AST nodes wrapping others may be drawn as the same graph node; keep the innermost
+            let name = String::from(target.clone());
+            if self.annotated.insert(name.clone()) {
+                // restating a node merges the new attributes into it; the `id` names the node's
+                // element in svg output
+                out.node_named(name)
+                    .set("tooltip", &escape_quoted(tooltip), true)
+                    .set("id", &format!("ast-{}", ast.id.0), true);
+            }
+        }
+        targets
     }
 }
 
@@ -170,87 +256,101 @@ impl ToDot<ast::Expr> for AstToDot {
             Expr::Like(like) => self.to_dot(&mut expr_subgraph, like),
             Expr::Between(btwn) => self.to_dot(&mut expr_subgraph, btwn),
             Expr::In(in_expr) => self.to_dot(&mut expr_subgraph, in_expr),
-            Expr::Case(_) => todo!(),
-            Expr::Struct(_) => todo!(),
-            Expr::Bag(_) => todo!(),
-            Expr::List(_) => todo!(),
-            Expr::Sexp(_) => todo!(),
+            Expr::Case(c) => self.to_dot(&mut expr_subgraph, c),
+            Expr::Struct(s) => self.to_dot(&mut expr_subgraph, s),
+            Expr::Bag(b) => self.to_dot(&mut expr_subgraph, b),
+            Expr::List(l) => self.to_dot(&mut expr_subgraph, l),
+            Expr::Sexp(s) => self.to_dot(&mut expr_subgraph, s),
             Expr::Path(p) => self.to_dot(&mut expr_subgraph, p),
             Expr::Call(c) => self.to_dot(&mut expr_subgraph, c),
             Expr::CallAgg(c) => self.to_dot(&mut expr_subgraph, c),
             Expr::Query(q) => self.to_dot(&mut expr_subgraph, q),
-            Expr::Error => todo!(),
+            Expr::Error => vec![expr_subgraph.node_auto_labelled("Error").id()],
         }
     }
 }
 
-#[inline]
-fn lit_to_str(ast: &ast::Lit) -> String {
-    use ast::Lit;
-    match ast {
-        Lit::Null => "NULL".to_string(),
-        Lit::Missing => "MISSING".to_string(),
-        Lit::Int8Lit(l) => l.to_string(),
-        Lit::Int16Lit(l) => l.to_string(),
-        Lit::Int32Lit(l) => l.to_string(),
-        Lit::Int64Lit(l) => l.to_string(),
-        Lit::DecimalLit(l) => l.to_string(),
-        Lit::NumericLit(l) => l.to_string(),
-        Lit::RealLit(l) => l.to_string(),
-        Lit::FloatLit(l) => l.to_string(),
-        Lit::DoubleLit(l) => l.to_string(),
-        Lit::BoolLit(l) => (if *l { "TRUE" } else { "FALSE" }).to_string(),
-        Lit::IonStringLit(l) => format!("`{}`", l),
-        Lit::CharStringLit(l) => format!("'{}'", l),
-        Lit::NationalCharStringLit(l) => format!("'{}'", l),
-        Lit::BitStringLit(l) => format!("b'{}'", l),
-        Lit::HexStringLit(l) => format!("x'{}'", l),
-        Lit::BagLit(b) => todo!("bag literals"),
-        Lit::ListLit(b) => todo!("list literals"),
-        Lit::StructLit(b) => todo!("struct literals"),
-        Lit::TypedLit(val_str, ty) => {
-            format!("{} '{}'", type_to_str(ty), val_str)
+impl AstToDot {
+    /// Draws a node labelled `lbl` with an edge to each side of each of `pairs`, labelled
+    /// `first_lbl` and `second_lbl`.
+    fn pairs_to_dot(
+        &mut self,
+        out: &mut Scope,
+        pairs: &[ast::ExprPair],
+        lbl: &str,
+        first_lbl: &str,
+        second_lbl: &str,
+    ) -> Targets {
+        let mut list = Vec::with_capacity(pairs.len());
+        for pair in pairs {
+            let id = out.node_auto_labelled(lbl).id();
+            self.to_dot(out, &pair.first).edges(out, &id, first_lbl);
+            self.to_dot(out, &pair.second).edges(out, &id, second_lbl);
+            list.push(id);
         }
+        list
     }
 }
 
-#[inline]
-fn custom_type_param_to_str(param: &ast::CustomTypeParam) -> String {
-    use ast::CustomTypeParam;
-    match param {
-        CustomTypeParam::Lit(lit) => lit_to_str(lit),
-        CustomTypeParam::Type(ty) => type_to_str(ty),
+impl ToDot<ast::Case> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::Case) -> Targets {
+        let id = out
+            .node_categorized("CASE", NodeCategory::Operator, &self.theme)
+            .id();
+
+        let (cases, default) = match ast {
+            ast::Case::SimpleCase(case) => {
+                self.to_dot(out, &case.expr).edges(out, &id, "");
+                (&case.cases, &case.default)
+            }
+            ast::Case::SearchedCase(case) => (&case.cases, &case.default),
+        };
+        self.pairs_to_dot(out, cases, "WHEN", "", "then")
+            .edges(out, &id, "");
+        self.to_dot(out, default).edges(out, &id, "else");
+
+        vec![id]
     }
 }
 
-#[inline]
-fn custom_type_part_to_str(part: &ast::CustomTypePart) -> String {
-    use ast::CustomTypePart;
-    match part {
-        CustomTypePart::Name(name) => symbol_primitive_to_label(name),
-        CustomTypePart::Parameterized(name, args) => {
-            let name = symbol_primitive_to_label(name);
-            let args = args
-                .iter()
-                .map(custom_type_param_to_str)
-                .collect::<Vec<_>>()
-                .join(",");
-            format!("{}({})", name, args)
-        }
+impl ToDot<ast::Struct> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::Struct) -> Targets {
+        let id = out.node_auto_labelled("Struct").id();
+
+        self.pairs_to_dot(out, &ast.fields, "Field", "key", "value")
+            .edges(out, &id, "");
+
+        vec![id]
+    }
+}
+
+impl ToDot<ast::Bag> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::Bag) -> Targets {
+        let id = out.node_auto_labelled("Bag").id();
+
+        self.to_dot(out, &ast.values).edges(out, &id, "");
+
+        vec![id]
+    }
+}
+
+impl ToDot<ast::List> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::List) -> Targets {
+        let id = out.node_auto_labelled("List").id();
+
+        self.to_dot(out, &ast.values).edges(out, &id, "");
+
+        vec![id]
     }
 }
 
-#[inline]
-fn type_to_str(ty: &ast::Type) -> String {
-    use ast::Type;
-    match ty {
-        Type::CustomType(cty) => cty
-            .parts
-            .iter()
-            .map(custom_type_part_to_str)
-            .collect::<Vec<_>>()
-            .join(" "),
-        _ => format!("{:?}", ty),
+impl ToDot<ast::Sexp> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::Sexp) -> Targets {
+        let id = out.node_auto_labelled("Sexp").id();
+
+        self.to_dot(out, &ast.values).edges(out, &id, "");
+
+        vec![id]
     }
 }
 
@@ -258,8 +358,7 @@ impl ToDot<ast::Lit> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::Lit) -> Targets {
         let lbl = lit_to_str(ast);
 
-        let mut node = out.node_auto();
-        node.set_label(&lbl).set_shape(Shape::Rectangle);
+        let node = out.node_categorized(&lbl, NodeCategory::Literal, &self.theme);
 
         vec![node.id()]
     }
@@ -267,26 +366,10 @@ impl ToDot<ast::Lit> for AstToDot {
 
 impl ToDot<ast::BinOp> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::BinOp) -> Targets {
-        use ast::BinOpKind;
-        let lbl = match ast.kind {
-            BinOpKind::Add => "+",
-            BinOpKind::Div => "/",
-            BinOpKind::Exp => "^",
-            BinOpKind::Mod => "%",
-            BinOpKind::Mul => "*",
-            BinOpKind::Sub => "-",
-            BinOpKind::And => "AND",
-            BinOpKind::Or => "OR",
-            BinOpKind::Concat => "||",
-            BinOpKind::Eq => "=",
-            BinOpKind::Gt => ">",
-            BinOpKind::Gte => ">=",
-            BinOpKind::Lt => "<",
-            BinOpKind::Lte => "<=",
-            BinOpKind::Ne => "<>",
-            BinOpKind::Is => "IS",
-        };
-        let id = out.node_auto_labelled(lbl).id();
+        let lbl = bin_op_to_str(&ast.kind);
+        let id = out
+            .node_categorized(lbl, NodeCategory::Operator, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.lhs).edges(out, &id, "");
         self.to_dot(out, &ast.rhs).edges(out, &id, "");
@@ -297,13 +380,10 @@ impl ToDot<ast::BinOp> for AstToDot {
 
 impl ToDot<ast::UniOp> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::UniOp) -> Targets {
-        use ast::UniOpKind;
-        let lbl = match ast.kind {
-            UniOpKind::Pos => "+",
-            UniOpKind::Neg => "-",
-            UniOpKind::Not => "NOT",
-        };
-        let id = out.node_auto_labelled(lbl).id();
+        let lbl = uni_op_to_str(&ast.kind);
+        let id = out
+            .node_categorized(lbl, NodeCategory::Operator, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.expr).edges(out, &id, "");
 
@@ -313,7 +393,9 @@ impl ToDot<ast::UniOp> for AstToDot {
 
 impl ToDot<ast::Like> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::Like) -> Targets {
-        let id = out.node_auto_labelled("LIKE").id();
+        let id = out
+            .node_categorized("LIKE", NodeCategory::Operator, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.value).edges(out, &id, "value");
         self.to_dot(out, &ast.pattern).edges(out, &id, "pattern");
@@ -325,7 +407,9 @@ impl ToDot<ast::Like> for AstToDot {
 
 impl ToDot<ast::Between> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::Between) -> Targets {
-        let id = out.node_auto_labelled("BETWEEN").id();
+        let id = out
+            .node_categorized("BETWEEN", NodeCategory::Operator, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.value).edges(out, &id, "value");
         self.to_dot(out, &ast.from).edges(out, &id, "from");
@@ -337,7 +421,9 @@ impl ToDot<ast::Between> for AstToDot {
 
 impl ToDot<ast::In> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::In) -> Targets {
-        let id = out.node_auto_labelled("IN").id();
+        let id = out
+            .node_categorized("IN", NodeCategory::Operator, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.lhs).edges(out, &id, "");
         self.to_dot(out, &ast.rhs).edges(out, &id, "");
@@ -361,15 +447,35 @@ impl ToDot<ast::QuerySet> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::QuerySet) -> Targets {
         use ast::QuerySet;
         match &ast {
-            QuerySet::BagOp(_) => todo!(),
+            QuerySet::BagOp(bag_op) => self.to_dot(out, bag_op),
             QuerySet::Select(select) => self.to_dot(out, select),
             QuerySet::Expr(e) => self.to_dot(out, e),
-            QuerySet::Values(_) => todo!(),
-            QuerySet::Table(_) => todo!(),
+            QuerySet::Values(values) => {
+                let id = out.node_auto_labelled("Values").id();
+                self.to_dot(out, values).edges(out, &id, "");
+                vec![id]
+            }
+            QuerySet::Table(table) => {
+                let lbl = format!("Table {}", symbol_primitive_to_label(&table.table_name));
+                vec![out
+                    .node_categorized(&lbl, NodeCategory::Scan, &self.theme)
+                    .id()]
+            }
         }
     }
 }
 
+impl ToDot<ast::BagOpExpr> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::BagOpExpr) -> Targets {
+        let id = out.node_auto_labelled(&bag_op_to_str(ast)).id();
+
+        self.to_dot(out, &ast.lhs).edges(out, &id, "left");
+        self.to_dot(out, &ast.rhs).edges(out, &id, "right");
+
+        vec![id]
+    }
+}
+
 impl ToDot<ast::Select> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::Select) -> Targets {
         let id = out.node_auto_labelled("Select").id();
@@ -432,8 +538,26 @@ impl ToDot<ast::Projection> for AstToDot {
                     }
                     list
                 }
-                ProjectionKind::ProjectPivot { .. } => todo!(),
-                ProjectionKind::ProjectValue(_) => todo!(),
+                ProjectionKind::ProjectPivot(pivot) => {
+                    let id = expr_subgraph.node_auto_labelled("Pivot").id();
+                    self.to_dot(&mut expr_subgraph, &pivot.value).edges(
+                        &mut expr_subgraph,
+                        &id,
+                        "value",
+                    );
+                    self.to_dot(&mut expr_subgraph, &pivot.key).edges(
+                        &mut expr_subgraph,
+                        &id,
+                        "at",
+                    );
+                    vec![id]
+                }
+                ProjectionKind::ProjectValue(value) => {
+                    let id = expr_subgraph.node_auto_labelled("Value").id();
+                    self.to_dot(&mut expr_subgraph, value)
+                        .edges(&mut expr_subgraph, &id, "");
+                    vec![id]
+                }
             }
         };
 
@@ -461,18 +585,12 @@ impl ToDot<ast::ProjectItem> for AstToDot {
     }
 }
 
-fn symbol_primitive_to_label(sym: &ast::SymbolPrimitive) -> String {
-    use ast::CaseSensitivity;
-    match &sym.case {
-        CaseSensitivity::CaseSensitive => format!("'{}'", sym.value),
-        CaseSensitivity::CaseInsensitive => format!("{}", sym.value),
-    }
-}
-
 impl ToDot<ast::SymbolPrimitive> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::SymbolPrimitive) -> Targets {
         let lbl = symbol_primitive_to_label(ast);
-        let id = out.node_auto_labelled(&lbl).id();
+        let id = out
+            .node_categorized(&lbl, NodeCategory::Variable, &self.theme)
+            .id();
         vec![id]
     }
 }
@@ -484,21 +602,65 @@ impl ToDot<ast::VarRef> for AstToDot {
             ast::ScopeQualifier::Unqualified => lbl,
             ast::ScopeQualifier::Qualified => format!("@{}", lbl),
         };
-        let id = out.node_auto_labelled(&lbl).id();
+        let id = out
+            .node_categorized(&lbl, NodeCategory::Variable, &self.theme)
+            .id();
 
         vec![id]
     }
 }
 
 impl ToDot<ast::OrderByExpr> for AstToDot {
-    fn to_dot(&mut self, _out: &mut Scope, _ast: &ast::OrderByExpr) -> Targets {
-        todo!("OrderByExpr");
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::OrderByExpr) -> Targets {
+        self.to_dot(out, &ast.sort_specs)
+    }
+}
+
+impl ToDot<ast::SortSpec> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::SortSpec) -> Targets {
+        let mut lbl = "SortSpec".to_string();
+        match &ast.ordering_spec {
+            Some(ast::OrderingSpec::Asc) => lbl.push_str(" | ASC"),
+            Some(ast::OrderingSpec::Desc) => lbl.push_str(" | DESC"),
+            None => {}
+        }
+        match &ast.null_ordering_spec {
+            Some(ast::NullOrderingSpec::First) => lbl.push_str(" | NULLS FIRST"),
+            Some(ast::NullOrderingSpec::Last) => lbl.push_str(" | NULLS LAST"),
+            None => {}
+        }
+        let id = out.node_auto_labelled(&lbl).id();
+
+        self.to_dot(out, &ast.expr).edges(out, &id, "");
+
+        vec![id]
     }
 }
 
 impl ToDot<ast::GroupByExpr> for AstToDot {
-    fn to_dot(&mut self, _out: &mut Scope, _ast: &ast::GroupByExpr) -> Targets {
-        todo!("GroupByExpr");
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::GroupByExpr) -> Targets {
+        let lbl = match &ast.strategy {
+            Some(ast::GroupingStrategy::GroupPartial) => "GroupBy | Partial",
+            _ => "GroupBy | Full",
+        };
+        let id = out.node_auto_labelled(lbl).id();
+
+        self.to_dot(out, &ast.keys).edges(out, &id, "");
+        self.to_dot(out, &ast.group_as_alias)
+            .edges(out, &id, "group as");
+
+        vec![id]
+    }
+}
+
+impl ToDot<ast::GroupKey> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::GroupKey) -> Targets {
+        let id = out.node_auto_labelled("GroupKey").id();
+
+        self.to_dot(out, &ast.expr).edges(out, &id, "");
+        self.to_dot(out, &ast.as_alias).edges(out, &id, "as");
+
+        vec![id]
     }
 }
 
@@ -523,7 +685,9 @@ impl ToDot<ast::FromLet> for AstToDot {
             ast::FromLetKind::Scan => "Scan",
             ast::FromLetKind::Unpivot => "Unpivot",
         };
-        let id = out.node_auto_labelled(lbl).id();
+        let id = out
+            .node_categorized(lbl, NodeCategory::Scan, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.expr).edges(out, &id, "");
         self.to_dot(out, &ast.as_alias).edges(out, &id, "as");
@@ -574,7 +738,9 @@ impl ToDot<ast::JoinSpec> for AstToDot {
 
 impl ToDot<ast::Call> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::Call) -> Targets {
-        let id = out.node_auto_labelled("Call").id();
+        let id = out
+            .node_categorized("Call", NodeCategory::Call, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.func_name).edges(out, &id, "name");
         self.to_dot(out, &ast.args).edges(out, &id, "args");
@@ -623,7 +789,9 @@ impl ToDot<ast::CallArg> for AstToDot {
 impl ToDot<ast::CallAgg> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::CallAgg) -> Targets {
         // Set quantifier is defined in `CallAgg.args`
-        let id = out.node_auto_labelled("CallAgg").id();
+        let id = out
+            .node_categorized("CallAgg", NodeCategory::Call, &self.theme)
+            .id();
 
         self.to_dot(out, &ast.func_name).edges(out, &id, "name");
         self.to_dot(out, &ast.args).edges(out, &id, "args");
@@ -646,8 +814,8 @@ impl ToDot<ast::Path> for AstToDot {
 impl ToDot<ast::PathStep> for AstToDot {
     fn to_dot(&mut self, out: &mut Scope, ast: &ast::PathStep) -> Targets {
         match &ast {
-            ast::PathStep::PathExpr(e) => self.to_dot(out, e),
-            ast::PathStep::PathWildCard => vec![out.node_auto_labelled("*").id()],
+            ast::PathStep::PathProject(e) | ast::PathStep::PathIndex(e) => self.to_dot(out, e),
+            ast::PathStep::PathForEach => vec![out.node_auto_labelled("*").id()],
             ast::PathStep::PathUnpivot => vec![out.node_auto_labelled("Unpivot").id()],
         }
     }
@@ -664,7 +832,105 @@ impl ToDot<ast::PathExpr> for AstToDot {
 }
 
 impl ToDot<ast::Let> for AstToDot {
-    fn to_dot(&mut self, _out: &mut Scope, _ast: &ast::Let) -> Targets {
-        todo!("Let");
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::Let) -> Targets {
+        self.to_dot(out, &ast.let_bindings)
+    }
+}
+
+impl ToDot<ast::LetBinding> for AstToDot {
+    fn to_dot(&mut self, out: &mut Scope, ast: &ast::LetBinding) -> Targets {
+        let id = out.node_auto_labelled("LetBinding").id();
+
+        self.to_dot(out, &ast.expr).edges(out, &id, "");
+        self.to_dot(out, &ast.as_alias).edges(out, &id, "as");
+
+        vec![id]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::evaluate::Compiler;
+
+    /// The labels of the nodes and edges in the dot rendering of `query`'s AST, in order.
+    fn labels(query: &str) -> Vec<String> {
+        let parsed = Compiler::default().parse(query).unwrap();
+        let dot = AstToDot::default().to_graph(&parsed.ast);
+        dot.lines()
+            .filter_map(|line| Some(line.split_once("label=\"")?.1.split_once('"')?.0))
+            .filter(|lbl| !lbl.is_empty())
+            .map(String::from)
+            .collect()
+    }
+
+    #[track_caller]
+    fn assert_labels(query: &str, expected: &[&str]) {
+        let labels = labels(query);
+        for lbl in expected {
+            assert!(labels.iter().any(|l| l == lbl), "no {lbl:?} in {labels:?}");
+        }
+    }
+
+    #[test]
+    fn case() {
+        assert_labels(
+            "SELECT CASE a WHEN 1 THEN 'x' ELSE 'y' END FROM t",
+            &["CASE", "WHEN", "then", "else", "'x'", "'y'"],
+        );
+        assert_labels(
+            "SELECT CASE WHEN a > 1 THEN 'x' END FROM t",
+            &["CASE", "WHEN", ">", "then"],
+        );
+    }
+
+    #[test]
+    fn collections() {
+        assert_labels(
+            "SELECT VALUE {'a': [x, 2], 'b': <<x>>, 'c': `(1 2)`} FROM t",
+            &["Value", "Struct", "Field", "key", "value", "List", "Bag"],
+        );
+    }
+
+    #[test]
+    fn pivot() {
+        assert_labels("PIVOT v AT k FROM t", &["Pivot", "value", "at", "v", "k"]);
+    }
+
+    #[test]
+    fn order_by() {
+        assert_labels(
+            "SELECT a FROM t ORDER BY a DESC NULLS FIRST, b",
+            &["order_by", "SortSpec | DESC | NULLS FIRST", "SortSpec"],
+        );
+    }
+
+    #[test]
+    fn group_by() {
+        assert_labels(
+            "SELECT k, g FROM t GROUP BY a AS k GROUP AS g",
+            &["GroupBy | Full", "GroupKey", "as", "group as"],
+        );
+        assert_labels(
+            "SELECT k FROM t GROUP PARTIAL BY a AS k",
+            &["GroupBy | Partial"],
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        assert_labels(
+            "SELECT a FROM t UNION ALL SELECT b FROM u",
+            &["Union All", "left", "right"],
+        );
+        assert_labels(
+            "SELECT a FROM t OUTER EXCEPT SELECT b FROM u",
+            &["Outer Except"],
+        );
+    }
+
+    #[test]
+    fn values() {
+        assert_labels("VALUES (1, 2), (3, 4)", &["Values", "List", "1", "4"]);
     }
 }
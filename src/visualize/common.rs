@@ -1,6 +1,195 @@
+use config::Config;
+use std::collections::HashMap;
+
 pub(crate) trait ToDotGraph<T> {
     fn to_graph(self, data: &T) -> String;
 }
 
-pub(crate) const BG_COLOR: &'static str = "\"#002b3600\"";
-pub(crate) const FG_COLOR: &'static str = "\"#839496\"";
+/// Kinds of graph nodes that are drawn with their own shape and color.
+#[derive(Debug, Copy, Clone, PartialEq, Eq, Hash)]
+pub enum NodeCategory {
+    /// Plan operators that read their input from an expression, e.g. scans, and AST `FROM` sources
+    Scan,
+    /// Plan joins
+    Join,
+    /// Plan filters, e.g. `WHERE` and `HAVING`
+    Filter,
+    /// Plan grouping, aggregation and distinct operators
+    Aggregate,
+    /// Plan projections
+    Project,
+    /// Plan bag operators, e.g. `UNION`
+    SetOp,
+    /// Plan ordering and limits
+    Sort,
+    /// AST queries and clauses
+    Clause,
+    /// AST literals
+    Literal,
+    /// AST variable references
+    Variable,
+    /// AST operators
+    Operator,
+    /// AST function and aggregate calls
+    Call,
+    /// Plan diff operators only in the second plan
+    Added,
+    /// Plan diff operators only in the first plan
+    Removed,
+    /// Plan diff operators that differ between the plans
+    Changed,
+}
+
+impl NodeCategory {
+    const ALL: [NodeCategory; 15] = [
+        NodeCategory::Scan,
+        NodeCategory::Join,
+        NodeCategory::Filter,
+        NodeCategory::Aggregate,
+        NodeCategory::Project,
+        NodeCategory::SetOp,
+        NodeCategory::Sort,
+        NodeCategory::Clause,
+        NodeCategory::Literal,
+        NodeCategory::Variable,
+        NodeCategory::Operator,
+        NodeCategory::Call,
+        NodeCategory::Added,
+        NodeCategory::Removed,
+        NodeCategory::Changed,
+    ];
+
+    /// The category's key within the `graph.colors` configuration table.
+    fn name(self) -> &'static str {
+        match self {
+            NodeCategory::Scan => "scan",
+            NodeCategory::Join => "join",
+            NodeCategory::Filter => "filter",
+            NodeCategory::Aggregate => "aggregate",
+            NodeCategory::Project => "project",
+            NodeCategory::SetOp => "set_op",
+            NodeCategory::Sort => "sort",
+            NodeCategory::Clause => "clause",
+            NodeCategory::Literal => "literal",
+            NodeCategory::Variable => "variable",
+            NodeCategory::Operator => "operator",
+            NodeCategory::Call => "call",
+            NodeCategory::Added => "added",
+            NodeCategory::Removed => "removed",
+            NodeCategory::Changed => "changed",
+        }
+    }
+
+    /// The graphviz shape used for nodes of the category.
+    pub(crate) fn shape(self) -> &'static str {
+        match self {
+            NodeCategory::Scan => "record",
+            NodeCategory::Join
+            | NodeCategory::Filter
+            | NodeCategory::Aggregate
+            | NodeCategory::Project
+            | NodeCategory::SetOp
+            | NodeCategory::Sort
+            | NodeCategory::Call
+            | NodeCategory::Added
+            | NodeCategory::Removed
+            | NodeCategory::Changed => "Mrecord",
+            NodeCategory::Clause | NodeCategory::Variable => "ellipse",
+            NodeCategory::Literal => "box",
+            NodeCategory::Operator => "circle",
+        }
+    }
+}
+
+/// Colors and fonts of rendered graphs.
+#[derive(Debug, Clone)]
+pub struct Theme {
+    pub background: String,
+    pub foreground: String,
+    pub font: String,
+    pub font_size: f32,
+    /// Outline colors of nodes, by category; uncategorized nodes use the foreground color.
+    pub colors: HashMap<NodeCategory, String>,
+}
+
+impl Default for Theme {
+    fn default() -> Self {
+        Theme::dark()
+    }
+}
+
+impl Theme {
+    /// Solarized dark, over a transparent background.
+    pub fn dark() -> Self {
+        Theme::solarized("#002b3600", "#839496")
+    }
+
+    /// Solarized light, over a transparent background.
+    pub fn light() -> Self {
+        Theme::solarized("#fdf6e300", "#657b83")
+    }
+
+    fn solarized(background: &str, foreground: &str) -> Self {
+        // solarized's accent colors are shared by its light and dark variants
+        let colors = HashMap::from([
+            (NodeCategory::Scan, "#268bd2"),
+            (NodeCategory::Join, "#d33682"),
+            (NodeCategory::Filter, "#cb4b16"),
+            (NodeCategory::Aggregate, "#859900"),
+            (NodeCategory::Project, "#2aa198"),
+            (NodeCategory::SetOp, "#6c71c4"),
+            (NodeCategory::Sort, "#b58900"),
+            (NodeCategory::Literal, "#b58900"),
+            (NodeCategory::Variable, "#268bd2"),
+            (NodeCategory::Operator, "#d33682"),
+            (NodeCategory::Call, "#859900"),
+            (NodeCategory::Added, "#859900"),
+            (NodeCategory::Removed, "#dc322f"),
+            (NodeCategory::Changed, "#b58900"),
+        ]);
+        Theme {
+            background: background.to_string(),
+            foreground: foreground.to_string(),
+            font: "Times-Roman".to_string(),
+            font_size: 14.0,
+            colors: colors
+                .into_iter()
+                .map(|(category, color)| (category, color.to_string()))
+                .collect(),
+        }
+    }
+
+    /// Reads a theme from the `graph` configuration table, based on the light or dark palette
+    /// selected by `repl.theme`.
+    pub fn from_config(config: &Config) -> Self {
+        let mut theme = match config.get_string("repl.theme").as_deref() {
+            Ok("light") => Theme::light(),
+            _ => Theme::dark(),
+        };
+        if let Ok(background) = config.get_string("graph.background") {
+            theme.background = background;
+        }
+        if let Ok(foreground) = config.get_string("graph.foreground") {
+            theme.foreground = foreground;
+        }
+        if let Ok(font) = config.get_string("graph.font") {
+            theme.font = font;
+        }
+        if let Ok(font_size) = config.get_float("graph.font_size") {
+            theme.font_size = font_size as f32;
+        }
+        for category in NodeCategory::ALL {
+            if let Ok(color) = config.get_string(&format!("graph.colors.{}", category.name())) {
+                theme.colors.insert(category, color);
+            }
+        }
+        theme
+    }
+
+    pub(crate) fn color(&self, category: NodeCategory) -> &str {
+        self.colors
+            .get(&category)
+            .map(String::as_str)
+            .unwrap_or(&self.foreground)
+    }
+}
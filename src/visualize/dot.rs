@@ -0,0 +1,545 @@
+//! A parser for the subset of the [dot] language produced by [`AstToDot`] and [`PlanToDot`].
+//!
+//! [dot]: https://graphviz.org/doc/info/lang.html
+//! [`AstToDot`]: crate::visualize::ast_to_dot::AstToDot
+//! [`PlanToDot`]: crate::visualize::plan_to_dot::PlanToDot
+
+use std::collections::HashMap;
+
+pub(crate) type Attrs = HashMap<String, String>;
+
+/// A parsed dot graph; nodes and edges are kept in the order they were declared.
+#[derive(Debug, Default)]
+pub(crate) struct DotGraph {
+    pub attrs: Attrs,
+    pub nodes: Vec<DotNode>,
+    pub edges: Vec<DotEdge>,
+    pub clusters: Vec<DotCluster>,
+}
+
+#[derive(Debug)]
+pub(crate) struct DotNode {
+    pub id: String,
+    pub attrs: Attrs,
+    /// The innermost cluster containing the node, as an index into [`DotGraph::clusters`].
+    pub cluster: Option<usize>,
+}
+
+#[derive(Debug)]
+pub(crate) struct DotEdge {
+    pub from: usize,
+    pub to: usize,
+    pub attrs: Attrs,
+}
+
+#[derive(Debug)]
+pub(crate) struct DotCluster {
+    pub attrs: Attrs,
+    /// The enclosing cluster, as an index into [`DotGraph::clusters`].
+    pub parent: Option<usize>,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Id(String),
+    Arrow,
+    Punct(char),
+}
+
+fn tokenize(src: &str) -> Result<Vec<Token>, String> {
+    let mut tokens = vec![];
+    let mut chars = src.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {}
+            '/' if chars.peek() == Some(&'/') => {
+                chars.by_ref().take_while(|c| *c != '\n').for_each(drop);
+            }
+            '/' if chars.peek() == Some(&'*') => {
+                chars.next();
+                let mut prev = ' ';
+                for c in chars.by_ref() {
+                    if prev == '*' && c == '/' {
+                        break;
+                    }
+                    prev = c;
+                }
+            }
+            '-' if matches!(chars.peek(), Some('>') | Some('-')) => {
+                chars.next();
+                tokens.push(Token::Arrow);
+            }
+            '{' | '}' | '[' | ']' | '=' | ';' | ',' | ':' => tokens.push(Token::Punct(c)),
+            '"' => {
+                // Only `\"` and line continuations are escapes at this level; other escapes are
+                // left in place for label processing.
+                let mut s = String::new();
+                loop {
+                    match chars.next() {
+                        None => return Err("unterminated string".to_string()),
+                        Some('"') => break,
+                        Some('\\') => match chars.next() {
+                            Some('"') => s.push('"'),
+                            Some('\n') => {}
+                            Some(c) => {
+                                s.push('\\');
+                                s.push(c);
+                            }
+                            None => return Err("unterminated string".to_string()),
+                        },
+                        Some(c) => s.push(c),
+                    }
+                }
+                tokens.push(Token::Id(s));
+            }
+            '<' => {
+                let mut depth = 1;
+                let mut s = String::new();
+                for c in chars.by_ref() {
+                    match c {
+                        '<' => depth += 1,
+                        '>' => depth -= 1,
+                        _ => {}
+                    }
+                    if depth == 0 {
+                        break;
+                    }
+                    s.push(c);
+                }
+                tokens.push(Token::Id(s));
+            }
+            c if c.is_alphanumeric() || matches!(c, '_' | '.' | '-' | '#') => {
+                let mut s = String::from(c);
+                while let Some(&c) = chars.peek() {
+                    if c.is_alphanumeric() || matches!(c, '_' | '.' | '#') {
+                        s.push(c);
+                        chars.next();
+                    } else {
+                        break;
+                    }
+                }
+                tokens.push(Token::Id(s));
+            }
+            c => return Err(format!("unexpected character `{c}`")),
+        }
+    }
+    Ok(tokens)
+}
+
+/// The attribute defaults in effect within a graph or subgraph.
+#[derive(Clone, Default)]
+struct Defaults {
+    node: Attrs,
+    edge: Attrs,
+}
+
+struct Parser {
+    tokens: Vec<Token>,
+    pos: usize,
+    graph: DotGraph,
+    node_ids: HashMap<String, usize>,
+}
+
+impl Parser {
+    fn peek(&self) -> Option<&Token> {
+        self.tokens.get(self.pos)
+    }
+
+    fn next(&mut self) -> Option<Token> {
+        let tok = self.tokens.get(self.pos).cloned();
+        self.pos += 1;
+        tok
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(&Token::Punct(c)) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn expect(&mut self, c: char) -> Result<(), String> {
+        if self.eat(c) {
+            Ok(())
+        } else {
+            Err(format!("expected `{c}`, found {:?}", self.peek()))
+        }
+    }
+
+    fn id(&mut self) -> Result<String, String> {
+        match self.next() {
+            Some(Token::Id(id)) => Ok(id),
+            tok => Err(format!("expected an identifier, found {tok:?}")),
+        }
+    }
+
+    fn peek_keyword(&self, kw: &str) -> bool {
+        matches!(self.peek(), Some(Token::Id(id)) if id.eq_ignore_ascii_case(kw))
+    }
+
+    fn graph(&mut self) -> Result<(), String> {
+        if self.peek_keyword("strict") {
+            self.pos += 1;
+        }
+        if !(self.peek_keyword("digraph") || self.peek_keyword("graph")) {
+            return Err(format!("expected a graph, found {:?}", self.peek()));
+        }
+        self.pos += 1;
+        if let Some(Token::Id(_)) = self.peek() {
+            self.pos += 1;
+        }
+        self.expect('{')?;
+        self.stmts(Defaults::default(), None)?;
+        self.expect('}')
+    }
+
+    /// Parses `[a=b, c=d][...]` into `attrs`, if present.
+    fn attr_lists(&mut self, attrs: &mut Attrs) -> Result<(), String> {
+        while self.eat('[') {
+            while !self.eat(']') {
+                let name = self.id()?;
+                let value = if self.eat('=') {
+                    self.id()?
+                } else {
+                    "true".to_string()
+                };
+                attrs.insert(name, value);
+                let _ = self.eat(',') || self.eat(';');
+            }
+        }
+        Ok(())
+    }
+
+    fn stmts(&mut self, mut defaults: Defaults, cluster: Option<usize>) -> Result<(), String> {
+        loop {
+            match self.peek() {
+                None | Some(Token::Punct('}')) => return Ok(()),
+                Some(Token::Punct(';')) => self.pos += 1,
+                Some(Token::Punct('{')) => {
+                    self.pos += 1;
+                    self.stmts(defaults.clone(), cluster)?;
+                    self.expect('}')?;
+                }
+                Some(Token::Id(id)) => match id.to_ascii_lowercase().as_str() {
+                    "graph" => {
+                        self.pos += 1;
+                        let mut attrs = Attrs::new();
+                        self.attr_lists(&mut attrs)?;
+                        self.graph_attrs(cluster).extend(attrs);
+                    }
+                    "node" => {
+                        self.pos += 1;
+                        self.attr_lists(&mut defaults.node)?;
+                    }
+                    "edge" => {
+                        self.pos += 1;
+                        self.attr_lists(&mut defaults.edge)?;
+                    }
+                    "subgraph" => {
+                        self.pos += 1;
+                        let mut inner = cluster;
+                        if let Some(Token::Id(name)) = self.peek() {
+                            if name.starts_with("cluster") {
+                                self.graph.clusters.push(DotCluster {
+                                    attrs: Attrs::new(),
+                                    parent: cluster,
+                                });
+                                inner = Some(self.graph.clusters.len() - 1);
+                            }
+                            self.pos += 1;
+                        }
+                        self.expect('{')?;
+                        self.stmts(defaults.clone(), inner)?;
+                        self.expect('}')?;
+                    }
+                    _ => self.node_or_edge(&defaults, cluster)?,
+                },
+                tok => return Err(format!("unexpected {tok:?}")),
+            }
+        }
+    }
+
+    fn graph_attrs(&mut self, cluster: Option<usize>) -> &mut Attrs {
+        match cluster {
+            Some(idx) => &mut self.graph.clusters[idx].attrs,
+            None => &mut self.graph.attrs,
+        }
+    }
+
+    fn node_or_edge(&mut self, defaults: &Defaults, cluster: Option<usize>) -> Result<(), String> {
+        let first = self.id()?;
+        if self.eat('=') {
+            let value = self.id()?;
+            self.graph_attrs(cluster).insert(first, value);
+            return Ok(());
+        }
+
+        let mut ids = vec![first];
+        self.port();
+        while self.peek() == Some(&Token::Arrow) {
+            self.pos += 1;
+            ids.push(self.id()?);
+            self.port();
+        }
+
+        if ids.len() == 1 {
+            let idx = self.node(&ids[0], defaults, cluster);
+            let mut attrs = Attrs::new();
+            self.attr_lists(&mut attrs)?;
+            self.graph.nodes[idx].attrs.extend(attrs);
+        } else {
+            let mut attrs = defaults.edge.clone();
+            self.attr_lists(&mut attrs)?;
+            let nodes: Vec<_> = ids
+                .iter()
+                .map(|id| self.node(id, defaults, cluster))
+                .collect();
+            for pair in nodes.windows(2) {
+                self.graph.edges.push(DotEdge {
+                    from: pair[0],
+                    to: pair[1],
+                    attrs: attrs.clone(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Skips a `:port[:compass]` suffix on a node id; ports are not used for layout.
+    fn port(&mut self) {
+        while self.eat(':') {
+            self.pos += 1;
+        }
+    }
+
+    /// Looks up a node by id, declaring it if it hasn't been seen before.
+    fn node(&mut self, id: &str, defaults: &Defaults, cluster: Option<usize>) -> usize {
+        if let Some(idx) = self.node_ids.get(id) {
+            return *idx;
+        }
+        self.graph.nodes.push(DotNode {
+            id: id.to_string(),
+            attrs: defaults.node.clone(),
+            cluster,
+        });
+        let idx = self.graph.nodes.len() - 1;
+        self.node_ids.insert(id.to_string(), idx);
+        idx
+    }
+}
+
+/// Parses a dot-formatted graph.
+pub(crate) fn parse(src: &str) -> Result<DotGraph, String> {
+    let mut parser = Parser {
+        tokens: tokenize(src)?,
+        pos: 0,
+        graph: DotGraph::default(),
+        node_ids: HashMap::new(),
+    };
+    parser.graph()?;
+    Ok(parser.graph)
+}
+
+/// A node label, split into lines of text.
+pub(crate) enum Label {
+    Text(Vec<String>),
+    Record {
+        vertical: bool,
+        fields: Vec<Vec<String>>,
+    },
+}
+
+/// Looks up an attribute, falling back to `default`.
+pub(crate) fn attr<'a>(attrs: &'a Attrs, name: &str, default: &'a str) -> &'a str {
+    attrs.get(name).map(String::as_str).unwrap_or(default)
+}
+
+/// A node's shape, defaulting to dot's `ellipse`.
+pub(crate) fn shape(attrs: &Attrs) -> &str {
+    attr(attrs, "shape", "ellipse")
+}
+
+/// Reads a node's label, splitting `record` and `Mrecord` labels into their fields.
+pub(crate) fn node_label(id: &str, attrs: &Attrs) -> Label {
+    let label = attr(attrs, "label", "\\N");
+    if !matches!(shape(attrs), "record" | "Mrecord") {
+        return Label::Text(label_lines(label, id));
+    }
+
+    let label = label.trim();
+    let (vertical, label) = match label.strip_prefix('{').and_then(|l| l.strip_suffix('}')) {
+        Some(inner) if !label.ends_with("\\}") => (true, inner),
+        _ => (false, label),
+    };
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => {
+                field.push(c);
+                field.extend(chars.next());
+            }
+            '|' => fields.push(std::mem::take(&mut field)),
+            // nested records and ports are flattened into plain fields
+            '{' | '}' => {}
+            '<' => {
+                chars.by_ref().take_while(|c| *c != '>').for_each(drop);
+            }
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    Label::Record {
+        vertical,
+        fields: fields.iter().map(|f| label_lines(f.trim(), id)).collect(),
+    }
+}
+
+/// Splits an escaped dot label into lines of text.
+pub(crate) fn label_lines(label: &str, id: &str) -> Vec<String> {
+    let mut lines = vec![];
+    let mut line = String::new();
+    let mut chars = label.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n' | 'l' | 'r') => lines.push(std::mem::take(&mut line)),
+                Some('N') => line.push_str(id),
+                Some('G') => {}
+                Some(c) => line.push(c),
+                None => {}
+            },
+            '\n' => lines.push(std::mem::take(&mut line)),
+            c => line.push(c),
+        }
+    }
+    // a trailing line break ends the last line rather than starting an empty one
+    if lines.is_empty() || !line.is_empty() {
+        lines.push(line);
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(lines: &[&str]) -> Vec<String> {
+        lines.iter().map(|l| l.to_string()).collect()
+    }
+
+    #[test]
+    fn quoted_ids() {
+        let graph = parse(
+            r#"digraph "a graph" { "a b" -> "c\"d"; "e\
+f"; <x <b>y</b>> }"#,
+        )
+        .unwrap();
+        let ids: Vec<_> = graph.nodes.iter().map(|n| n.id.as_str()).collect();
+        assert_eq!(ids, ["a b", "c\"d", "ef", "x <b>y</b>"]);
+        assert_eq!(graph.edges.len(), 1);
+        assert!(parse(r#"digraph { "a }"#).is_err());
+    }
+
+    #[test]
+    fn label_escapes() {
+        assert_eq!(label_lines(r"a\nb\lc\r", "n"), lines(&["a", "b", "c"]));
+        assert_eq!(label_lines(r"\N: \G\{x\}", "n"), lines(&["n: {x}"]));
+        assert_eq!(label_lines("a\n\\n", "n"), lines(&["a", ""]));
+        assert_eq!(label_lines("", "n"), lines(&[""]));
+        // escapes other than `\"` are kept by the tokenizer for label processing
+        let graph = parse(r#"digraph { a [label="say \"hi\"\nbye"] }"#).unwrap();
+        assert_eq!(graph.nodes[0].attrs["label"], r#"say "hi"\nbye"#);
+    }
+
+    #[test]
+    fn record_labels() {
+        let attrs = |label: &str| {
+            Attrs::from([
+                ("shape".to_string(), "Mrecord".to_string()),
+                ("label".to_string(), label.to_string()),
+            ])
+        };
+        let Label::Record { vertical, fields } =
+            node_label("n", &attrs(r"{ scan | t \| u | <p> as t }"))
+        else {
+            panic!("expected a record label");
+        };
+        assert!(vertical);
+        assert_eq!(
+            fields,
+            [lines(&["scan"]), lines(&["t | u"]), lines(&["as t"])]
+        );
+
+        let Label::Record { vertical, .. } = node_label("n", &attrs(r"a | b\}")) else {
+            panic!("expected a record label");
+        };
+        assert!(!vertical);
+    }
+
+    #[test]
+    fn attributes() {
+        let graph = parse(
+            r#"
+            // a comment
+            digraph {
+                rankdir = TB;
+                graph [bgcolor="white", fontname=Helvetica]
+                node [shape=box];
+                edge [color=red];
+                a; b [shape=ellipse, style=filled]
+                /* another comment */
+                subgraph cluster_0 {
+                    label = "a cluster";
+                    node [color=blue];
+                    c [label=C]
+                    subgraph cluster_1 { d }
+                }
+                a -> b -> c [label=x];
+                c:port:n -> d;
+            }"#,
+        )
+        .unwrap();
+        assert_eq!(graph.attrs["rankdir"], "TB");
+        assert_eq!(graph.attrs["bgcolor"], "white");
+        assert_eq!(graph.attrs["fontname"], "Helvetica");
+
+        let node = |id: &str| graph.nodes.iter().find(|n| n.id == id).unwrap();
+        assert_eq!(shape(&node("a").attrs), "box");
+        assert_eq!(shape(&node("b").attrs), "ellipse");
+        assert_eq!(node("b").attrs["style"], "filled");
+        assert_eq!(attr(&node("c").attrs, "color", "black"), "blue");
+        assert_eq!(attr(&node("a").attrs, "color", "black"), "black");
+
+        assert_eq!(node("a").cluster, None);
+        assert_eq!(node("c").cluster, Some(0));
+        assert_eq!(node("d").cluster, Some(1));
+        assert_eq!(graph.clusters[0].attrs["label"], "a cluster");
+        assert_eq!(graph.clusters[1].parent, Some(0));
+
+        let edges: Vec<_> = graph
+            .edges
+            .iter()
+            .map(|e| {
+                let attrs = &e.attrs;
+                (
+                    graph.nodes[e.from].id.as_str(),
+                    graph.nodes[e.to].id.as_str(),
+                    attr(attrs, "label", ""),
+                    attr(attrs, "color", ""),
+                )
+            })
+            .collect();
+        assert_eq!(
+            edges,
+            [
+                ("a", "b", "x", "red"),
+                ("b", "c", "x", "red"),
+                ("c", "d", "", "red")
+            ]
+        );
+    }
+}
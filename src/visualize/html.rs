@@ -0,0 +1,141 @@
+//! A self-contained html page for exploring a rendered graph alongside the query it was drawn for.
+
+use crate::ast_tree::node_span;
+use crate::visualize::common::Theme;
+use crate::visualize::dot::{self, node_label, Label};
+use partiql_common::node::NodeId;
+use partiql_parser::Parsed;
+use serde_json::{json, Map, Value};
+
+static EXPLORER_TEMPLATE: &str = include_str!(concat!(
+    env!("CARGO_MANIFEST_DIR"),
+    "/resources/explorer.html"
+));
+
+/// Which of a node's neighbors make up its subtree when it is collapsed.
+#[derive(Debug, Copy, Clone)]
+pub(crate) enum Subtree {
+    /// The targets of its edges, e.g. the children of AST nodes
+    Targets,
+    /// The sources of its edges, e.g. the inputs of plan operators
+    Sources,
+}
+
+/// Embeds an svg rendering of a dot-formatted graph into an html page, along with a model of the
+/// graph's nodes and edges for the page's scripts.
+pub(crate) fn explorer(
+    title: &str,
+    theme: &Theme,
+    graph: &str,
+    svg: &str,
+    parsed: &Parsed,
+    subtree: Subtree,
+) -> String {
+    let graph = dot::parse(graph).expect("dot parse");
+    let text = parsed.text;
+
+    let mut nodes = Map::new();
+    for node in &graph.nodes {
+        let label = match node_label(&node.id, &node.attrs) {
+            Label::Text(lines) => lines,
+            Label::Record { fields, .. } => fields.concat(),
+        };
+        let ast_id = node
+            .attrs
+            .get("id")
+            .and_then(|id| id.strip_prefix("ast-"))
+            .and_then(|id| id.parse().ok());
+        let span = ast_id.and_then(|id| node_span(parsed, NodeId(id)));
+        let source = span.clone().map(|span| &text[span]);
+        // scripts index the query text by utf-16 code units, rather than bytes
+        let span = span.map(|span| {
+            let start = text[..span.start].encode_utf16().count();
+            let end = start + text[span].encode_utf16().count();
+            json!([start, end])
+        });
+        nodes.insert(
+            node.id.clone(),
+            json!({
+                "label": label,
+                "ast_id": ast_id,
+                "span": span,
+                "source": source,
+            }),
+        );
+    }
+    let edges: Vec<_> = graph
+        .edges
+        .iter()
+        .map(|e| json!([graph.nodes[e.from].id, graph.nodes[e.to].id]))
+        .collect();
+    let model = json!({
+        "nodes": Value::Object(nodes),
+        "edges": edges,
+        "subtree": match subtree {
+            Subtree::Targets => "targets",
+            Subtree::Sources => "sources",
+        },
+        "query": text,
+    });
+    // keep the model's text from closing the script element it's embedded in
+    let model = serde_json::to_string(&model)
+        .expect("json print")
+        .replace("</", "<\\/");
+
+    // inline svg can't carry the xml prolog or doctype of a standalone document
+    let svg = svg.find("<svg").map(|idx| &svg[idx..]).unwrap_or(svg);
+
+    fill(
+        EXPLORER_TEMPLATE,
+        &[
+            ("title", &escape_html(title)),
+            ("background", opaque(&theme.background)),
+            ("foreground", &theme.foreground),
+            ("font", &escape_html(&theme.font)),
+            ("model", &model),
+            ("svg", svg),
+        ],
+    )
+}
+
+/// Replaces `{{name}}` placeholders in `template` in a single pass, so that placeholders within
+/// the substituted text, e.g. from the query, are left as they are.
+fn fill(template: &str, values: &[(&str, &str)]) -> String {
+    let mut out = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+        let value = rest.find("}}").and_then(|end| {
+            let (_, value) = values.iter().find(|(name, _)| *name == &rest[2..end])?;
+            Some((end, value))
+        });
+        match value {
+            Some((end, value)) => {
+                out.push_str(value);
+                rest = &rest[end + 2..];
+            }
+            None => {
+                out.push_str("{{");
+                rest = &rest[2..];
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Drops the alpha channel of a `#rrggbbaa` color, since the page itself can't be transparent.
+fn opaque(color: &str) -> &str {
+    match color {
+        c if c.len() == 9 && c.starts_with('#') => &c[..7],
+        c => c,
+    }
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
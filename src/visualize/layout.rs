@@ -0,0 +1,1140 @@
+//! A pure-Rust layered layout of dot graphs, rendered to SVG.
+//!
+//! This follows the usual Sugiyama-style phases: nodes are assigned to layers by longest path,
+//! long edges are split by virtual nodes, layers are ordered by repeated barycenter sweeps (keeping
+//! clusters contiguous), and nodes are then placed horizontally near the mean of their neighbors.
+//! Only top-to-bottom layouts are supported.
+
+use crate::error::CLIError;
+use crate::visualize::dot::{self, attr, label_lines, node_label, shape, Attrs, DotGraph, Label};
+use std::cmp::Ordering;
+use std::fmt::Write;
+
+const NODE_SEP: f64 = 18.0;
+const RANK_SEP: f64 = 42.0;
+const CLUSTER_PAD: f64 = 10.0;
+const MARGIN: f64 = 8.0;
+const ARROW_LEN: f64 = 9.0;
+const SWEEPS: usize = 12;
+
+/// Lays out a dot-formatted graph and renders it to an SVG document.
+pub(crate) fn to_svg(graph: &str) -> Result<String, CLIError> {
+    let graph = dot::parse(graph).map_err(|msg| CLIError::RenderError {
+        msg: format!("invalid dot: {msg}"),
+    })?;
+    let layout = Layout::new(&graph);
+    layout.render(&graph).map_err(|_| CLIError::RenderError {
+        msg: "could not write the svg".to_string(),
+    })
+}
+
+struct LayoutNode {
+    /// The dot node this was created for, or `None` for a virtual node routing a long edge.
+    node: Option<usize>,
+    layer: usize,
+    width: f64,
+    height: f64,
+    x: f64,
+    y: f64,
+    /// The clusters containing this node, outermost first.
+    clusters: Vec<usize>,
+    up: Vec<usize>,
+    down: Vec<usize>,
+}
+
+struct Route {
+    edge: usize,
+    /// Layout nodes along the edge, from its source to its target.
+    points: Vec<usize>,
+}
+
+/// The font of a graph's labels, from its `fontname` and `fontsize` attributes.
+struct Font {
+    family: String,
+    size: f64,
+}
+
+impl Font {
+    fn new(attrs: &Attrs) -> Self {
+        let family = match attr(attrs, "fontname", "Times-Roman") {
+            // graphviz's default, named for postscript
+            "Times-Roman" => "Times",
+            name => name,
+        };
+        let size = attr(attrs, "fontsize", "14").parse().unwrap_or(14.0);
+        Font {
+            family: family.to_string(),
+            size,
+        }
+    }
+
+    fn char_width(&self) -> f64 {
+        self.size * 0.6
+    }
+
+    fn line_height(&self) -> f64 {
+        self.size * 1.25
+    }
+
+    fn text_size(&self, lines: &[String]) -> (f64, f64) {
+        let chars = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0);
+        (
+            chars as f64 * self.char_width(),
+            lines.len() as f64 * self.line_height(),
+        )
+    }
+
+    /// Writes lines of text centered on `(x, y)`.
+    fn text(
+        &self,
+        out: &mut String,
+        x: f64,
+        y: f64,
+        lines: &[String],
+        color: &str,
+    ) -> std::fmt::Result {
+        let line_height = self.line_height();
+        let top = y - line_height * lines.len() as f64 / 2.0;
+        for (idx, line) in lines.iter().enumerate() {
+            if line.is_empty() {
+                continue;
+            }
+            // approximate the baseline for a line vertically centered in its box
+            let baseline = top + line_height * (idx as f64 + 0.5) + self.size * 0.35;
+            writeln!(
+                out,
+                r#"<text x="{x:.2}" y="{baseline:.2}" text-anchor="middle" font-family="{},serif" font-size="{}"{}>{}</text>"#,
+                escape_xml(&self.family),
+                self.size,
+                paint("fill", color),
+                escape_xml(line)
+            )?;
+        }
+        Ok(())
+    }
+}
+
+struct Layout {
+    font: Font,
+    nodes: Vec<LayoutNode>,
+    labels: Vec<Label>,
+    layers: Vec<Vec<usize>>,
+    routes: Vec<Route>,
+    width: f64,
+    height: f64,
+}
+
+impl Layout {
+    fn new(graph: &DotGraph) -> Self {
+        let font = Font::new(&graph.attrs);
+        let labels: Vec<_> = graph
+            .nodes
+            .iter()
+            .map(|n| node_label(&n.id, &n.attrs))
+            .collect();
+        let ranks = rank(graph);
+
+        let mut nodes: Vec<_> = graph
+            .nodes
+            .iter()
+            .enumerate()
+            .map(|(idx, n)| {
+                let (width, height) = node_size(shape(&n.attrs), &labels[idx], &font);
+                LayoutNode {
+                    node: Some(idx),
+                    layer: ranks[idx],
+                    width,
+                    height,
+                    x: 0.0,
+                    y: 0.0,
+                    clusters: cluster_path(graph, n.cluster),
+                    up: vec![],
+                    down: vec![],
+                }
+            })
+            .collect();
+
+        // Split edges spanning several layers with virtual nodes, so each segment joins adjacent
+        // layers; edges pointing up the layers are routed downwards and reversed when drawn.
+        let mut routes = vec![];
+        for (idx, edge) in graph.edges.iter().enumerate() {
+            if edge.from == edge.to {
+                continue;
+            }
+            let reversed = ranks[edge.from] > ranks[edge.to];
+            let (top, bottom) = if reversed {
+                (edge.to, edge.from)
+            } else {
+                (edge.from, edge.to)
+            };
+            let clusters = common_prefix(&nodes[top].clusters, &nodes[bottom].clusters).to_vec();
+            let mut points = vec![top];
+            for layer in ranks[top] + 1..ranks[bottom] {
+                nodes.push(LayoutNode {
+                    node: None,
+                    layer,
+                    width: 0.0,
+                    height: 0.0,
+                    x: 0.0,
+                    y: 0.0,
+                    clusters: clusters.clone(),
+                    up: vec![],
+                    down: vec![],
+                });
+                points.push(nodes.len() - 1);
+            }
+            points.push(bottom);
+            for pair in points.windows(2) {
+                nodes[pair[0]].down.push(pair[1]);
+                nodes[pair[1]].up.push(pair[0]);
+            }
+            if reversed {
+                points.reverse();
+            }
+            routes.push(Route { edge: idx, points });
+        }
+
+        let mut layout = Layout {
+            font,
+            layers: initial_order(&nodes),
+            nodes,
+            labels,
+            routes,
+            width: 0.0,
+            height: 0.0,
+        };
+        layout.order();
+        layout.place();
+        layout
+    }
+
+    /// Reorders each layer to reduce edge crossings, keeping the best ordering seen.
+    fn order(&mut self) {
+        // the initial order can split a cluster, and may already have the fewest crossings
+        for layer in 0..self.layers.len() {
+            self.group_clusters(layer);
+        }
+        let mut best = self.layers.clone();
+        let mut best_crossings = self.crossings();
+        for sweep in 0..SWEEPS {
+            let down = sweep % 2 == 0;
+            let layers: Vec<_> = if down {
+                (1..self.layers.len()).collect()
+            } else {
+                (0..self.layers.len().saturating_sub(1)).rev().collect()
+            };
+            for layer in layers {
+                self.sort_layer(layer, down);
+            }
+            let crossings = self.crossings();
+            if crossings < best_crossings {
+                best = self.layers.clone();
+                best_crossings = crossings;
+            }
+        }
+        self.layers = best;
+    }
+
+    /// Moves the members of each cluster in a layer next to its first member, keeping nodes
+    /// otherwise in order.
+    fn group_clusters(&mut self, layer: usize) {
+        let order = &self.layers[layer];
+        let first = |c: usize| {
+            order
+                .iter()
+                .position(|n| self.nodes[*n].clusters.contains(&c))
+                .unwrap_or(0)
+        };
+        let mut keyed: Vec<_> = order
+            .iter()
+            .enumerate()
+            .map(|(pos, n)| {
+                let mut key: Vec<_> = self.nodes[*n].clusters.iter().map(|c| first(*c)).collect();
+                key.push(pos);
+                (key, *n)
+            })
+            .collect();
+        keyed.sort();
+        self.layers[layer] = keyed.into_iter().map(|(_, n)| n).collect();
+    }
+
+    fn positions(&self) -> Vec<usize> {
+        let mut pos = vec![0; self.nodes.len()];
+        for layer in &self.layers {
+            for (idx, n) in layer.iter().enumerate() {
+                pos[*n] = idx;
+            }
+        }
+        pos
+    }
+
+    /// Sorts a layer by the barycenter of each node's neighbors in the previous layer of the sweep.
+    /// Nodes are grouped by cluster, with each cluster ordered by the barycenter of its members.
+    fn sort_layer(&mut self, layer: usize, down: bool) {
+        let pos = self.positions();
+        let bary = |n: usize| {
+            let node = &self.nodes[n];
+            let neighbors = if down { &node.up } else { &node.down };
+            if neighbors.is_empty() {
+                pos[n] as f64
+            } else {
+                neighbors.iter().map(|m| pos[*m] as f64).sum::<f64>() / neighbors.len() as f64
+            }
+        };
+
+        let mut cluster_bary: Vec<(f64, usize)> = vec![];
+        let mut cluster_sum = std::collections::HashMap::new();
+        for n in &self.layers[layer] {
+            for c in &self.nodes[*n].clusters {
+                let entry = cluster_sum.entry(*c).or_insert((0.0, 0));
+                entry.0 += bary(*n);
+                entry.1 += 1;
+            }
+        }
+        for (c, (sum, count)) in cluster_sum {
+            if cluster_bary.len() <= c {
+                cluster_bary.resize(c + 1, (0.0, 0));
+            }
+            cluster_bary[c] = (sum / count as f64, count);
+        }
+
+        let key = |n: usize| -> Vec<(f64, usize, usize)> {
+            let mut key: Vec<_> = self.nodes[n]
+                .clusters
+                .iter()
+                .map(|c| (cluster_bary[*c].0, 1, *c))
+                .collect();
+            key.push((bary(n), 0, pos[n]));
+            key
+        };
+        let mut keyed: Vec<_> = self.layers[layer].iter().map(|n| (key(*n), *n)).collect();
+        keyed.sort_by(|(a, _), (b, _)| {
+            a.iter()
+                .zip(b)
+                .map(|(a, b)| {
+                    a.0.partial_cmp(&b.0)
+                        .unwrap_or(Ordering::Equal)
+                        .then(a.1.cmp(&b.1))
+                        .then(a.2.cmp(&b.2))
+                })
+                .find(|o| *o != Ordering::Equal)
+                .unwrap_or(Ordering::Equal)
+        });
+        self.layers[layer] = keyed.into_iter().map(|(_, n)| n).collect();
+    }
+
+    fn crossings(&self) -> usize {
+        let pos = self.positions();
+        let mut total = 0;
+        for layer in &self.layers {
+            let segments: Vec<_> = layer
+                .iter()
+                .flat_map(|u| self.nodes[*u].down.iter().map(|v| (pos[*u], pos[*v])))
+                .collect();
+            for (i, (a1, b1)) in segments.iter().enumerate() {
+                for (a2, b2) in &segments[i + 1..] {
+                    if (a1 < a2 && b1 > b2) || (a1 > a2 && b1 < b2) {
+                        total += 1;
+                    }
+                }
+            }
+        }
+        total
+    }
+
+    /// The minimum distance between the centers of adjacent nodes `a` and `b` in a layer.
+    fn separation(&self, a: usize, b: usize) -> f64 {
+        let (a, b) = (&self.nodes[a], &self.nodes[b]);
+        let shared = common_prefix(&a.clusters, &b.clusters).len();
+        let borders = a.clusters.len() + b.clusters.len() - 2 * shared;
+        (a.width + b.width) / 2.0 + NODE_SEP + CLUSTER_PAD * borders as f64
+    }
+
+    /// Places each node of a layer as close to its desired position as separation allows.
+    fn place_layer(&mut self, layer: usize, desired: &[f64]) {
+        let order = &self.layers[layer];
+        let n = order.len();
+        let mut left = desired.to_vec();
+        for i in 1..n {
+            left[i] = left[i].max(left[i - 1] + self.separation(order[i - 1], order[i]));
+        }
+        let mut right = desired.to_vec();
+        for i in (0..n.saturating_sub(1)).rev() {
+            right[i] = right[i].min(right[i + 1] - self.separation(order[i], order[i + 1]));
+        }
+        for i in 0..n {
+            self.nodes[order[i]].x = (left[i] + right[i]) / 2.0;
+        }
+    }
+
+    fn place(&mut self) {
+        for layer in 0..self.layers.len() {
+            let order = self.layers[layer].clone();
+            let mut x = 0.0;
+            for (i, n) in order.iter().enumerate() {
+                if i > 0 {
+                    x += self.separation(order[i - 1], *n);
+                }
+                self.nodes[*n].x = x;
+            }
+        }
+
+        // Alternately pull nodes towards their neighbors below and above, finishing with parents
+        // centered over their children.
+        for sweep in 0..SWEEPS {
+            let down = sweep % 2 == 1;
+            let layers: Vec<_> = if down {
+                (1..self.layers.len()).collect()
+            } else {
+                (0..self.layers.len().saturating_sub(1)).rev().collect()
+            };
+            for layer in layers {
+                let desired: Vec<_> = self.layers[layer]
+                    .iter()
+                    .map(|n| {
+                        let node = &self.nodes[*n];
+                        let neighbors = if down { &node.up } else { &node.down };
+                        if neighbors.is_empty() {
+                            node.x
+                        } else {
+                            neighbors.iter().map(|m| self.nodes[*m].x).sum::<f64>()
+                                / neighbors.len() as f64
+                        }
+                    })
+                    .collect();
+                self.place_layer(layer, &desired);
+            }
+        }
+
+        self.separate_clusters();
+
+        let depth = |n: &LayoutNode| n.clusters.len() as f64;
+        let min_x = self
+            .nodes
+            .iter()
+            .map(|n| n.x - n.width / 2.0 - CLUSTER_PAD * depth(n))
+            .fold(f64::INFINITY, f64::min);
+        for n in &mut self.nodes {
+            n.x += MARGIN - min_x;
+        }
+
+        let layer_depth = |layer: &Vec<usize>, nodes: &Vec<LayoutNode>| {
+            layer.iter().map(|n| depth(&nodes[*n])).fold(0.0, f64::max)
+        };
+        let mut y = MARGIN;
+        for (idx, layer) in self.layers.iter().enumerate() {
+            y += layer_depth(layer, &self.nodes) * (CLUSTER_PAD + self.font.line_height());
+            let height = layer
+                .iter()
+                .map(|n| self.nodes[*n].height)
+                .fold(0.0, f64::max);
+            for n in layer {
+                self.nodes[*n].y = y + height / 2.0;
+            }
+            y += height + layer_depth(layer, &self.nodes) * CLUSTER_PAD;
+            if idx + 1 < self.layers.len() {
+                y += RANK_SEP;
+            }
+        }
+
+        self.height = y + MARGIN;
+        self.width = self
+            .nodes
+            .iter()
+            .map(|n| n.x + n.width / 2.0 + CLUSTER_PAD * depth(n))
+            .fold(0.0, f64::max)
+            + MARGIN;
+    }
+
+    /// Spreads apart sibling clusters whose boxes overlap, since nodes are only separated from
+    /// their neighbors within a layer.
+    fn separate_clusters(&mut self) {
+        let count = self.nodes.iter().flat_map(|n| n.clusters.iter()).max();
+        let Some(count) = count.map(|c| c + 1) else {
+            return;
+        };
+        // the parent of each cluster, as seen from its members
+        let mut parents = vec![None; count];
+        for n in &self.nodes {
+            for (depth, c) in n.clusters.iter().enumerate() {
+                parents[*c] = depth.checked_sub(1).map(|d| n.clusters[d]);
+            }
+        }
+
+        // Handle the innermost clusters first, so their parents' boxes include any spreading.
+        let mut by_depth: Vec<_> = (0..count).collect();
+        let depth_of = |c: usize| {
+            let mut depth = 0;
+            let mut parent = parents[c];
+            while let Some(p) = parent {
+                depth += 1;
+                parent = parents[p];
+            }
+            depth
+        };
+        by_depth.sort_by_key(|c| std::cmp::Reverse(depth_of(*c)));
+        let mut handled = std::collections::HashSet::new();
+        for c in by_depth {
+            if !handled.insert(parents[c]) {
+                continue;
+            }
+            // bounded, as clusters ordered inconsistently across layers can't always be separated
+            for _ in 0..count * count {
+                let boxes = self.node_boxes(count);
+                let mut siblings: Vec<_> = (0..count)
+                    .filter(|s| parents[*s] == parents[c])
+                    .filter_map(|s| boxes[s].map(|b| (s, b)))
+                    .collect();
+                siblings.sort_by(|(_, a), (_, b)| a.0.partial_cmp(&b.0).unwrap_or(Ordering::Equal));
+                let overlap = siblings.iter().enumerate().find_map(|(i, (a, ab))| {
+                    siblings[i + 1..].iter().find_map(|(_, bb)| {
+                        let vertical = ab.1 <= bb.3 && bb.1 <= ab.3;
+                        let shift = ab.2 + NODE_SEP - bb.0;
+                        (vertical && shift > 0.5).then_some((*a, bb.0, shift))
+                    })
+                });
+                let Some((left, edge, shift)) = overlap else {
+                    break;
+                };
+                self.shift_right_of(left, edge, shift);
+            }
+        }
+    }
+
+    /// Shifts right all nodes that are right of `edge`, or of cluster `left`'s members in their layer.
+    fn shift_right_of(&mut self, left: usize, edge: f64, shift: f64) {
+        for layer in &self.layers {
+            let last = layer
+                .iter()
+                .rposition(|n| self.nodes[*n].clusters.contains(&left));
+            for (idx, n) in layer.iter().enumerate() {
+                let node = &mut self.nodes[*n];
+                let right = match last {
+                    Some(last) => idx > last,
+                    None => node.x >= edge,
+                };
+                if right {
+                    node.x += shift;
+                }
+            }
+        }
+    }
+
+    /// The bounding boxes of each cluster's members, as `(left, top, right, bottom)`.
+    fn node_boxes(&self, count: usize) -> Vec<Option<(f64, f64, f64, f64)>> {
+        let mut boxes: Vec<Option<(f64, f64, f64, f64)>> = vec![None; count];
+        for n in &self.nodes {
+            for (depth, c) in n.clusters.iter().enumerate() {
+                let pad = CLUSTER_PAD * (n.clusters.len() - depth) as f64;
+                let layer = n.layer as f64;
+                let rect = (
+                    n.x - n.width / 2.0 - pad,
+                    layer,
+                    n.x + n.width / 2.0 + pad,
+                    layer,
+                );
+                boxes[*c] = Some(match boxes[*c] {
+                    None => rect,
+                    Some(b) => (
+                        b.0.min(rect.0),
+                        b.1.min(rect.1),
+                        b.2.max(rect.2),
+                        b.3.max(rect.3),
+                    ),
+                });
+            }
+        }
+        boxes
+    }
+
+    /// The bounding boxes of each cluster, as `(left, top, right, bottom)`.
+    fn cluster_boxes(&self, graph: &DotGraph) -> Vec<Option<(f64, f64, f64, f64)>> {
+        let mut boxes: Vec<Option<(f64, f64, f64, f64)>> = vec![None; graph.clusters.len()];
+        for n in &self.nodes {
+            for (depth, c) in n.clusters.iter().enumerate() {
+                let levels = (n.clusters.len() - depth) as f64;
+                let pad = CLUSTER_PAD * levels;
+                let label = self.font.line_height() * levels;
+                let rect = (
+                    n.x - n.width / 2.0 - pad,
+                    n.y - n.height / 2.0 - pad - label,
+                    n.x + n.width / 2.0 + pad,
+                    n.y + n.height / 2.0 + pad,
+                );
+                boxes[*c] = Some(match boxes[*c] {
+                    None => rect,
+                    Some(b) => (
+                        b.0.min(rect.0),
+                        b.1.min(rect.1),
+                        b.2.max(rect.2),
+                        b.3.max(rect.3),
+                    ),
+                });
+            }
+        }
+        boxes
+    }
+
+    fn render(&self, graph: &DotGraph) -> Result<String, std::fmt::Error> {
+        let mut out = String::new();
+        let (w, h) = (self.width, self.height);
+        writeln!(
+            out,
+            r#"<?xml version="1.0" encoding="UTF-8" standalone="no"?>"#
+        )?;
+        writeln!(
+            out,
+            r#"<svg xmlns="http://www.w3.org/2000/svg" width="{w:.0}pt" height="{h:.0}pt" viewBox="0 0 {w:.2} {h:.2}">"#
+        )?;
+        if let Some(bg) = graph.attrs.get("bgcolor") {
+            writeln!(
+                out,
+                r#"<rect width="{w:.2}" height="{h:.2}"{}/>"#,
+                paint("fill", bg)
+            )?;
+        }
+
+        let graph_pen = attr(&graph.attrs, "pencolor", "black");
+        let graph_font = attr(&graph.attrs, "fontcolor", "black");
+        for (idx, bbox) in self.cluster_boxes(graph).iter().enumerate() {
+            let Some((l, t, r, b)) = bbox else { continue };
+            let attrs = &graph.clusters[idx].attrs;
+            let pen = attrs
+                .get("pencolor")
+                .or_else(|| attrs.get("color"))
+                .map(String::as_str)
+                .unwrap_or(graph_pen);
+            writeln!(
+                out,
+                r#"<rect x="{l:.2}" y="{t:.2}" width="{:.2}" height="{:.2}" fill="none"{}/>"#,
+                r - l,
+                b - t,
+                paint("stroke", pen)
+            )?;
+            if let Some(label) = attrs.get("label") {
+                let font = attr(attrs, "fontcolor", graph_font);
+                let lines = label_lines(label, "");
+                let y = t + CLUSTER_PAD / 2.0 + self.font.line_height() * lines.len() as f64 / 2.0;
+                self.font.text(&mut out, (l + r) / 2.0, y, &lines, font)?;
+            }
+        }
+
+        // like graphviz, nodes and edges are grouped by the dot ids they were drawn for
+        for route in &self.routes {
+            let edge = &graph.edges[route.edge];
+            writeln!(
+                out,
+                r#"<g class="edge" data-from="{}" data-to="{}">"#,
+                escape_xml(&graph.nodes[edge.from].id),
+                escape_xml(&graph.nodes[edge.to].id)
+            )?;
+            self.render_edge(&mut out, &edge.attrs, route)?;
+            writeln!(out, "</g>")?;
+        }
+
+        for n in &self.nodes {
+            if let Some(idx) = n.node {
+                let attrs = &graph.nodes[idx].attrs;
+                write!(
+                    out,
+                    r#"<g class="node" data-name="{}""#,
+                    escape_xml(&graph.nodes[idx].id)
+                )?;
+                if let Some(id) = attrs.get("id") {
+                    write!(out, r#" id="{}""#, escape_xml(id))?;
+                }
+                writeln!(out, ">")?;
+                if let Some(tooltip) = attrs.get("tooltip") {
+                    let tooltip = label_lines(tooltip, &graph.nodes[idx].id).join("\n");
+                    writeln!(out, "<title>{}</title>", escape_xml(&tooltip))?;
+                }
+                render_node(&mut out, n, attrs, &self.labels[idx], &self.font)?;
+                writeln!(out, "</g>")?;
+            }
+        }
+
+        writeln!(out, "</svg>")?;
+        Ok(out)
+    }
+
+    fn render_edge(&self, out: &mut String, attrs: &Attrs, route: &Route) -> std::fmt::Result {
+        let color = attr(attrs, "color", "black");
+        let first = &self.nodes[route.points[0]];
+        let last = &self.nodes[route.points[route.points.len() - 1]];
+        let dir = if first.y < last.y { 1.0 } else { -1.0 };
+
+        let mut points: Vec<(f64, f64)> = route
+            .points
+            .iter()
+            .map(|p| (self.nodes[*p].x, self.nodes[*p].y))
+            .collect();
+        let n = points.len();
+        points[0].1 += dir * first.height / 2.0;
+        points[n - 1].1 -= dir * last.height / 2.0;
+        let tip = points[n - 1];
+        points[n - 1].1 -= dir * ARROW_LEN;
+
+        let mut path = format!("M{:.2},{:.2}", points[0].0, points[0].1);
+        for pair in points.windows(2) {
+            let ((x1, y1), (x2, y2)) = (pair[0], pair[1]);
+            let mid = (y1 + y2) / 2.0;
+            write!(path, " C{x1:.2},{mid:.2} {x2:.2},{mid:.2} {x2:.2},{y2:.2}")?;
+        }
+        writeln!(
+            out,
+            r#"<path d="{path}" fill="none"{}/>"#,
+            paint("stroke", color)
+        )?;
+        writeln!(
+            out,
+            r#"<polygon points="{:.2},{:.2} {:.2},{:.2} {:.2},{:.2}"{}{}/>"#,
+            tip.0,
+            tip.1,
+            tip.0 - ARROW_LEN / 2.5,
+            tip.1 - dir * ARROW_LEN,
+            tip.0 + ARROW_LEN / 2.5,
+            tip.1 - dir * ARROW_LEN,
+            paint("fill", color),
+            paint("stroke", color)
+        )?;
+
+        if let Some(label) = attrs.get("label").filter(|l| !l.is_empty()) {
+            let lines = label_lines(label, "");
+            let ((x1, y1), (x2, y2)) = (points[0], points[1]);
+            let width = lines.iter().map(|l| l.chars().count()).max().unwrap_or(0) as f64;
+            // place the label on the outside of the edge's bend, away from any sibling edges;
+            // those diverge from the source, or converge on the target
+            let (from, to) = (&self.nodes[route.points[0]], &self.nodes[route.points[1]]);
+            let (fan_out, fan_in) = if dir > 0.0 {
+                (from.down.len(), to.up.len())
+            } else {
+                (from.up.len(), to.down.len())
+            };
+            let toward = if x2 < x1 { -1.0 } else { 1.0 };
+            let side = if fan_in > 1 && fan_out <= 1 {
+                -toward
+            } else {
+                toward
+            };
+            let x = (x1 + x2) / 2.0 + side * (4.0 + width * self.font.char_width() / 2.0);
+            self.font.text(
+                out,
+                x,
+                (y1 + y2) / 2.0,
+                &lines,
+                attr(attrs, "fontcolor", "black"),
+            )?;
+        }
+        Ok(())
+    }
+}
+
+/// Assigns each node a layer by longest path, after reversing edges that close a cycle.
+fn rank(graph: &DotGraph) -> Vec<usize> {
+    let n = graph.nodes.len();
+    let mut out: Vec<Vec<usize>> = vec![vec![]; n];
+    for edge in &graph.edges {
+        if edge.from != edge.to {
+            out[edge.from].push(edge.to);
+        }
+    }
+
+    // Depth-first search for back edges; 0 = unvisited, 1 = on the stack, 2 = done.
+    let mut state = vec![0u8; n];
+    let mut edges = vec![];
+    for root in 0..n {
+        if state[root] != 0 {
+            continue;
+        }
+        let mut stack = vec![(root, 0)];
+        state[root] = 1;
+        while let Some((u, i)) = stack.pop() {
+            if let Some(&v) = out[u].get(i) {
+                stack.push((u, i + 1));
+                match state[v] {
+                    0 => {
+                        edges.push((u, v));
+                        state[v] = 1;
+                        stack.push((v, 0));
+                    }
+                    1 => edges.push((v, u)),
+                    _ => edges.push((u, v)),
+                }
+            } else {
+                state[u] = 2;
+            }
+        }
+    }
+
+    let mut preds: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut succs: Vec<Vec<usize>> = vec![vec![]; n];
+    let mut in_degree = vec![0; n];
+    for (u, v) in &edges {
+        preds[*v].push(*u);
+        succs[*u].push(*v);
+        in_degree[*v] += 1;
+    }
+    let mut ranks = vec![0; n];
+    let mut topo = vec![];
+    let mut ready: Vec<_> = (0..n).filter(|v| in_degree[*v] == 0).rev().collect();
+    while let Some(u) = ready.pop() {
+        topo.push(u);
+        for v in &succs[u] {
+            ranks[*v] = ranks[*v].max(ranks[u] + 1);
+            in_degree[*v] -= 1;
+            if in_degree[*v] == 0 {
+                ready.push(*v);
+            }
+        }
+    }
+
+    // Pull sources down to sit just above their nearest successor.
+    for u in topo.into_iter().rev() {
+        if preds[u].is_empty() && !succs[u].is_empty() {
+            ranks[u] = succs[u].iter().map(|v| ranks[*v]).min().unwrap_or(1) - 1;
+        }
+    }
+    ranks
+}
+
+/// Orders each layer by a depth-first traversal, so related nodes start out close together.
+fn initial_order(nodes: &[LayoutNode]) -> Vec<Vec<usize>> {
+    let depth = nodes.iter().map(|n| n.layer + 1).max().unwrap_or(0);
+    let mut layers = vec![vec![]; depth];
+    let mut seen = vec![false; nodes.len()];
+    for root in 0..nodes.len() {
+        if seen[root] || !nodes[root].up.is_empty() {
+            continue;
+        }
+        let mut stack = vec![root];
+        while let Some(n) = stack.pop() {
+            if std::mem::replace(&mut seen[n], true) {
+                continue;
+            }
+            layers[nodes[n].layer].push(n);
+            stack.extend(nodes[n].down.iter().rev());
+        }
+    }
+    for n in 0..nodes.len() {
+        if !seen[n] {
+            layers[nodes[n].layer].push(n);
+        }
+    }
+    layers
+}
+
+fn cluster_path(graph: &DotGraph, mut cluster: Option<usize>) -> Vec<usize> {
+    let mut path = vec![];
+    while let Some(c) = cluster {
+        path.push(c);
+        cluster = graph.clusters[c].parent;
+    }
+    path.reverse();
+    path
+}
+
+fn common_prefix<'a>(a: &'a [usize], b: &[usize]) -> &'a [usize] {
+    let len = a.iter().zip(b).take_while(|(a, b)| a == b).count();
+    &a[..len]
+}
+
+fn node_size(shape: &str, label: &Label, font: &Font) -> (f64, f64) {
+    let (w, h) = match label {
+        Label::Text(lines) => {
+            let (w, h) = font.text_size(lines);
+            match shape {
+                "box" | "rect" | "rectangle" | "square" => (w + 16.0, h + 12.0),
+                "diamond" | "Mdiamond" => (w * 2.0 + 8.0, h * 2.0 + 8.0),
+                "parallelogram" => (w + h + 20.0, h + 12.0),
+                "circle" => {
+                    let d = w.max(h) * 1.42 + 8.0;
+                    (d, d)
+                }
+                "plaintext" | "plain" | "none" => return (w + 8.0, h + 8.0),
+                "point" => return (8.0, 8.0),
+                _ => (w * 1.42 + 8.0, h * 1.42 + 8.0),
+            }
+        }
+        Label::Record { vertical, fields } => {
+            let sizes = fields.iter().map(|f| font.text_size(f));
+            if *vertical {
+                sizes.fold((0.0, 0.0), |(w, h), (fw, fh)| {
+                    (f64::max(w, fw + 16.0), h + fh + 8.0)
+                })
+            } else {
+                sizes.fold((0.0, 0.0), |(w, h), (fw, fh)| {
+                    (w + fw + 16.0, f64::max(h, fh + 8.0))
+                })
+            }
+        }
+    };
+    (w.max(54.0), h.max(36.0))
+}
+
+fn render_node(
+    out: &mut String,
+    n: &LayoutNode,
+    attrs: &Attrs,
+    label: &Label,
+    font: &Font,
+) -> std::fmt::Result {
+    let color = attr(attrs, "color", "black");
+    let font_color = attr(attrs, "fontcolor", "black");
+    let fill = if attr(attrs, "style", "").contains("filled") {
+        paint("fill", attr(attrs, "fillcolor", color))
+    } else {
+        " fill=\"none\"".to_string()
+    };
+    let dash = if attr(attrs, "style", "").contains("dashed") {
+        r#" stroke-dasharray="5,2""#
+    } else {
+        ""
+    };
+    let width = match attrs.get("penwidth").and_then(|w| w.parse::<f32>().ok()) {
+        Some(width) => format!(r#" stroke-width="{width}""#),
+        None => String::new(),
+    };
+    let style = format!("{fill}{}{width}{dash}", paint("stroke", color));
+    let (x, y, w, h) = (n.x, n.y, n.width, n.height);
+    let (l, t) = (x - w / 2.0, y - h / 2.0);
+
+    match label {
+        Label::Text(lines) => {
+            match shape(attrs) {
+                "box" | "rect" | "rectangle" | "square" => writeln!(
+                    out,
+                    r#"<rect x="{l:.2}" y="{t:.2}" width="{w:.2}" height="{h:.2}"{style}/>"#
+                )?,
+                "diamond" | "Mdiamond" => writeln!(
+                    out,
+                    r#"<polygon points="{x:.2},{t:.2} {:.2},{y:.2} {x:.2},{:.2} {l:.2},{y:.2}"{style}/>"#,
+                    l + w,
+                    t + h
+                )?,
+                "parallelogram" => {
+                    let skew = h * 0.3;
+                    writeln!(
+                        out,
+                        r#"<polygon points="{:.2},{t:.2} {:.2},{t:.2} {:.2},{:.2} {l:.2},{:.2}"{style}/>"#,
+                        l + skew,
+                        l + w,
+                        l + w - skew,
+                        t + h,
+                        t + h
+                    )?
+                }
+                "circle" | "point" => writeln!(
+                    out,
+                    r#"<circle cx="{x:.2}" cy="{y:.2}" r="{:.2}"{style}/>"#,
+                    w / 2.0
+                )?,
+                "plaintext" | "plain" | "none" => {}
+                _ => writeln!(
+                    out,
+                    r#"<ellipse cx="{x:.2}" cy="{y:.2}" rx="{:.2}" ry="{:.2}"{style}/>"#,
+                    w / 2.0,
+                    h / 2.0
+                )?,
+            }
+            if shape(attrs) != "point" {
+                font.text(out, x, y, lines, font_color)?;
+            }
+        }
+        Label::Record { vertical, fields } => {
+            let rx = if shape(attrs) == "Mrecord" { 8.0 } else { 0.0 };
+            writeln!(
+                out,
+                r#"<rect x="{l:.2}" y="{t:.2}" width="{w:.2}" height="{h:.2}" rx="{rx}"{style}/>"#
+            )?;
+            let sizes: Vec<_> = fields.iter().map(|f| font.text_size(f)).collect();
+            let mut offset = 0.0;
+            for (idx, (field, (fw, fh))) in fields.iter().zip(sizes).enumerate() {
+                if *vertical {
+                    let fh = if idx == fields.len() - 1 {
+                        h - offset
+                    } else {
+                        fh + 8.0
+                    };
+                    if idx > 0 {
+                        writeln!(
+                            out,
+                            r#"<line x1="{l:.2}" y1="{:.2}" x2="{:.2}" y2="{:.2}"{}/>"#,
+                            t + offset,
+                            l + w,
+                            t + offset,
+                            paint("stroke", color)
+                        )?;
+                    }
+                    font.text(out, x, t + offset + fh / 2.0, field, font_color)?;
+                    offset += fh;
+                } else {
+                    let fw = if idx == fields.len() - 1 {
+                        w - offset
+                    } else {
+                        fw + 16.0
+                    };
+                    if idx > 0 {
+                        writeln!(
+                            out,
+                            r#"<line x1="{:.2}" y1="{t:.2}" x2="{:.2}" y2="{:.2}"{}/>"#,
+                            l + offset,
+                            l + offset,
+                            t + h,
+                            paint("stroke", color)
+                        )?;
+                    }
+                    font.text(out, l + offset + fw / 2.0, y, field, font_color)?;
+                    offset += fw;
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Formats a dot color as an svg paint attribute, splitting any alpha channel into an opacity.
+fn paint(attr: &str, color: &str) -> String {
+    match color {
+        "transparent" | "none" | "invis" => format!(r#" {attr}="none""#),
+        c if c.len() == 9 && c.starts_with('#') => {
+            let alpha = u8::from_str_radix(&c[7..], 16).unwrap_or(255);
+            format!(
+                r#" {attr}="{}" {attr}-opacity="{:.3}""#,
+                &c[..7],
+                alpha as f64 / 255.0
+            )
+        }
+        c => format!(r#" {attr}="{}""#, escape_xml(c)),
+    }
+}
+
+fn escape_xml(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lay_out(src: &str) -> (DotGraph, Layout) {
+        let graph = dot::parse(src).unwrap();
+        let layout = Layout::new(&graph);
+        (graph, layout)
+    }
+
+    /// The layout node drawn for the dot node `id`.
+    fn node<'a>(graph: &DotGraph, layout: &'a Layout, id: &str) -> &'a LayoutNode {
+        let idx = graph.nodes.iter().position(|n| n.id == id).unwrap();
+        layout.nodes.iter().find(|n| n.node == Some(idx)).unwrap()
+    }
+
+    /// The x coordinate of the text `label` in an svg.
+    fn text_x(svg: &str, label: &str) -> f64 {
+        let line = svg
+            .lines()
+            .find(|l| l.ends_with(&format!(">{label}</text>")))
+            .unwrap();
+        let x = line.split_once(" x=\"").unwrap().1;
+        x[..x.find('"').unwrap()].parse().unwrap()
+    }
+
+    #[test]
+    fn layers_by_longest_path() {
+        let (graph, layout) = lay_out("digraph { a -> b -> c; a -> c; d -> c }");
+        let ranks: Vec<_> = ["a", "b", "c", "d"]
+            .iter()
+            .map(|id| node(&graph, &layout, id).layer)
+            .collect();
+        // `d` is pulled down to sit just above `c`
+        assert_eq!(ranks, [0, 1, 2, 1]);
+
+        // `a -> c` spans two layers, so is routed through a virtual node
+        let virtual_nodes: Vec<_> = layout.nodes.iter().filter(|n| n.node.is_none()).collect();
+        assert_eq!(virtual_nodes.len(), 1);
+        assert_eq!(virtual_nodes[0].layer, 1);
+        let route = layout.routes.iter().find(|r| r.edge == 2).unwrap();
+        assert_eq!(route.points.len(), 3);
+    }
+
+    #[test]
+    fn cycles_are_layered() {
+        let (graph, layout) = lay_out("digraph { a -> b -> c -> a }");
+        let ranks: Vec<_> = ["a", "b", "c"]
+            .iter()
+            .map(|id| node(&graph, &layout, id).layer)
+            .collect();
+        assert_eq!(ranks, [0, 1, 2]);
+
+        // the edge closing the cycle points up the layers, and is drawn from its source
+        let route = layout.routes.iter().find(|r| r.edge == 2).unwrap();
+        let first = layout.nodes[route.points[0]].node.unwrap();
+        let last = layout.nodes[*route.points.last().unwrap()].node.unwrap();
+        assert_eq!(
+            (
+                graph.nodes[first].id.as_str(),
+                graph.nodes[last].id.as_str()
+            ),
+            ("c", "a")
+        );
+    }
+
+    #[test]
+    fn crossings_are_reduced() {
+        let (_, mut layout) = lay_out("digraph { a -> c; a -> d; b -> e; b -> f; c -> g; f -> h }");
+        assert_eq!(layout.crossings(), 0);
+
+        // reverse the middle layer, so every edge into it crosses those of the other parent
+        layout.layers[1].reverse();
+        assert!(layout.crossings() > 0);
+        layout.order();
+        assert_eq!(layout.crossings(), 0);
+    }
+
+    #[test]
+    fn clusters_stay_contiguous() {
+        let (graph, layout) =
+            lay_out("digraph { subgraph cluster_0 { b; d } a -> b; a -> c; a -> d }");
+        let b = node(&graph, &layout, "b").x;
+        let c = node(&graph, &layout, "c").x;
+        let d = node(&graph, &layout, "d").x;
+        assert!(
+            !(b.min(d) < c && c < b.max(d)),
+            "c is placed between b and d"
+        );
+    }
+
+    #[test]
+    fn edge_labels_are_placed_outside_bends() {
+        // edges fanning out from a node are labelled on their outer sides
+        let src = "digraph { p -> l [label=L]; p -> r [label=R] }";
+        let (graph, layout) = lay_out(src);
+        let svg = layout.render(&graph).unwrap();
+        let p = node(&graph, &layout, "p").x;
+        let (l, r) = (node(&graph, &layout, "l").x, node(&graph, &layout, "r").x);
+        assert!(l < p && p < r);
+        assert!(text_x(&svg, "L") < (p + l) / 2.0);
+        assert!(text_x(&svg, "R") > (p + r) / 2.0);
+
+        // as are edges converging on a node, like the inputs of a join
+        let src = "digraph { l -> j [label=L]; r -> j [label=R] }";
+        let (graph, layout) = lay_out(src);
+        let svg = layout.render(&graph).unwrap();
+        let j = node(&graph, &layout, "j").x;
+        let (l, r) = (node(&graph, &layout, "l").x, node(&graph, &layout, "r").x);
+        assert!(l < j && j < r);
+        assert!(text_x(&svg, "L") < (l + j) / 2.0);
+        assert!(text_x(&svg, "R") > (r + j) / 2.0);
+    }
+
+    #[test]
+    fn invalid_graphs_are_errors() {
+        assert!(matches!(
+            to_svg("digraph { a -> }"),
+            Err(CLIError::RenderError { .. })
+        ));
+        assert!(to_svg("digraph { a -> b }").unwrap().contains("<svg"));
+    }
+}
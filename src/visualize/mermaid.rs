@@ -0,0 +1,114 @@
+//! Translates the dot graphs produced by [`AstToDot`] and [`PlanToDot`] into [Mermaid] flowcharts.
+//!
+//! [Mermaid]: https://mermaid.js.org/syntax/flowchart.html
+//! [`AstToDot`]: crate::visualize::ast_to_dot::AstToDot
+//! [`PlanToDot`]: crate::visualize::plan_to_dot::PlanToDot
+
+use crate::visualize::dot::{self, attr, label_lines, node_label, shape, DotGraph, Label};
+use std::fmt::Write;
+
+/// Converts a dot-formatted graph into a Mermaid flowchart.
+pub(crate) fn to_mermaid(graph: &str) -> String {
+    let graph = dot::parse(graph).expect("dot parse");
+    write_flowchart(&graph).expect("mermaid write")
+}
+
+fn write_flowchart(graph: &DotGraph) -> Result<String, std::fmt::Error> {
+    let mut out = String::new();
+    let direction = match attr(&graph.attrs, "rankdir", "TB") {
+        dir @ ("TB" | "BT" | "LR" | "RL") => dir,
+        _ => "TB",
+    };
+    writeln!(out, "flowchart {direction}")?;
+    write_scope(&mut out, graph, None, 1)?;
+
+    for edge in &graph.edges {
+        write!(out, "    n{} -->", edge.from)?;
+        if let Some(label) = edge.attrs.get("label").filter(|l| !l.is_empty()) {
+            write!(out, "|\"{}\"|", text(&label_lines(label, "")))?;
+        }
+        writeln!(out, " n{}", edge.to)?;
+    }
+    Ok(out)
+}
+
+/// Writes the nodes directly within `cluster`, followed by its nested clusters as subgraphs.
+fn write_scope(
+    out: &mut String,
+    graph: &DotGraph,
+    cluster: Option<usize>,
+    depth: usize,
+) -> std::fmt::Result {
+    let indent = "    ".repeat(depth);
+    for (idx, node) in graph.nodes.iter().enumerate() {
+        if node.cluster != cluster {
+            continue;
+        }
+        let label = match node_label(&node.id, &node.attrs) {
+            Label::Text(lines) => text(&lines),
+            Label::Record { vertical, fields } => {
+                let fields: Vec<_> = fields.iter().map(|f| text(f)).collect();
+                fields.join(if vertical { "<br/>" } else { " | " })
+            }
+        };
+        let (open, close) = match shape(&node.attrs) {
+            "ellipse" | "oval" => ("([", "])"),
+            "Mrecord" => ("(", ")"),
+            "circle" | "doublecircle" | "point" => ("((", "))"),
+            "diamond" => ("{", "}"),
+            "parallelogram" => ("[/", "/]"),
+            _ => ("[", "]"),
+        };
+        writeln!(out, "{indent}n{idx}{open}\"{label}\"{close}")?;
+    }
+
+    for (idx, child) in graph.clusters.iter().enumerate() {
+        // as in graphviz, clusters without any nodes aren't drawn
+        if child.parent != cluster || !has_nodes(graph, idx) {
+            continue;
+        }
+        let label = text(&label_lines(attr(&child.attrs, "label", ""), ""));
+        let label = if label.is_empty() {
+            " ".to_string()
+        } else {
+            label
+        };
+        writeln!(out, "{indent}subgraph c{idx} [\"{label}\"]")?;
+        write_scope(out, graph, Some(idx), depth + 1)?;
+        writeln!(out, "{indent}end")?;
+    }
+    Ok(())
+}
+
+/// Whether `cluster` or any cluster nested within it contains a node.
+fn has_nodes(graph: &DotGraph, cluster: usize) -> bool {
+    graph.nodes.iter().any(|node| {
+        let mut current = node.cluster;
+        while let Some(idx) = current {
+            if idx == cluster {
+                return true;
+            }
+            current = graph.clusters[idx].parent;
+        }
+        false
+    })
+}
+
+/// Joins lines of label text, escaping characters that are significant within a Mermaid string.
+fn text(lines: &[String]) -> String {
+    let escaped: Vec<_> = lines
+        .iter()
+        .map(|line| {
+            line.chars()
+                .map(|c| match c {
+                    '#' => "#35;".to_string(),
+                    '"' => "#quot;".to_string(),
+                    '<' => "#lt;".to_string(),
+                    '>' => "#gt;".to_string(),
+                    c => c.to_string(),
+                })
+                .collect::<String>()
+        })
+        .collect();
+    escaped.join("<br/>")
+}
@@ -1,4 +1,8 @@
 pub mod ast_to_dot;
 pub(crate) mod common;
+pub(crate) mod dot;
+pub(crate) mod html;
+pub(crate) mod layout;
+pub(crate) mod mermaid;
 pub mod plan_to_dot;
 pub mod render;
@@ -1,213 +1,646 @@
-use dot_writer::{Attributes, DotWriter, Node, NodeId, Scope, Shape};
+use dot_writer::{Attributes, DotWriter, Node, NodeId, Scope};
 use std::collections::HashMap;
 
-use crate::visualize::common::{ToDotGraph, BG_COLOR, FG_COLOR};
-use crate::visualize::render::to_dot;
+use crate::analyze::AnalyzedPlan;
+use crate::eval_plan::{EvalGraph, EvalOp};
+use crate::explain::{branch_label, op_label, OpLabel, Subqueries};
+use crate::plan_diff::{Change, DiffOp, PlanDiff};
+use crate::visualize::common::{NodeCategory, Theme, ToDotGraph};
 use itertools::Itertools;
-use partiql_logical::{
-    AggregateExpression, BinaryOp, BindingsOp, JoinKind, LogicalPlan, OpId, ValueExpr,
-};
-use serde::de::Unexpected::Option;
+use partiql_logical::{BindingsOp, LogicalPlan};
 
-pub struct PlanToDot {}
+pub struct PlanToDot {
+    theme: Theme,
+}
 
 impl Default for PlanToDot {
     fn default() -> Self {
-        PlanToDot {}
+        PlanToDot {
+            theme: Theme::default(),
+        }
     }
 }
 
 impl PlanToDot {
+    /// Draws plans using `theme`'s colors and fonts.
+    pub fn with_theme(theme: Theme) -> Self {
+        PlanToDot { theme }
+    }
+
     pub(crate) fn to_dot(&self, scope: &mut Scope, plan: &LogicalPlan<BindingsOp>) {
+        self.draw(scope, plan, None)
+    }
+
+    /// Draws `plan`, labelling each operator with its stats from `analyzed`, if any, followed by a
+    /// cluster for each of the subqueries it refers to.
+    fn draw(
+        &self,
+        scope: &mut Scope,
+        plan: &LogicalPlan<BindingsOp>,
+        analyzed: Option<&AnalyzedPlan>,
+    ) {
+        let subqueries = Subqueries::of(plan);
+        self.draw_ops(scope, plan, &subqueries, analyzed);
+        for (number, subplan) in subqueries.plans() {
+            let mut cluster = scope.cluster();
+            cluster.set("label", &format!("subquery #{number}"), true);
+            self.draw_ops(&mut cluster, subplan, &subqueries, None);
+        }
+    }
+
+    fn draw_ops<'a>(
+        &self,
+        scope: &mut Scope,
+        plan: &'a LogicalPlan<BindingsOp>,
+        subqueries: &Subqueries<'a>,
+        analyzed: Option<&AnalyzedPlan>,
+    ) {
         let mut graph_nodes = HashMap::new();
         for (opid, op) in plan.operators_by_id() {
-            graph_nodes.insert(opid, self.op_to_dot(scope, op));
+            let mut label = op_label(op, subqueries);
+            let mut penwidth = None;
+            if let Some(analyzed) = analyzed {
+                if let Some(stats) = analyzed.stats.get(&opid) {
+                    label.fields.extend(stats.fields());
+                }
+                // outline operators more heavily the more of the evaluation time they take
+                penwidth = Some(1.0 + 4.0 * analyzed.share(opid));
+            }
+            graph_nodes.insert(opid, self.op_to_dot(scope, op, label, penwidth));
         }
 
         for (src, dst, branch) in plan.flows() {
+            let label = branch_label(plan.operator(*dst).expect("dst op"), *branch);
             let src = graph_nodes.get(src).expect("src op");
             let dst = graph_nodes.get(dst).expect("dst op");
 
+            scope.edge(src, dst).attributes().set_label(&label);
+        }
+    }
+
+    fn op_to_dot(
+        &self,
+        scope: &mut Scope,
+        op: &BindingsOp,
+        label: OpLabel,
+        penwidth: Option<f32>,
+    ) -> NodeId {
+        let mut node = scope.node_auto();
+        let shape = match op_category(op) {
+            Some(category) => {
+                node.set("color", self.theme.color(category), true);
+                category.shape()
+            }
+            None => "Mrecord",
+        };
+        node.set("shape", shape, false)
+            .set_label(&record_label(label));
+        if let Some(penwidth) = penwidth {
+            node.set("penwidth", &format!("{penwidth:.1}"), false);
+        }
+
+        node.id()
+    }
+}
+
+impl PlanToDot {
+    /// Draws the operators of a compiled evaluation plan, labelled with their indices.
+    pub(crate) fn eval_to_dot(&self, scope: &mut Scope, graph: &EvalGraph) {
+        let mut graph_nodes = HashMap::new();
+        for op in &graph.operators {
+            graph_nodes.insert(op.id, self.eval_op_to_dot(scope, op));
+        }
+
+        for flow in &graph.flows {
+            let (Some(src), Some(dst)) = (graph_nodes.get(&flow.src), graph_nodes.get(&flow.dst))
+            else {
+                continue;
+            };
             scope
                 .edge(src, dst)
                 .attributes()
-                .set_label(&branch.to_string());
+                .set_label(&flow.branch.to_string());
         }
     }
 
-    fn op_to_dot(&self, scope: &mut Scope, op: &BindingsOp) -> NodeId {
+    fn eval_op_to_dot(&self, scope: &mut Scope, op: &EvalOp) -> NodeId {
         let mut node = scope.node_auto();
-        let label = match op {
-            BindingsOp::Scan(s) => {
-                format!("{{scan | {} | as {} }}", expr_to_str(&s.expr), s.as_key)
-            }
-            BindingsOp::Pivot(p) => format!(
-                "{{pivot | {} | at {} }}",
-                expr_to_str(&p.value),
-                expr_to_str(&p.key)
-            ),
-            BindingsOp::Unpivot(u) => format!(
-                "{{unpivot | {}  | as {} | at {} }}",
-                expr_to_str(&u.expr),
-                &u.as_key,
-                &u.at_key.as_deref().unwrap_or("")
-            ),
-            BindingsOp::Filter(f) => format!("{{filter | {} }}", expr_to_str(&f.expr)),
-            BindingsOp::OrderBy(o) => {
-                let specs = o
-                    .specs
-                    .iter()
-                    .map(|s| {
-                        format!(
-                            "{} {:?} NULLS {:?}",
-                            expr_to_str(&s.expr),
-                            s.order,
-                            s.null_order
-                        )
-                    })
-                    .join(" | ");
-                format!("{{order by | {} }}", specs)
-            }
-            BindingsOp::LimitOffset(lo) => {
-                let clauses = [
-                    lo.limit
-                        .as_ref()
-                        .map(|e| format!("limit {}", expr_to_str(&e))),
-                    lo.offset
-                        .as_ref()
-                        .map(|e| format!("offset {}", expr_to_str(&e))),
-                ]
-                .iter()
-                .filter_map(|o| o.as_ref())
-                .join(" | ");
-                format!("{{ {clauses} }}")
-            }
-            BindingsOp::Join(join) => {
-                let kind = match join.kind {
-                    JoinKind::Inner => "inner",
-                    JoinKind::Left => "left",
-                    JoinKind::Right => "right",
-                    JoinKind::Full => "full",
-                    JoinKind::Cross => "cross",
-                };
-                format!(
-                    "{{ {} join | {} }}",
-                    kind,
-                    join.on
-                        .as_ref()
-                        .map(|e| expr_to_str(e))
-                        .unwrap_or("".to_string())
-                )
+        let shape = match eval_op_category(&op.label) {
+            Some(category) => {
+                node.set("color", self.theme.color(category), true);
+                category.shape()
             }
-            BindingsOp::BagOp(_) => "set op (TODO)".to_string(),
-            BindingsOp::Project(p) => {
-                format!(
-                    "{{project  | {} }}",
-                    p.exprs
-                        .iter()
-                        .map(|(k, e)| format!("{}:{}", k, expr_to_str(e)))
-                        .join(" | "),
-                )
-            }
-            BindingsOp::ProjectAll => {
-                format!("{{project  * }}")
-            }
-            BindingsOp::ProjectValue(pv) => {
-                format!("{{project value | {} }}", expr_to_str(&pv.expr))
-            }
-            BindingsOp::ExprQuery(eq) => {
-                format!("{{ {} }}", expr_to_str(&eq.expr))
+            None => "Mrecord",
+        };
+        let label = OpLabel {
+            name: format!("#{}", op.id),
+            fields: vec![op.label.clone()],
+        };
+        node.set("shape", shape, false)
+            .set_label(&record_label(label));
+
+        node.id()
+    }
+}
+
+/// Draws the operators of two aligned plans, colored by how they changed between the plans.
+pub struct PlanDiffToDot {
+    theme: Theme,
+}
+
+impl Default for PlanDiffToDot {
+    fn default() -> Self {
+        PlanDiffToDot {
+            theme: Theme::default(),
+        }
+    }
+}
+
+impl PlanDiffToDot {
+    /// Draws plan diffs using `theme`'s colors and fonts.
+    pub fn with_theme(theme: Theme) -> Self {
+        PlanDiffToDot { theme }
+    }
+
+    pub(crate) fn to_dot(&self, scope: &mut Scope, diff: &PlanDiff) {
+        for root in &diff.roots {
+            self.op_to_dot(scope, root);
+        }
+        for (number, subquery) in &diff.subqueries {
+            let mut cluster = scope.cluster();
+            cluster.set("label", &format!("subquery #{number}"), true);
+            for root in &subquery.roots {
+                self.op_to_dot(&mut cluster, root);
             }
-            BindingsOp::Distinct => "distinct".to_string(),
-            BindingsOp::GroupBy(g) => {
-                format!(
-                    "{{group by | {:?} | {{ keys | {{ {} }} }} | {{ aggs | {{ {} }} }} | as {} }}",
-                    g.strategy,
-                    g.exprs
-                        .iter()
-                        .map(|(k, e)| format!("{}:{}", k, expr_to_str(e)))
-                        .join(" | "),
-                    g.aggregate_exprs.iter().map(agg_expr_to_str).join(" | "),
-                    g.group_as_alias.as_deref().unwrap_or(""),
-                )
+        }
+    }
+
+    fn op_to_dot(&self, scope: &mut Scope, op: &DiffOp) -> NodeId {
+        let id = {
+            let mut node = scope.node_auto();
+            node.set("shape", "Mrecord", false)
+                .set_label(&record_label(op.label()));
+            if let Some(category) = change_category(op.change) {
+                let color = self.theme.color(category);
+                node.set("color", color, true).set("fontcolor", color, true);
             }
-            BindingsOp::Having(h) => {
-                format!("{{ having | {} }}", expr_to_str(&h.expr))
+            if op.change == Change::Removed {
+                node.set("style", "dashed", false);
             }
-            BindingsOp::Sink => "sink".to_string(),
+            node.id()
         };
-        node.set_shape(Shape::Mrecord)
-            .set_label(&format!("{label}"));
 
-        node.id()
+        for (branch, input) in &op.inputs {
+            let src = self.op_to_dot(scope, input);
+            let mut edge = scope.edge(&src, &id);
+            let mut attrs = edge.attributes();
+            attrs.set_label(branch);
+            // edges to or from an added or removed operator are added or removed with it
+            let change = [input.change, op.change]
+                .into_iter()
+                .find(|change| matches!(change, Change::Added | Change::Removed));
+            if let Some(category) = change.and_then(change_category) {
+                attrs.set("color", self.theme.color(category), true);
+            }
+        }
+        id
+    }
+}
+
+fn change_category(change: Change) -> Option<NodeCategory> {
+    match change {
+        Change::Unchanged => None,
+        Change::Changed => Some(NodeCategory::Changed),
+        Change::Added => Some(NodeCategory::Added),
+        Change::Removed => Some(NodeCategory::Removed),
+    }
+}
+
+/// Formats an operator's label as a graphviz record of its name and fields.
+fn record_label(label: OpLabel) -> String {
+    let OpLabel { name, fields } = label;
+    if fields.is_empty() {
+        escape_record(&name)
+    } else {
+        format!(
+            "{{ {} | {} }}",
+            escape_record(&name),
+            fields.iter().map(|f| escape_record(f)).join(" | ")
+        )
     }
 }
 
-fn expr_to_str(expr: &ValueExpr) -> String {
-    match expr {
-        ValueExpr::BinaryExpr(BinaryOp::And, lhs, rhs) => {
-            format!(
-                "{{  AND | {{ {} | {} }}  }}",
-                expr_to_str(lhs),
-                expr_to_str(rhs)
-            )
+/// The category an operator is drawn as, if any.
+fn op_category(op: &BindingsOp) -> Option<NodeCategory> {
+    match op {
+        BindingsOp::Scan(_) | BindingsOp::Unpivot(_) | BindingsOp::ExprQuery(_) => {
+            Some(NodeCategory::Scan)
         }
-        ValueExpr::BinaryExpr(BinaryOp::Or, lhs, rhs) => {
-            format!(
-                "{{  OR | {{ {} | {} }}  }}",
-                expr_to_str(lhs),
-                expr_to_str(rhs)
-            )
+        BindingsOp::Join(_) => Some(NodeCategory::Join),
+        BindingsOp::Filter(_) | BindingsOp::Having(_) => Some(NodeCategory::Filter),
+        BindingsOp::GroupBy(_) | BindingsOp::Distinct | BindingsOp::Pivot(_) => {
+            Some(NodeCategory::Aggregate)
         }
-        expr => {
-            let expr: String = format!("{:?}", expr).escape_default().collect();
-            let expr = expr.replace('{', "\\{");
-            let expr = expr.replace('}', "\\}");
-            let expr = expr.replace('<', "\\<");
-            let expr = expr.replace('>', "\\>");
-            expr
+        BindingsOp::Project(_) | BindingsOp::ProjectAll | BindingsOp::ProjectValue(_) => {
+            Some(NodeCategory::Project)
         }
+        BindingsOp::BagOp(_) => Some(NodeCategory::SetOp),
+        BindingsOp::OrderBy(_) | BindingsOp::LimitOffset(_) => Some(NodeCategory::Sort),
+        BindingsOp::Sink => None,
     }
 }
 
-fn agg_expr_to_str(agg_expr: &AggregateExpression) -> String {
-    let expr: String = format!("{:?}", agg_expr.expr).escape_default().collect();
-    let expr = expr.replace('{', "\\{");
-    let expr = expr.replace('}', "\\}");
-    format!(
-        "{}:{}:{:?}:{:?}",
-        agg_expr.name, expr, agg_expr.func, agg_expr.setq
-    )
+/// The category an evaluation plan operator is drawn as, if any, from the evaluator's description
+/// of it.
+fn eval_op_category(label: &str) -> Option<NodeCategory> {
+    let kind = label
+        .split(|c: char| c.is_whitespace() || c == '{' || c == '(')
+        .next()
+        .unwrap_or_default();
+    match kind {
+        "SCAN" | "EvalUnpivot" | "EvalExprQuery" => Some(NodeCategory::Scan),
+        "Inner" | "Left" | "Right" | "Full" if label.contains(" JOIN") => Some(NodeCategory::Join),
+        "EvalFilter" | "EvalHaving" => Some(NodeCategory::Filter),
+        "EvalGroupBy" | "EvalDistinct" | "EvalPivot" => Some(NodeCategory::Aggregate),
+        "SELECT" | "EvalSelectAll" | "EvalSelectValue" => Some(NodeCategory::Project),
+        "EvalOuterUnion" | "EvalOuterIntersect" | "EvalOuterExcept" => Some(NodeCategory::SetOp),
+        "EvalOrderBy" | "EvalLimitOffset" => Some(NodeCategory::Sort),
+        _ => None,
+    }
+}
+
+/// Escapes text for use within a graphviz record label.
+fn escape_record(text: &str) -> String {
+    let text: String = text.escape_debug().collect();
+    let text = text.replace('{', "\\{");
+    let text = text.replace('}', "\\}");
+    let text = text.replace('<', "\\<");
+    let text = text.replace('>', "\\>");
+    let text = text.replace('|', "\\|");
+    text
 }
 
 impl ToDotGraph<LogicalPlan<BindingsOp>> for PlanToDot {
-    fn to_graph(mut self, plan: &LogicalPlan<BindingsOp>) -> String {
-        let mut output_bytes = Vec::new();
-
-        {
-            let mut writer = DotWriter::from(&mut output_bytes);
-            writer.set_pretty_print(true);
-            let mut digraph = writer.digraph();
-            digraph
-                .graph_attributes()
-                .set_rank_direction(dot_writer::RankDirection::TopBottom)
-                .set("bgcolor", BG_COLOR, false)
-                .set("fontcolor", FG_COLOR, false)
-                .set("pencolor", FG_COLOR, false);
-            digraph.node_attributes().set("color", FG_COLOR, false).set(
-                "fontcolor",
-                FG_COLOR,
-                false,
-            );
-            digraph.edge_attributes().set("color", FG_COLOR, false).set(
-                "fontcolor",
-                FG_COLOR,
-                false,
-            );
+    fn to_graph(self, plan: &LogicalPlan<BindingsOp>) -> String {
+        write_graph(&self.theme, |digraph| self.to_dot(digraph, plan))
+    }
+}
+
+impl ToDotGraph<AnalyzedPlan> for PlanToDot {
+    fn to_graph(self, analyzed: &AnalyzedPlan) -> String {
+        write_graph(&self.theme, |digraph| {
+            self.draw(digraph, &analyzed.plan, Some(analyzed))
+        })
+    }
+}
+
+impl ToDotGraph<EvalGraph> for PlanToDot {
+    fn to_graph(self, graph: &EvalGraph) -> String {
+        write_graph(&self.theme, |digraph| self.eval_to_dot(digraph, graph))
+    }
+}
+
+impl ToDotGraph<PlanDiff> for PlanDiffToDot {
+    fn to_graph(self, diff: &PlanDiff) -> String {
+        write_graph(&self.theme, |digraph| self.to_dot(digraph, diff))
+    }
+}
+
+/// Writes a digraph styled with `theme`, whose contents are drawn by `draw`.
+fn write_graph<F>(theme: &Theme, draw: F) -> String
+where
+    F: FnOnce(&mut Scope),
+{
+    let mut output_bytes = Vec::new();
+
+    {
+        let mut writer = DotWriter::from(&mut output_bytes);
+        writer.set_pretty_print(true);
+        let mut digraph = writer.digraph();
+        digraph
+            .graph_attributes()
+            .set_rank_direction(dot_writer::RankDirection::TopBottom)
+            .set("bgcolor", &theme.background, true)
+            .set("fontcolor", &theme.foreground, true)
+            .set("pencolor", &theme.foreground, true)
+            .set("fontname", &theme.font, true)
+            .set("fontsize", &theme.font_size.to_string(), false);
+        digraph
+            .node_attributes()
+            .set("color", &theme.foreground, true)
+            .set("fontcolor", &theme.foreground, true)
+            .set("fontname", &theme.font, true)
+            .set("fontsize", &theme.font_size.to_string(), false);
+        digraph
+            .edge_attributes()
+            .set("color", &theme.foreground, true)
+            .set("fontcolor", &theme.foreground, true)
+            .set("fontname", &theme.font, true)
+            .set("fontsize", &theme.font_size.to_string(), false);
+
+        draw(&mut digraph);
+    }
+
+    return String::from_utf8(output_bytes).expect("invalid utf8");
+}
+
+#[cfg(test)]
+mod tests {
+    //! Snapshots of the dot rendering of a plan with each kind of operator; the text renderings of
+    //! the same plans are in `explain`'s tests.
+
+    use super::*;
+    use crate::evaluate::Compiler;
+
+    /// The node and edge statements of a dot graph, without the theme's graph, node and edge
+    /// attributes.
+    fn statements(dot: &str) -> String {
+        let defaults = ["digraph", "graph [", "node [", "edge [", "}"];
+        dot.lines()
+            .filter(|line| !line.is_empty() && !defaults.iter().any(|d| line.starts_with(d)))
+            .map(|line| format!("{line}\n"))
+            .collect()
+    }
+
+    /// Plans `query`, and checks the dot rendering of its plan; the expected rendering starts with
+    /// a line break, for readability.
+    fn assert_dot(query: &str, dot: &str) {
+        let compiler = Compiler::default();
+        let parsed = compiler.parse(query).unwrap();
+        let plan = compiler.plan(&parsed).unwrap();
+        assert_eq!(statements(&PlanToDot::default().to_graph(&plan)), dot[1..]);
+    }
+
+    #[test]
+    fn scan_and_project() {
+        assert_dot(
+            "SELECT a FROM t",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [shape=Mrecord, label="sink"];
+node_1 -> node_0 [label="0"];
+node_0 -> node_2 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn pivot() {
+        assert_dot(
+            "PIVOT v.a AT v.b FROM t AS v",
+            r##"
+node_0 [color="#859900", shape=Mrecord, label="{ pivot | v.a | at v.b }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as v }"];
+node_2 [shape=Mrecord, label="sink"];
+node_1 -> node_0 [label="0"];
+node_0 -> node_2 [label="0"];
+"##,
+        );
+    }
 
-            self.to_dot(&mut digraph, plan);
+    #[test]
+    fn unpivot() {
+        assert_dot(
+            "SELECT k, v FROM UNPIVOT t AS v AT k",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | k:k | v:v }"];
+node_1 [color="#268bd2", shape=record, label="{ unpivot | t | as v | at k }"];
+node_2 [shape=Mrecord, label="sink"];
+node_1 -> node_0 [label="0"];
+node_0 -> node_2 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn filter() {
+        assert_dot(
+            "SELECT a FROM t WHERE a > 1",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [color="#cb4b16", shape=Mrecord, label="{ filter | a \> 1 }"];
+node_3 [shape=Mrecord, label="sink"];
+node_1 -> node_2 [label="0"];
+node_2 -> node_0 [label="0"];
+node_0 -> node_3 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn order_by() {
+        assert_dot(
+            "SELECT a FROM t ORDER BY a DESC",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [color="#b58900", shape=Mrecord, label="{ order by | a DESC NULLS FIRST }"];
+node_3 [shape=Mrecord, label="sink"];
+node_1 -> node_2 [label="0"];
+node_2 -> node_0 [label="0"];
+node_0 -> node_3 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn limit_offset() {
+        assert_dot(
+            "SELECT a FROM t LIMIT 10 OFFSET 2",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [color="#b58900", shape=Mrecord, label="{ limit 10 | offset 2 }"];
+node_3 [shape=Mrecord, label="sink"];
+node_1 -> node_2 [label="0"];
+node_2 -> node_0 [label="0"];
+node_0 -> node_3 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn join() {
+        assert_dot(
+            "SELECT t.a, u.b FROM t JOIN u ON t.a = u.b",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:t.a | b:u.b }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [color="#268bd2", shape=record, label="{ scan | u | as u }"];
+node_3 [color="#d33682", shape=Mrecord, label="{ inner join | t.a = u.b }"];
+node_4 [shape=Mrecord, label="sink"];
+node_1 -> node_3 [label="left"];
+node_2 -> node_3 [label="right"];
+node_3 -> node_0 [label="0"];
+node_0 -> node_4 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn set_operations() {
+        for (op, label) in [
+            ("UNION", "union distinct"),
+            ("UNION ALL", "union all"),
+            ("INTERSECT", "intersect distinct"),
+            ("INTERSECT ALL", "intersect all"),
+            ("EXCEPT", "except distinct"),
+            ("EXCEPT ALL", "except all"),
+        ] {
+            let query = format!("SELECT a FROM t {op} SELECT a FROM u");
+            let dot = format!(
+                r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{{ project | a:a }}"];
+node_1 [color="#268bd2", shape=record, label="{{ scan | t | as t }}"];
+node_2 [color="#2aa198", shape=Mrecord, label="{{ project | a:a }}"];
+node_3 [color="#268bd2", shape=record, label="{{ scan | u | as u }}"];
+node_4 [color="#6c71c4", shape=Mrecord, label="{label}"];
+node_5 [shape=Mrecord, label="sink"];
+node_1 -> node_0 [label="0"];
+node_3 -> node_2 [label="0"];
+node_0 -> node_4 [label="left"];
+node_2 -> node_4 [label="right"];
+node_4 -> node_5 [label="0"];
+"##
+            );
+            assert_dot(&query, &dot);
         }
+    }
+
+    #[test]
+    fn project_all() {
+        assert_dot(
+            "SELECT * FROM t",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="project *"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [shape=Mrecord, label="sink"];
+node_1 -> node_0 [label="0"];
+node_0 -> node_2 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn project_value() {
+        assert_dot(
+            "SELECT VALUE a FROM t",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project value | a }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [shape=Mrecord, label="sink"];
+node_1 -> node_0 [label="0"];
+node_0 -> node_2 [label="0"];
+"##,
+        );
+    }
 
-        return String::from_utf8(output_bytes).expect("invalid utf8");
+    #[test]
+    fn expr_query() {
+        assert_dot(
+            "1 + 1",
+            r##"
+node_0 [color="#268bd2", shape=record, label="1 + 1"];
+node_1 [shape=Mrecord, label="sink"];
+node_0 -> node_1 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn distinct() {
+        assert_dot(
+            "SELECT DISTINCT a FROM t",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a }"];
+node_1 [color="#859900", shape=Mrecord, label="distinct"];
+node_2 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_3 [shape=Mrecord, label="sink"];
+node_2 -> node_0 [label="0"];
+node_0 -> node_1 [label="0"];
+node_1 -> node_3 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn group_by() {
+        assert_dot(
+            "SELECT a, COUNT(*) AS n FROM t GROUP BY a",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a | n:\"$__agg_1\" }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [color="#859900", shape=Mrecord, label="{ group by | FULL | keys: a:a | aggs: $__agg_1:count(1) }"];
+node_3 [shape=Mrecord, label="sink"];
+node_1 -> node_2 [label="0"];
+node_2 -> node_0 [label="0"];
+node_0 -> node_3 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn having() {
+        assert_dot(
+            "SELECT a, COUNT(*) AS n FROM t GROUP BY a HAVING COUNT(*) > 1",
+            r##"
+node_0 [color="#2aa198", shape=Mrecord, label="{ project | a:a | n:\"$__agg_1\" }"];
+node_1 [color="#268bd2", shape=record, label="{ scan | t | as t }"];
+node_2 [color="#859900", shape=Mrecord, label="{ group by | FULL | keys: a:a | aggs: $__agg_1:count(1) }"];
+node_3 [color="#cb4b16", shape=Mrecord, label="{ having | \"$__agg_2\" \> 1 }"];
+node_4 [shape=Mrecord, label="sink"];
+node_1 -> node_2 [label="0"];
+node_2 -> node_3 [label="0"];
+node_3 -> node_0 [label="0"];
+node_0 -> node_4 [label="0"];
+"##,
+        );
+    }
+
+    #[test]
+    fn eval_operators_are_categorized() {
+        use crate::eval_plan::eval_graph;
+        use NodeCategory::*;
+
+        let cases = [
+            ("SELECT a FROM t WHERE a > 1", vec![Scan, Filter, Project]),
+            (
+                "SELECT a FROM t GROUP BY a HAVING count(*) > 1 ORDER BY a LIMIT 1",
+                vec![Scan, Aggregate, Filter, Sort, Sort, Project],
+            ),
+            (
+                "SELECT DISTINCT VALUE a FROM t",
+                vec![Scan, Project, Aggregate],
+            ),
+            ("SELECT * FROM t", vec![Scan, Project]),
+            ("PIVOT v AT k FROM t", vec![Scan, Aggregate]),
+            ("SELECT a FROM UNPIVOT t AS a", vec![Scan, Project]),
+            (
+                "SELECT x.a FROM t AS x LEFT JOIN u AS y ON true",
+                vec![Scan, Join, Scan, Project],
+            ),
+            (
+                "SELECT a FROM t OUTER EXCEPT SELECT a FROM u",
+                vec![Scan, Project, Scan, Project, SetOp],
+            ),
+            ("1 + 1", vec![Scan]),
+        ];
+        let compiler = Compiler::default();
+        for (query, expected) in cases {
+            let parsed = compiler.parse(query).unwrap();
+            let plan = compiler.plan(&parsed).unwrap();
+            let mut graph = eval_graph(&compiler.compile(&parsed, &plan).unwrap());
+            graph.operators.sort_by_key(|op| op.id);
+            let categories: Vec<_> = graph
+                .operators
+                .iter()
+                .filter(|op| op.label != "SINK")
+                .map(|op| eval_op_category(&op.label))
+                .collect();
+            let expected: Vec<_> = expected.into_iter().map(Some).collect();
+            assert_eq!(categories, expected, "{query}");
+        }
     }
 }
@@ -1,30 +1,41 @@
+use crate::args::{DisplayArgs, Layout};
 use crate::visualize::ast_to_dot::AstToDot;
 
 use std::convert::AsRef;
+use std::io::IsTerminal;
 use std::io::Write;
+#[cfg(feature = "graphviz")]
 use std::os::raw::c_char;
+#[cfg(feature = "graphviz")]
 use std::slice;
+#[cfg(feature = "graphviz")]
 use strum::AsRefStr;
 
+use crate::analyze::AnalyzedPlan;
+use crate::error::{CLIError, CLIErrors};
+use crate::eval_plan::EvalGraph;
+use crate::plan_diff::PlanDiff;
 use crate::visualize::common::ToDotGraph;
-use crate::visualize::plan_to_dot::PlanToDot;
+use crate::visualize::html::{self, Subtree};
+use crate::visualize::layout;
+use crate::visualize::mermaid;
+use crate::visualize::plan_to_dot::{PlanDiffToDot, PlanToDot};
+use clap::ValueEnum;
+use config::Config;
+#[cfg(feature = "graphviz")]
 use graphviz_sys as gv;
 use partiql_ast::ast;
 use partiql_ast::ast::{AstNode, Expr, TopLevelQuery};
 use partiql_logical::{BindingsOp, LogicalPlan};
-use serde::Serialize;
+use partiql_parser::Parsed;
 use tiny_skia::Transform;
 
-/// Convert an AST into JSON
-#[inline]
-pub fn to_json<T>(data: &T) -> String
-where
-    T: ?Sized + Serialize,
-{
-    serde_json::to_string_pretty(&data).expect("json print")
-}
+pub use crate::args::ImageProtocol;
+pub use crate::json::to_json;
+pub use crate::visualize::common::Theme;
 
 /// Graphviz output formats
+#[cfg(feature = "graphviz")]
 #[derive(AsRefStr, Debug, Copy, Clone)]
 #[strum(serialize_all = "lowercase")]
 #[non_exhaustive]
@@ -45,6 +56,7 @@ pub enum GraphVizFormat {
 }
 
 /// FFI to graphviz-sys to convert a dot-formatted graph into the specified format.
+#[cfg(feature = "graphviz")]
 fn gv_render(format: GraphVizFormat, graph_str: String) -> Vec<u8> {
     let c_graph_str = std::ffi::CString::new(graph_str).expect("cstring new failed");
     let c_dot = std::ffi::CString::new("dot").expect("cstring new failed");
@@ -52,9 +64,7 @@ fn gv_render(format: GraphVizFormat, graph_str: String) -> Vec<u8> {
 
     unsafe {
         let gvc = gv::gvContext();
-        // TODO gvParseArgs to pass 'theme' colors, etc?
-        //    See section 4 of https://www.graphviz.org/pdf/libguide.pdf
-        //    See `dot --help`
+        // theme colors and fonts are set as attributes of the graph itself
         let g = gv::agmemread(c_graph_str.as_ptr());
 
         gv::gvLayout(gvc, g, c_dot.as_ptr());
@@ -78,91 +88,344 @@ fn gv_render(format: GraphVizFormat, graph_str: String) -> Vec<u8> {
 
 pub struct Graph(pub String);
 
-impl Into<Graph> for &AstNode<TopLevelQuery> {
-    fn into(self) -> Graph {
-        Graph(AstToDot::default().to_graph(self))
+/// Data that can be drawn as a dot graph.
+pub trait IntoGraph {
+    fn into_graph(self, theme: &Theme) -> Graph;
+}
+
+impl IntoGraph for &AstNode<TopLevelQuery> {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(AstToDot::default().with_theme(theme.clone()).to_graph(self))
+    }
+}
+
+impl IntoGraph for &Parsed<'_> {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(
+            AstToDot::with_source(self)
+                .with_theme(theme.clone())
+                .to_graph(&self.ast),
+        )
     }
 }
 
-impl Into<Graph> for &Box<ast::Expr> {
-    fn into(self) -> Graph {
-        Graph(AstToDot::default().to_graph(self))
+impl IntoGraph for &Box<ast::Expr> {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(AstToDot::default().with_theme(theme.clone()).to_graph(self))
     }
 }
 
-impl Into<Graph> for &LogicalPlan<BindingsOp> {
-    fn into(self) -> Graph {
-        Graph(PlanToDot::default().to_graph(self))
+impl IntoGraph for &PlanDiff {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(PlanDiffToDot::with_theme(theme.clone()).to_graph(self))
+    }
+}
+
+impl IntoGraph for &AnalyzedPlan {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(PlanToDot::with_theme(theme.clone()).to_graph(self))
+    }
+}
+
+impl IntoGraph for &EvalGraph {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(PlanToDot::with_theme(theme.clone()).to_graph(self))
+    }
+}
+
+impl IntoGraph for &LogicalPlan<BindingsOp> {
+    fn into_graph(self, theme: &Theme) -> Graph {
+        Graph(PlanToDot::with_theme(theme.clone()).to_graph(self))
     }
 }
 
 /// FFI to graphviz-sys to convert a dot-formatted graph into the specified text format.
+#[cfg(feature = "graphviz")]
 #[inline]
-fn render_to_string<T>(format: GraphVizFormat, data: T) -> String
+fn render_to_string<T>(format: GraphVizFormat, theme: &Theme, data: T) -> String
 where
-    T: Into<Graph>,
+    T: IntoGraph,
 {
-    let Graph(graph_str) = data.into();
+    let Graph(graph_str) = data.into_graph(theme);
     String::from_utf8(gv_render(format, graph_str)).expect("valid utf8")
 }
 
-/// Convert an AST into an attributed dot graph.
+/// Convert an AST into a dot graph, attributed with positions by the `graphviz` layout.
 #[inline]
-pub fn to_dot<T>(data: T) -> String
+pub fn to_dot<T>(layout: Layout, theme: &Theme, data: T) -> String
 where
-    T: Into<Graph>,
+    T: IntoGraph,
 {
-    render_to_string(GraphVizFormat::Dot, data)
+    match layout {
+        #[cfg(feature = "graphviz")]
+        Layout::Graphviz => render_to_string(GraphVizFormat::Dot, theme, data),
+        Layout::Layered => data.into_graph(theme).0,
+    }
 }
 
 /// Convert an AST into a pretty-printed dot graph.
+#[cfg(feature = "graphviz")]
 #[inline]
-pub fn to_pretty_dot<T>(data: T) -> String
+pub fn to_pretty_dot<T>(theme: &Theme, data: T) -> String
 where
-    T: Into<Graph>,
+    T: IntoGraph,
 {
-    render_to_string(GraphVizFormat::Canon, data)
+    render_to_string(GraphVizFormat::Canon, theme, data)
 }
 
-/// Convert an AST into a graphviz svg.
+/// Convert an AST into a Mermaid flowchart.
 #[inline]
-pub fn to_svg<T>(data: T) -> String
+pub fn to_mermaid<T>(data: T) -> String
+where
+    T: IntoGraph,
+{
+    mermaid::to_mermaid(&data.into_graph(&Theme::default()).0)
+}
+
+/// Convert an AST into an svg using the given layout.
+#[inline]
+pub fn to_svg<T>(layout: Layout, theme: &Theme, data: T) -> Result<String, CLIErrors>
+where
+    T: IntoGraph,
+{
+    graph_to_svg(layout, &data.into_graph(theme))
+}
+
+fn graph_to_svg(layout: Layout, Graph(graph_str): &Graph) -> Result<String, CLIErrors> {
+    match layout {
+        #[cfg(feature = "graphviz")]
+        Layout::Graphviz => Ok(String::from_utf8(gv_render(
+            GraphVizFormat::Svg,
+            graph_str.clone(),
+        ))
+        .expect("valid utf8")),
+        Layout::Layered => Ok(layout::to_svg(graph_str)?),
+    }
+}
+
+/// Convert a parsed query's AST into an interactive html page, showing its graph alongside the
+/// query's text.
+pub fn ast_to_html(layout: Layout, theme: &Theme, parsed: &Parsed) -> Result<String, CLIErrors> {
+    let graph = parsed.into_graph(theme);
+    let svg = graph_to_svg(layout, &graph)?;
+    Ok(html::explorer(
+        "PartiQL AST",
+        theme,
+        &graph.0,
+        &svg,
+        parsed,
+        Subtree::Targets,
+    ))
+}
+
+/// Convert a plan, or an analyzed plan, into an interactive html page, showing its graph alongside
+/// the text of the query it was planned from.
+pub fn plan_to_html<T>(
+    layout: Layout,
+    theme: &Theme,
+    plan: T,
+    parsed: &Parsed,
+) -> Result<String, CLIErrors>
 where
-    T: Into<Graph>,
+    T: IntoGraph,
 {
-    render_to_string(GraphVizFormat::Svg, data)
+    let graph = plan.into_graph(theme);
+    let svg = graph_to_svg(layout, &graph)?;
+    Ok(html::explorer(
+        "PartiQL plan",
+        theme,
+        &graph.0,
+        &svg,
+        parsed,
+        Subtree::Sources,
+    ))
 }
 
-/// Convert an AST into a graphviz svg and render it to png.
-pub fn to_png<T>(data: T) -> Vec<u8>
+/// Convert an AST into an svg and render it to png.
+pub fn to_png<T>(layout: Layout, theme: &Theme, data: T) -> Result<Vec<u8>, CLIErrors>
 where
-    T: Into<Graph>,
+    T: IntoGraph,
 {
-    let svg_data = to_svg(data);
+    svg_to_png(&to_svg(layout, theme, data)?, 1.0)
+}
 
+/// Rasterize an svg to png, with its size multiplied by `scale`.
+fn svg_to_png(svg_data: &str, scale: f32) -> Result<Vec<u8>, CLIErrors> {
     let mut opt = usvg::Options::default();
+    let fontdb = opt.fontdb_mut();
+    fontdb.load_system_fonts();
+    // graphs use `serif`; fall back to any installed face if its default family is missing
+    let serif = usvg::fontdb::Query {
+        families: &[usvg::fontdb::Family::Serif],
+        ..Default::default()
+    };
+    if fontdb.query(&serif).is_none() {
+        let fallback = fontdb
+            .faces()
+            .find_map(|face| face.families.first())
+            .map(|(family, _)| family.clone());
+        if let Some(family) = fallback {
+            fontdb.set_serif_family(family);
+        }
+    }
 
-    let rtree = usvg::Tree::from_data(svg_data.as_bytes(), &opt).unwrap();
-    let pixmap_size = rtree.size().to_int_size();
-    let mut pixmap = tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height()).unwrap();
-    resvg::render(&rtree, Transform::default(), &mut pixmap.as_mut());
-    pixmap.encode_png().expect("png encoding failed")
+    let rtree =
+        usvg::Tree::from_data(svg_data.as_bytes(), &opt).map_err(|err| CLIError::RenderError {
+            msg: format!("invalid svg: {err}"),
+        })?;
+    let size = rtree.size().to_int_size();
+    let pixmap_size = size.scale_by(scale).unwrap_or(size);
+    let mut pixmap =
+        tiny_skia::Pixmap::new(pixmap_size.width(), pixmap_size.height()).ok_or_else(|| {
+            CLIError::RenderError {
+                msg: format!("can't rasterize an image of {size:?}"),
+            }
+        })?;
+    let transform = Transform::from_scale(
+        pixmap_size.width() as f32 / size.width() as f32,
+        pixmap_size.height() as f32 / size.height() as f32,
+    );
+    resvg::render(&rtree, transform, &mut pixmap.as_mut());
+    Ok(pixmap.encode_png().expect("png encoding failed"))
 }
 
-/// Convert an AST into a graphviz svg and render it to png, then display in the console.
-pub fn display<T>(data: T)
+/// How the `display` format shows rendered graphs in the terminal.
+#[derive(Debug, Clone)]
+pub struct DisplayOptions {
+    pub protocol: ImageProtocol,
+    /// Maximum width of the image, in terminal columns
+    pub max_width: Option<u32>,
+    /// Maximum height of the image, in terminal rows
+    pub max_height: Option<u32>,
+    /// Multiplier of the rendered image's size
+    pub scale: f32,
+}
+
+impl Default for DisplayOptions {
+    fn default() -> Self {
+        DisplayOptions {
+            protocol: ImageProtocol::Auto,
+            max_width: None,
+            max_height: None,
+            scale: 1.0,
+        }
+    }
+}
+
+impl DisplayOptions {
+    /// Reads the `[display]` section of a configuration, defaulting any missing settings.
+    pub fn from_config(config: &Config) -> Self {
+        let mut options = DisplayOptions::default();
+        if let Ok(protocol) = config.get_string("display.protocol") {
+            if let Ok(protocol) = ImageProtocol::from_str(&protocol, true) {
+                options.protocol = protocol;
+            }
+        }
+        if let Ok(max_width) = config.get_int("display.max_width") {
+            options.max_width = u32::try_from(max_width).ok();
+        }
+        if let Ok(max_height) = config.get_int("display.max_height") {
+            options.max_height = u32::try_from(max_height).ok();
+        }
+        if let Ok(scale) = config.get_float("display.scale") {
+            options.scale = scale as f32;
+        }
+        options
+    }
+
+    /// Overrides the options with any set by command line flags.
+    pub fn with_args(self, args: &DisplayArgs) -> Self {
+        DisplayOptions {
+            protocol: args.protocol.unwrap_or(self.protocol),
+            max_width: args.max_width.or(self.max_width),
+            max_height: args.max_height.or(self.max_height),
+            scale: args.scale.unwrap_or(self.scale),
+        }
+    }
+
+    /// The protocol to display images with, or `None` if they should be shown as text instead.
+    fn resolve_protocol(&self) -> Option<ImageProtocol> {
+        match self.protocol {
+            ImageProtocol::Text => None,
+            ImageProtocol::Auto => {
+                // escape sequences written to a file or pipe, or to a terminal that doesn't
+                // understand them, only show up as garbage
+                if !std::io::stdout().is_terminal() {
+                    None
+                } else if viuer::get_kitty_support() != viuer::KittySupport::None {
+                    Some(ImageProtocol::Kitty)
+                } else if viuer::is_iterm_supported() {
+                    Some(ImageProtocol::Iterm)
+                } else if viuer::is_sixel_supported() {
+                    Some(ImageProtocol::Sixel)
+                } else {
+                    None
+                }
+            }
+            protocol => Some(protocol),
+        }
+    }
+
+    /// The width and height to print an image of `img_width` by `img_height` pixels at, in
+    /// terminal cells, to fit it within the maximum width and height.
+    fn fit(&self, img_width: u32, img_height: u32) -> (Option<u32>, Option<u32>) {
+        if self.max_width.is_none() && self.max_height.is_none() {
+            // `viuer` fits images to the terminal by default
+            return (None, None);
+        }
+        let (term_width, term_height) = viuer::terminal_size();
+        let max_width = self
+            .max_width
+            .unwrap_or(u32::MAX)
+            .min(term_width as u32)
+            .max(1);
+        let max_height = self
+            .max_height
+            .unwrap_or(u32::MAX)
+            .min((term_height as u32).saturating_sub(1))
+            .max(1);
+        // `viuer` only preserves the aspect ratio when given a single bound, so pass whichever
+        // the image reaches first; terminal cells are about twice as tall as they are wide
+        let (img_width, img_height) = (img_width as u64, img_height as u64);
+        if img_width * 2 * max_height as u64 >= img_height * max_width as u64 {
+            (Some(max_width), None)
+        } else {
+            (None, Some(max_height))
+        }
+    }
+}
+
+/// Display a rendered graph directly in the terminal, using the image protocol of `options`.
+///
+/// Returns `false`, without printing anything, when the graph should be shown as text instead:
+/// when that is the chosen protocol, or when automatic selection finds no graphics support.
+pub fn display<T>(
+    layout: Layout,
+    theme: &Theme,
+    options: &DisplayOptions,
+    data: T,
+) -> Result<bool, CLIErrors>
 where
-    T: Into<Graph>,
+    T: IntoGraph,
 {
-    let png = to_png(data);
+    let Some(protocol) = options.resolve_protocol() else {
+        return Ok(false);
+    };
+    let png = svg_to_png(&to_svg(layout, theme, data)?, options.scale)?;
+    let img = image::load_from_memory(&png).expect("png loading failed.");
+    let (width, height) = options.fit(img.width(), img.height());
 
     let conf = viuer::Config {
         absolute_offset: false,
         transparent: true,
-        use_sixel: false,
+        width,
+        height,
+        use_kitty: protocol == ImageProtocol::Kitty,
+        use_iterm: protocol == ImageProtocol::Iterm,
+        use_sixel: protocol == ImageProtocol::Sixel,
         ..Default::default()
     };
 
-    let img = image::load_from_memory(&png).expect("png loading failed.");
     viuer::print(&img, &conf).expect("Image printing failed.");
+    Ok(true)
 }
@@ -0,0 +1,409 @@
+//! Warnings about a query's data. In permissive mode, evaluation doesn't fail on a type mismatch or
+//! on a path to an attribute that doesn't exist; the expression evaluates to MISSING instead. The
+//! checks here look for the MISSING values this leaves in a query's result, and for the type
+//! mismatches that caused them. Errors that permissive evaluation records rather than failing on
+//! are warnings too.
+
+use crate::args::ErrorFormat;
+use crate::error::{evaluation_errors, underline, CLIError, ErrorEntry, ErrorRecord};
+use crate::evaluate::{projected_columns, Compiler};
+use miette::{Diagnostic, LabeledSpan, Severity, SourceCode};
+use partiql_ast::ast;
+use partiql_ast::visit::{Traverse, Visit, Visitor};
+use partiql_ast_passes::name_resolver::Symbol;
+use partiql_common::node::NodeId;
+use partiql_common::syntax::location::{BytePosition, Location};
+use partiql_eval::env::basic::MapBindings;
+use partiql_eval::error::EvaluationError;
+use partiql_logical::{BindingsOp, LogicalPlan};
+use partiql_parser::Parsed;
+use partiql_value::{BindingsName, Value};
+use thiserror::Error;
+
+#[derive(Debug, Error, Diagnostic)]
+#[error("Warnings for query `{query}`")]
+#[diagnostic(severity(Warning))]
+pub struct CLIWarnings {
+    query: String,
+    #[related]
+    related: Vec<CLIWarning>,
+}
+
+#[derive(Debug, Error)]
+pub enum CLIWarning {
+    #[error("`{column}` is MISSING in {missing} of {rows} rows")]
+    MissingColumn {
+        src: String,
+        column: String,
+        missing: usize,
+        rows: usize,
+        /// Whether strict evaluation found a type mismatch
+        mismatched: bool,
+        loc: Option<Location<BytePosition>>,
+    },
+
+    #[error("{missing} of {values} values are MISSING")]
+    MissingValues {
+        src: String,
+        missing: usize,
+        values: usize,
+        mismatched: bool,
+    },
+
+    #[error("The query evaluated to MISSING")]
+    MissingResult { src: String, mismatched: bool },
+
+    #[error("Type mismatch evaluated as MISSING: {msg}")]
+    TypeMismatch { src: String, msg: String },
+
+    /// An error that permissive evaluation recorded rather than failing on, or that strict
+    /// evaluation found other than a type mismatch
+    #[error(transparent)]
+    Evaluation(CLIError),
+}
+
+impl Diagnostic for CLIWarning {
+    fn code<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let code = match self {
+            CLIWarning::MissingColumn { .. } => "partiql::warning::missing_column",
+            CLIWarning::MissingValues { .. } => "partiql::warning::missing_values",
+            CLIWarning::MissingResult { .. } => "partiql::warning::missing_result",
+            CLIWarning::TypeMismatch { .. } => "partiql::warning::type_mismatch",
+            CLIWarning::Evaluation(err) => return err.code(),
+        };
+        Some(Box::new(code))
+    }
+
+    fn severity(&self) -> Option<Severity> {
+        Some(Severity::Warning)
+    }
+
+    fn help<'a>(&'a self) -> Option<Box<dyn std::fmt::Display + 'a>> {
+        let help = match self {
+            CLIWarning::MissingColumn {
+                mismatched: true, ..
+            }
+            | CLIWarning::MissingValues {
+                mismatched: true, ..
+            }
+            | CLIWarning::MissingResult {
+                mismatched: true, ..
+            } => {
+                "a type mismatch (see below) or a path to an attribute that doesn't exist evaluates to MISSING"
+            }
+            CLIWarning::MissingColumn { .. } => {
+                "no type mismatches were found, so a path here likely refers to an attribute that some rows don't have; a MISSING attribute is left out of its row"
+            }
+            CLIWarning::MissingValues { .. } | CLIWarning::MissingResult { .. } => {
+                "no type mismatches were found, so a path likely refers to an attribute that doesn't exist"
+            }
+            CLIWarning::TypeMismatch { .. } => {
+                "an operator or function given arguments of the wrong type or number evaluates to MISSING; `found` marks the first mismatched argument as MISSING, and only the first mismatch is reported"
+            }
+            CLIWarning::Evaluation(err) => return err.help(),
+        };
+        Some(Box::new(help))
+    }
+
+    fn source_code(&self) -> Option<&dyn SourceCode> {
+        match self {
+            CLIWarning::MissingColumn { src, .. }
+            | CLIWarning::MissingValues { src, .. }
+            | CLIWarning::MissingResult { src, .. }
+            | CLIWarning::TypeMismatch { src, .. } => Some(src),
+            CLIWarning::Evaluation(err) => err.source_code(),
+        }
+    }
+
+    fn labels(&self) -> Option<Box<dyn Iterator<Item = LabeledSpan> + '_>> {
+        match self {
+            CLIWarning::MissingColumn { loc, .. } => loc
+                .as_ref()
+                .map(|loc| Box::new(std::iter::once(underline(loc))) as Box<_>),
+            CLIWarning::Evaluation(err) => err.labels(),
+            _ => None,
+        }
+    }
+}
+
+impl CLIWarning {
+    /// The name of the kind of warning.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            CLIWarning::MissingColumn { .. } => "missing_column",
+            CLIWarning::MissingValues { .. } => "missing_values",
+            CLIWarning::MissingResult { .. } => "missing_result",
+            CLIWarning::TypeMismatch { .. } => "type_mismatch",
+            CLIWarning::Evaluation(err) => err.kind(),
+        }
+    }
+
+    /// What went wrong.
+    pub fn message(&self) -> String {
+        match self {
+            CLIWarning::Evaluation(err) => err.message(),
+            other => other.to_string(),
+        }
+    }
+}
+
+impl CLIWarnings {
+    /// The errors that permissive evaluation of `parsed` recorded rather than failing on.
+    pub fn recorded(parsed: &Parsed, errors: Vec<EvaluationError>) -> Self {
+        CLIWarnings {
+            query: parsed.text.to_string(),
+            related: evaluation_errors(parsed, errors)
+                .into_iter()
+                .map(CLIWarning::Evaluation)
+                .collect(),
+        }
+    }
+
+    /// Checks `result`, the permissive evaluation of `parsed`'s `plan` over `bindings`, for MISSING
+    /// values, then evaluates `plan` again in strict mode for the type mismatch that caused them.
+    ///
+    /// Strict evaluation is also how mismatches that leave no trace in the result are found, e.g.
+    /// in a `WHERE` clause, whose rows are dropped when it evaluates to MISSING. Its other errors
+    /// are warned about as they are, unless permissive evaluation recorded them already.
+    pub fn check(
+        &mut self,
+        compiler: &Compiler,
+        parsed: &Parsed,
+        plan: &LogicalPlan<BindingsOp>,
+        bindings: MapBindings<Value>,
+        result: &Value,
+    ) {
+        let src = parsed.text.to_string();
+        let (mismatches, others): (Vec<_>, Vec<_>) = compiler
+            .strict_errors(plan, bindings)
+            .into_iter()
+            .partition(is_mismatch);
+        let mismatches: Vec<_> = mismatches
+            .into_iter()
+            .map(|err| CLIWarning::TypeMismatch {
+                src: src.clone(),
+                msg: match err {
+                    EvaluationError::IllegalState(msg) => msg,
+                    err => err.to_string(),
+                },
+            })
+            .collect();
+        let mismatched = !mismatches.is_empty();
+        let recorded: Vec<_> = self.related.iter().map(|w| w.to_string()).collect();
+        let others: Vec<_> = evaluation_errors(parsed, others)
+            .into_iter()
+            .filter(|err| !recorded.contains(&err.to_string()))
+            .map(CLIWarning::Evaluation)
+            .collect();
+
+        let related = &mut self.related;
+        match (result, projected_columns(plan)) {
+            (Value::Missing, _) => related.push(CLIWarning::MissingResult {
+                src: src.clone(),
+                mismatched,
+            }),
+            (Value::Bag(_) | Value::List(_), Some(columns)) => {
+                let rows: Vec<_> = result
+                    .iter()
+                    .filter_map(|row| match row {
+                        Value::Tuple(tuple) => Some(tuple),
+                        _ => None,
+                    })
+                    .collect();
+                let items = ProjectItems::of(compiler, parsed);
+                for column in columns {
+                    let name = BindingsName::CaseSensitive(column.as_str().into());
+                    let missing = rows
+                        .iter()
+                        .filter(|row| matches!(row.get(&name), None | Some(Value::Missing)))
+                        .count();
+                    if missing > 0 {
+                        related.push(CLIWarning::MissingColumn {
+                            src: src.clone(),
+                            loc: items.location(&column),
+                            column,
+                            missing,
+                            rows: rows.len(),
+                            mismatched,
+                        });
+                    }
+                }
+            }
+            (Value::Bag(_) | Value::List(_), None) => {
+                let values = result.iter().count();
+                let missing = result
+                    .iter()
+                    .filter(|v| matches!(v, Value::Missing))
+                    .count();
+                if missing > 0 {
+                    related.push(CLIWarning::MissingValues {
+                        src: src.clone(),
+                        missing,
+                        values,
+                        mismatched,
+                    });
+                }
+            }
+            _ => {}
+        }
+        related.extend(mismatches);
+        related.extend(others);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.related.is_empty()
+    }
+
+    pub fn len(&self) -> usize {
+        self.related.len()
+    }
+}
+
+/// Whether a strict evaluation error is an operator or function's check of its arguments' types or
+/// number failing, which permissive evaluation turns into MISSING.
+fn is_mismatch(err: &EvaluationError) -> bool {
+    matches!(err, EvaluationError::IllegalState(msg)
+        if msg.starts_with("expected `(") || msg.starts_with("# of evaluated arguments"))
+}
+
+/// Writes `warnings` to stderr as rendered diagnostics, or as a record in `format`.
+pub fn report_warnings(format: ErrorFormat, warnings: CLIWarnings) {
+    let record = ErrorRecord {
+        query: Some(warnings.query.clone()),
+        message: warnings.to_string(),
+        list: "warnings",
+        errors: warnings
+            .related
+            .iter()
+            .map(|w| ErrorEntry::of(&warnings.query, w.kind(), w.message(), w))
+            .collect(),
+    };
+    match format {
+        ErrorFormat::Human => eprintln!("{:?}", miette::Report::new(warnings)),
+        ErrorFormat::Json => eprintln!("{}", record.to_json()),
+        ErrorFormat::Ion => record.eprint_ion(),
+    }
+}
+
+/// The items of a query's outermost `SELECT` list, by the name of the column each projects.
+struct ProjectItems<'a> {
+    parsed: &'a Parsed<'a>,
+    items: Vec<(String, NodeId)>,
+}
+
+impl<'a> ProjectItems<'a> {
+    fn of(compiler: &Compiler, parsed: &'a Parsed<'a>) -> Self {
+        let mut items = vec![];
+        if let Ok(registry) = compiler.resolve_names(parsed) {
+            let mut finder = ProjectItemFinder::default();
+            parsed.ast.visit(&mut finder);
+            // Columns are named as lowering names them, after their (possibly inferred) alias
+            items = finder
+                .items
+                .into_iter()
+                .filter_map(|id| {
+                    let name = match registry.aliases.get(&id)? {
+                        Symbol::Known(sym) => sym.value.clone(),
+                        Symbol::Unknown(id) => format!("_{id}"),
+                    };
+                    Some((name, id))
+                })
+                .collect();
+        }
+        ProjectItems { parsed, items }
+    }
+
+    fn location(&self, column: &str) -> Option<Location<BytePosition>> {
+        let (_, id) = self.items.iter().find(|(name, _)| name == column)?;
+        self.parsed.locations.get(id).cloned()
+    }
+}
+
+#[derive(Default)]
+struct ProjectItemFinder {
+    current: Option<NodeId>,
+    depth: usize,
+    items: Vec<NodeId>,
+    done: bool,
+}
+
+impl<'ast> Visitor<'ast> for ProjectItemFinder {
+    fn enter_ast_node(&mut self, id: NodeId) -> Traverse {
+        self.current = Some(id);
+        Traverse::Continue
+    }
+
+    fn enter_projection(&mut self, _projection: &'ast ast::Projection) -> Traverse {
+        self.depth += 1;
+        Traverse::Continue
+    }
+
+    fn exit_projection(&mut self, _projection: &'ast ast::Projection) -> Traverse {
+        // Only the outermost `SELECT` list is the result's
+        self.depth -= 1;
+        self.done |= self.depth == 0;
+        Traverse::Continue
+    }
+
+    fn enter_project_expr(&mut self, _project_expr: &'ast ast::ProjectExpr) -> Traverse {
+        if !self.done && self.depth == 1 {
+            self.items.extend(self.current);
+        }
+        Traverse::Continue
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// The kinds of the warnings about `query`, with its result checked if `check`.
+    fn warning_kinds(query: &str, check: bool) -> Vec<&'static str> {
+        let compiler = Compiler::default();
+        let parsed = compiler.parse(query).expect("parse");
+        let plan = compiler.plan(&parsed).expect("plan");
+        let eval = compiler.compile(&parsed, &plan).expect("compile");
+        let (evaluated, recorded) = compiler
+            .evaluate_recorded(&parsed, eval, MapBindings::default())
+            .expect("evaluate");
+        let mut warnings = CLIWarnings::recorded(&parsed, recorded);
+        if check {
+            let bindings = MapBindings::default();
+            warnings.check(&compiler, &parsed, &plan, bindings, &evaluated.result);
+        }
+        warnings.related.iter().map(CLIWarning::kind).collect()
+    }
+
+    #[test]
+    fn mismatches_are_only_checked_for_on_request() {
+        let query = "SELECT a.x + 1 AS y FROM << {'x': 1}, {'x': 'a'} >> AS a";
+        assert_eq!(warning_kinds(query, false), Vec::<&str>::new());
+        assert_eq!(
+            warning_kinds(query, true),
+            vec!["missing_column", "type_mismatch"]
+        );
+    }
+
+    #[test]
+    fn recorded_errors_keep_their_kind_and_are_reported_once() {
+        let query = "SELECT * FROM << {'a': 1} >> AS l FULL JOIN << {'a': 1} >> AS r ON l.a = r.a";
+        let kinds = vec!["evaluation_not_yet_implemented"];
+        assert_eq!(warning_kinds(query, false), kinds);
+        assert_eq!(warning_kinds(query, true), kinds);
+    }
+
+    #[test]
+    fn only_argument_checks_are_mismatches() {
+        let illegal = |msg: &str| EvaluationError::IllegalState(msg.to_string());
+        assert!(is_mismatch(&illegal("expected `(int)`, found `(MISSING)`")));
+        assert!(is_mismatch(&illegal(
+            "# of evaluated arguments (1) does not match expectation 2"
+        )));
+        assert!(!is_mismatch(&illegal(
+            "Left side of FROM source should result in a bag of bindings"
+        )));
+        assert!(!is_mismatch(&EvaluationError::NotYetImplemented(
+            "GROUP PARTIAL".to_string()
+        )));
+    }
+}